@@ -7,6 +7,10 @@ use chain_crypto::{
 };
 use ed25519_bip32::{DerivationError, DerivationScheme};
 use hex::FromHexError;
+use jormungandr_lib::{
+    crypto::password::{self, DecryptionError},
+    interfaces::NodeSecret,
+};
 use rand::{rngs::OsRng, SeedableRng};
 use rand_chacha::ChaChaRng;
 use std::{
@@ -15,6 +19,10 @@ use std::{
 };
 use structopt::{clap::arg_enum, StructOpt};
 use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// environment variable holding the passphrase of the encrypted node secrets
+const PASSPHRASE_ENV_VAR: &str = "JORMUNGANDR_SECRET_PASSPHRASE";
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -53,6 +61,14 @@ pub enum Error {
     SignatureVerification,
     #[error("failed to derive from BIP32 public key")]
     Derivation(#[from] DerivationError),
+    #[error("invalid node secret")]
+    InvalidSecret(#[from] serde_yaml::Error),
+    #[error("cannot read the passphrase")]
+    Passphrase(#[source] std::io::Error),
+    #[error("the passphrases do not match")]
+    PassphraseMismatch,
+    #[error("cannot decrypt the node secret")]
+    Decryption(#[from] DecryptionError),
     #[error("ed25519bip32 key expected, signature bech32 has invalid HRP: '{actual_hrp}', expected: '{public_hrp}' or '{private_hrp}'")]
     UnexpectedBip32Bech32Hrp {
         actual_hrp: String,
@@ -78,6 +94,10 @@ pub enum Key {
     Verify(Verify),
     /// derive a child key from a ed25519bip32 parent key
     Derive(Derive),
+    /// encrypt a node secret file with a passphrase
+    EncryptSecret(EncryptSecret),
+    /// decrypt a node secret file encrypted with `encrypt-secret`
+    DecryptSecret(DecryptSecret),
 }
 
 #[derive(StructOpt, Debug)]
@@ -187,6 +207,50 @@ pub struct Derive {
     child_key: OutputFile,
 }
 
+#[derive(StructOpt, Debug)]
+pub struct EncryptSecret {
+    /// the node secret file (in YAML format) to encrypt
+    ///
+    /// if no value passed, the node secret will be read from the
+    /// standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    passphrase: PassphraseFile,
+
+    /// output the encrypted node secret to the given file or to stdout if
+    /// not provided
+    #[structopt(name = "OUTPUT_FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct DecryptSecret {
+    /// the encrypted node secret file
+    ///
+    /// if no value passed, the encrypted node secret will be read from the
+    /// standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    passphrase: PassphraseFile,
+
+    /// output the node secret to the given file or to stdout if not provided
+    #[structopt(name = "OUTPUT_FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+struct PassphraseFile {
+    /// read the passphrase from the given file. If not provided, the
+    /// passphrase is read from the `JORMUNGANDR_SECRET_PASSPHRASE`
+    /// environment variable or prompted for
+    #[structopt(long = "passphrase-file")]
+    passphrase_file: Option<PathBuf>,
+}
+
 #[derive(StructOpt, Debug)]
 struct OutputFile {
     /// output the key to the given file or to stdout if not provided
@@ -194,6 +258,36 @@ struct OutputFile {
     output: Option<PathBuf>,
 }
 
+impl PassphraseFile {
+    /// get the passphrase from the file, the environment or the terminal.
+    /// When prompting for a new passphrase, it is asked for twice.
+    fn read(&self, confirm: bool) -> Result<Zeroizing<String>, Error> {
+        if let Some(path) = &self.passphrase_file {
+            let passphrase =
+                Zeroizing::new(std::fs::read_to_string(path).map_err(Error::Passphrase)?);
+            return Ok(Zeroizing::new(
+                passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned(),
+            ));
+        }
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+            return Ok(Zeroizing::new(passphrase));
+        }
+        let passphrase = Zeroizing::new(
+            rpassword::read_password_from_tty(Some("Passphrase: ")).map_err(Error::Passphrase)?,
+        );
+        if confirm {
+            let confirmation = Zeroizing::new(
+                rpassword::read_password_from_tty(Some("Confirm passphrase: "))
+                    .map_err(Error::Passphrase)?,
+            );
+            if *passphrase != *confirmation {
+                return Err(Error::PassphraseMismatch);
+            }
+        }
+        Ok(passphrase)
+    }
+}
+
 impl OutputFile {
     fn open(&self) -> Result<impl Write, Error> {
         io::open_file_write(&self.output).map_err(|source| Error::InvalidOutput {
@@ -224,6 +318,8 @@ impl Key {
             Key::Sign(args) => args.exec(),
             Key::Verify(args) => args.exec(),
             Key::Derive(args) => args.exec(),
+            Key::EncryptSecret(args) => args.exec(),
+            Key::DecryptSecret(args) => args.exec(),
         }
     }
 }
@@ -399,6 +495,38 @@ impl Derive {
     }
 }
 
+impl EncryptSecret {
+    fn exec(self) -> Result<(), Error> {
+        let mut secret = Zeroizing::new(Vec::new());
+        io::open_file_read(&self.input)?.read_to_end(&mut secret)?;
+        // make sure a valid node secret is encrypted
+        let _: NodeSecret = serde_yaml::from_slice(&secret)?;
+        let passphrase = self.passphrase.read(true)?;
+        let encrypted = password::encrypt(passphrase.as_bytes(), &secret, &mut OsRng);
+        write_output(&self.output, &encrypted)
+    }
+}
+
+impl DecryptSecret {
+    fn exec(self) -> Result<(), Error> {
+        let mut encrypted = Vec::new();
+        io::open_file_read(&self.input)?.read_to_end(&mut encrypted)?;
+        let passphrase = self.passphrase.read(false)?;
+        let secret = password::decrypt(passphrase.as_bytes(), &encrypted)?;
+        let _: NodeSecret = serde_yaml::from_slice(&secret)?;
+        write_output(&self.output, &secret)
+    }
+}
+
+fn write_output(path: &Option<PathBuf>, data: &[u8]) -> Result<(), Error> {
+    let mut output = io::open_file_write(path).map_err(|source| Error::InvalidOutput {
+        source,
+        path: path.clone().unwrap_or_default(),
+    })?;
+    output.write_all(data)?;
+    Ok(())
+}
+
 fn read_hex<P: AsRef<Path>>(path: &Option<P>) -> Result<Vec<u8>, Error> {
     hex::decode(io::read_line(path)?).map_err(Into::into)
 }
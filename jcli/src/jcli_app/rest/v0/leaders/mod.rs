@@ -1,5 +1,6 @@
 use crate::jcli_app::rest::Error;
 use crate::jcli_app::utils::{io, DebugFlag, HostAddr, OutputFormat, RestApiSender};
+use jormungandr_lib::interfaces::LeadershipScheduleEntry;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -39,6 +40,25 @@ pub enum Leaders {
 
     /// Leadership log operations
     Logs(GetLogs),
+
+    /// Get the slots the leaders are elected for in an epoch
+    Schedule {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+        /// the epoch to get the schedule of, the epoch of the tip if not
+        /// provided. The schedule of the next epoch is only available once
+        /// its stake distribution is final.
+        #[structopt(short, long)]
+        epoch: Option<u32>,
+        /// print the schedule as CSV, one slot per line. The output format
+        /// is ignored.
+        #[structopt(long)]
+        csv: bool,
+    },
 }
 
 #[derive(StructOpt)]
@@ -70,6 +90,13 @@ impl Leaders {
                 debug,
                 output_format,
             }) => get_logs(addr, debug, output_format),
+            Leaders::Schedule {
+                addr,
+                debug,
+                output_format,
+                epoch,
+                csv,
+            } => get_schedule(addr, debug, output_format, epoch, csv),
         }
     }
 }
@@ -118,3 +145,35 @@ fn get_logs(addr: HostAddr, debug: DebugFlag, output_format: OutputFormat) -> Re
     println!("{}", formatted);
     Ok(())
 }
+
+fn get_schedule(
+    addr: HostAddr,
+    debug: DebugFlag,
+    output_format: OutputFormat,
+    epoch: Option<u32>,
+    csv: bool,
+) -> Result<(), Error> {
+    let url = addr
+        .with_segments(&["v0", "leaders", "schedule"])?
+        .into_url();
+    let builder = reqwest::blocking::Client::new()
+        .get(url)
+        .query(&[("epoch", epoch)]);
+    let response = RestApiSender::new(builder, &debug).send()?;
+    response.ok_response()?;
+    if csv {
+        let schedule: Vec<LeadershipScheduleEntry> = response.body().json()?;
+        println!("enclave_leader_id,scheduled_at_date,scheduled_at_time");
+        for entry in schedule {
+            println!(
+                "{},{},{}",
+                entry.enclave_leader_id, entry.scheduled_at_date, entry.scheduled_at_time
+            );
+        }
+    } else {
+        let schedule = response.body().json_value()?;
+        let formatted = output_format.format_json(schedule)?;
+        println!("{}", formatted);
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use crate::jcli_app::rest::Error;
+use crate::jcli_app::utils::{DebugFlag, HostAddr, OutputFormat, RestApiSender};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub enum Mempool {
+    /// Get the summary of the mempool and its pending fragments, oldest first
+    Get {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+        /// Number of pending fragments to skip, default 0
+        #[structopt(short, long)]
+        offset: Option<usize>,
+        /// Maximum number of pending fragments, at most 1000, default 100
+        #[structopt(short, long)]
+        limit: Option<usize>,
+    },
+}
+
+impl Mempool {
+    pub fn exec(self) -> Result<(), Error> {
+        let Mempool::Get {
+            addr,
+            debug,
+            output_format,
+            offset,
+            limit,
+        } = self;
+        let url = addr.with_segments(&["v0", "mempool"])?.into_url();
+        let builder = reqwest::blocking::Client::new()
+            .get(url)
+            .query(&[("offset", offset), ("limit", limit)]);
+        let response = RestApiSender::new(builder, &debug).send()?;
+        response.ok_response()?;
+        let status = response.body().json_value()?;
+        let formatted = output_format.format_json(status)?;
+        println!("{}", formatted);
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ mod account;
 mod block;
 mod diagnostic;
 mod leaders;
+mod mempool;
 mod message;
 mod network;
 mod node;
@@ -27,6 +28,8 @@ pub enum V0 {
     Block(block::Block),
     /// Node leaders operations
     Leaders(leaders::Leaders),
+    /// Mempool information
+    Mempool(mempool::Mempool),
     /// Message sending
     Message(message::Message),
     /// Network information
@@ -61,6 +64,7 @@ impl V0 {
             V0::Account(account) => account.exec(),
             V0::Block(block) => block.exec(),
             V0::Leaders(leaders) => leaders.exec(),
+            V0::Mempool(mempool) => mempool.exec(),
             V0::Message(message) => message.exec(),
             V0::Network(network) => network.exec(),
             V0::Node(node) => node.exec(),
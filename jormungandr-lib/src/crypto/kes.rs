@@ -0,0 +1,103 @@
+//! evolution of the KES keys of the stake pools
+//!
+//! A KES key signs for a single period at a time and is evolved forward
+//! from one period to the next. The key is updated in place so the state
+//! of the past periods cannot be used to sign again.
+
+use chain_crypto::{KeyEvolvingSignatureAlgorithm, SecretKey, SumEd25519_12};
+use thiserror::Error;
+
+/// number of periods of the `SumEd25519_12` KES keys
+pub const KES_NUM_PERIODS: u32 = 1 << 12;
+
+/// the periods of a KES key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KesPeriods {
+    /// the period the key is currently at
+    pub current: u32,
+    /// the number of periods the key can still be evolved to
+    pub remaining: u32,
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum KesError {
+    #[error("the KES key is at period {current}, it cannot sign for the past period {requested}")]
+    PeriodPassed { current: u32, requested: u32 },
+    #[error("the KES key expired, period {requested} is past its last period {last}")]
+    Expired { last: u32, requested: u32 },
+}
+
+/// the period the key is currently at
+pub fn period(key: &SecretKey<SumEd25519_12>) -> u32 {
+    SumEd25519_12::get_period(key.leak_secret()) as u32
+}
+
+/// the current period of the key and the number of periods left
+pub fn periods(key: &SecretKey<SumEd25519_12>) -> KesPeriods {
+    let current = period(key);
+    KesPeriods {
+        current,
+        remaining: KES_NUM_PERIODS - 1 - current,
+    }
+}
+
+/// Evolve the key up to the given period. The key is updated in place, the
+/// state of the previous periods is not kept.
+pub fn evolve(key: &mut SecretKey<SumEd25519_12>, period: u32) -> Result<(), KesError> {
+    let last = KES_NUM_PERIODS - 1;
+    if period > last {
+        return Err(KesError::Expired {
+            last,
+            requested: period,
+        });
+    }
+    let current = self::period(key);
+    if period < current {
+        return Err(KesError::PeriodPassed {
+            current,
+            requested: period,
+        });
+    }
+    for _ in current..period {
+        let updated = SumEd25519_12::update(key.leak_secret_mut());
+        // the period has been checked against the last period of the key
+        debug_assert!(updated);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kes_key_evolves_forward_only() {
+        let mut key = SecretKey::<SumEd25519_12>::generate(rand::rngs::OsRng);
+        assert_eq!(period(&key), 0);
+
+        evolve(&mut key, 3).unwrap();
+        assert_eq!(period(&key), 3);
+
+        evolve(&mut key, 3).unwrap();
+        assert_eq!(period(&key), 3);
+
+        assert!(matches!(
+            evolve(&mut key, 2),
+            Err(KesError::PeriodPassed {
+                current: 3,
+                requested: 2
+            })
+        ));
+        assert!(matches!(
+            evolve(&mut key, KES_NUM_PERIODS),
+            Err(KesError::Expired { .. })
+        ));
+        assert_eq!(
+            periods(&key),
+            KesPeriods {
+                current: 3,
+                remaining: KES_NUM_PERIODS - 4
+            }
+        );
+    }
+}
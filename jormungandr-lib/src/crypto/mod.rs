@@ -18,6 +18,16 @@
 //! `serde_json`) it will give a bech32 encoding. But utilising
 //! `Display` will provide an hexadecimal encoding version of the key.
 //!
+//! # KES keys
+//!
+//! The evolution of the key evolving signature keys of the stake pools
+//! from one period to the next.
+//!
+//! # Password encryption
+//!
+//! The encryption of secret data, like the node secret files, with a key
+//! derived from a passphrase.
+//!
 //! # Account keys
 //!
 //! The proper type for the account management and interfaces.
@@ -27,5 +37,7 @@
 
 pub mod account;
 pub mod hash;
+pub mod kes;
 pub mod key;
+pub mod password;
 pub(crate) mod serde;
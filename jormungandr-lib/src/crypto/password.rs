@@ -0,0 +1,173 @@
+//! encryption of secret data with a passphrase
+//!
+//! The key is derived from the passphrase with PBKDF2-HMAC-SHA512 and a
+//! random salt, the data is encrypted and authenticated with
+//! ChaCha20-Poly1305. The encrypted data is laid out as:
+//!
+//! ```text
+//! | magic (4) | version (1) | iterations (4, BE) | salt (16) | nonce (12) | ciphertext | tag (16) |
+//! ```
+//!
+//! The header (everything before the ciphertext) is authenticated along
+//! with the ciphertext. The decrypted data and the derived keys are zeroed
+//! when dropped.
+
+use cryptoxide::{chacha20poly1305::ChaCha20Poly1305, hmac::Hmac, pbkdf2::pbkdf2, sha2::Sha512};
+use rand_core::{CryptoRng, RngCore};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"JSEC";
+const VERSION: u8 = 1;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+const TAG_SIZE: usize = 16;
+const HEADER_SIZE: usize = MAGIC.len() + 1 + 4 + SALT_SIZE + NONCE_SIZE;
+
+/// number of iterations of the key derivation function
+pub const ITERATIONS: u32 = 100_000;
+/// maximum number of iterations of the key derivation function accepted
+/// when decrypting, so the header of the data cannot stall the decryption
+pub const MAX_ITERATIONS: u32 = 10 * ITERATIONS;
+
+#[derive(Debug, Error)]
+pub enum DecryptionError {
+    #[error("the data is not encrypted")]
+    NotEncrypted,
+    #[error("unsupported encryption format version {0}")]
+    UnsupportedVersion(u8),
+    #[error("the encrypted data is truncated")]
+    Truncated,
+    #[error("{0} key derivation iterations, the maximum is {}", MAX_ITERATIONS)]
+    TooManyIterations(u32),
+    #[error("no key derivation iterations")]
+    NoIterations,
+    #[error("invalid passphrase or corrupted data")]
+    Authentication,
+}
+
+/// check if the data starts like data encrypted with `encrypt`
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// encrypt the data with a key derived from the passphrase
+pub fn encrypt<R: RngCore + CryptoRng>(passphrase: &[u8], data: &[u8], rng: &mut R) -> Vec<u8> {
+    let mut salt = [0; SALT_SIZE];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0; NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+
+    let mut encrypted = Vec::with_capacity(HEADER_SIZE + data.len() + TAG_SIZE);
+    encrypted.extend_from_slice(MAGIC);
+    encrypted.push(VERSION);
+    encrypted.extend_from_slice(&ITERATIONS.to_be_bytes());
+    encrypted.extend_from_slice(&salt);
+    encrypted.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, ITERATIONS);
+    let mut cipher = ChaCha20Poly1305::new(&key[..], &nonce, &encrypted);
+    let mut ciphertext = vec![0; data.len()];
+    let mut tag = [0; TAG_SIZE];
+    cipher.encrypt(data, &mut ciphertext, &mut tag);
+
+    encrypted.extend_from_slice(&ciphertext);
+    encrypted.extend_from_slice(&tag);
+    encrypted
+}
+
+/// decrypt data encrypted with `encrypt`
+pub fn decrypt(passphrase: &[u8], encrypted: &[u8]) -> Result<Zeroizing<Vec<u8>>, DecryptionError> {
+    if !is_encrypted(encrypted) {
+        return Err(DecryptionError::NotEncrypted);
+    }
+    if encrypted.len() < HEADER_SIZE + TAG_SIZE {
+        return Err(DecryptionError::Truncated);
+    }
+    let (header, rest) = encrypted.split_at(HEADER_SIZE);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+
+    let version = header[MAGIC.len()];
+    if version != VERSION {
+        return Err(DecryptionError::UnsupportedVersion(version));
+    }
+    let (iterations, rest) = header[MAGIC.len() + 1..].split_at(4);
+    let mut iterations_bytes = [0; 4];
+    iterations_bytes.copy_from_slice(iterations);
+    let iterations = u32::from_be_bytes(iterations_bytes);
+    if iterations == 0 {
+        return Err(DecryptionError::NoIterations);
+    }
+    if iterations > MAX_ITERATIONS {
+        return Err(DecryptionError::TooManyIterations(iterations));
+    }
+    let (salt, nonce) = rest.split_at(SALT_SIZE);
+
+    let key = derive_key(passphrase, salt, iterations);
+    let mut cipher = ChaCha20Poly1305::new(&key[..], nonce, header);
+    let mut data = Zeroizing::new(vec![0; ciphertext.len()]);
+    if cipher.decrypt(ciphertext, &mut data[..], tag) {
+        Ok(data)
+    } else {
+        Err(DecryptionError::Authentication)
+    }
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> Zeroizing<[u8; KEY_SIZE]> {
+    let mut mac = Hmac::new(Sha512::new(), passphrase);
+    let mut key = Zeroizing::new([0; KEY_SIZE]);
+    pbkdf2(&mut mac, salt, iterations, &mut key[..]);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn encrypted_data_roundtrip() {
+        let data = b"genesis:\n  sig_key: kes25519-12-sk1...\n";
+        let encrypted = encrypt(b"passphrase", data, &mut OsRng);
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(*decrypt(b"passphrase", &encrypted).unwrap(), data.to_vec());
+
+        assert!(matches!(
+            decrypt(b"wrong passphrase", &encrypted),
+            Err(DecryptionError::Authentication)
+        ));
+
+        let mut tampered = encrypted.clone();
+        let last = tampered.len() - TAG_SIZE - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            decrypt(b"passphrase", &tampered),
+            Err(DecryptionError::Authentication)
+        ));
+
+        let mut stalling = encrypted.clone();
+        let iterations = MAX_ITERATIONS + 1;
+        stalling[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&iterations.to_be_bytes());
+        assert!(matches!(
+            decrypt(b"passphrase", &stalling),
+            Err(DecryptionError::TooManyIterations(_))
+        ));
+
+        let mut no_iterations = encrypted.clone();
+        no_iterations[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&0u32.to_be_bytes());
+        assert!(matches!(
+            decrypt(b"passphrase", &no_iterations),
+            Err(DecryptionError::NoIterations)
+        ));
+
+        assert!(matches!(
+            decrypt(b"passphrase", &encrypted[..HEADER_SIZE]),
+            Err(DecryptionError::Truncated)
+        ));
+        assert!(matches!(
+            decrypt(b"passphrase", data),
+            Err(DecryptionError::NotEncrypted)
+        ));
+    }
+}
@@ -1,4 +1,6 @@
+use crate::time::Duration;
 use serde::{Deserialize, Serialize};
+use std::time;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct PoolMaxEntries(usize);
@@ -6,6 +8,15 @@ pub struct PoolMaxEntries(usize);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct LogMaxEntries(usize);
 
+/// the margin, in percent, by which the fee of a fragment needs to exceed
+/// the fee of a pending fragment in order to replace it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct FeeReplacementMargin(u64);
+
+/// how long a fragment is kept in the mempool before it expires
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct FragmentTtl(Duration);
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Mempool {
@@ -15,6 +26,17 @@ pub struct Mempool {
     /// maximum number of entries in the fragment logs
     #[serde(default)]
     pub log_max_entries: LogMaxEntries,
+    /// minimum fee increase, in percent, for a fragment to replace a pending
+    /// fragment spending the same UTxO or account spending counter
+    #[serde(default)]
+    pub fee_replacement_margin: FeeReplacementMargin,
+    /// save the pending fragments and the fragment logs in the node storage
+    /// directory so they are restored after a restart
+    #[serde(default)]
+    pub persistent: bool,
+    /// how long a fragment stays in the mempool before being rejected
+    #[serde(default)]
+    pub fragment_ttl: FragmentTtl,
 }
 
 impl Default for PoolMaxEntries {
@@ -29,11 +51,26 @@ impl Default for LogMaxEntries {
     }
 }
 
+impl Default for FeeReplacementMargin {
+    fn default() -> Self {
+        FeeReplacementMargin(10)
+    }
+}
+
+impl Default for FragmentTtl {
+    fn default() -> Self {
+        FragmentTtl(Duration::new(30 * 60, 0))
+    }
+}
+
 impl Default for Mempool {
     fn default() -> Self {
         Mempool {
             pool_max_entries: PoolMaxEntries::default(),
             log_max_entries: LogMaxEntries::default(),
+            fee_replacement_margin: FeeReplacementMargin::default(),
+            persistent: false,
+            fragment_ttl: FragmentTtl::default(),
         }
     }
 }
@@ -61,3 +98,27 @@ impl From<LogMaxEntries> for usize {
         s.0
     }
 }
+
+impl From<u64> for FeeReplacementMargin {
+    fn from(s: u64) -> Self {
+        FeeReplacementMargin(s)
+    }
+}
+
+impl From<FeeReplacementMargin> for u64 {
+    fn from(s: FeeReplacementMargin) -> Self {
+        s.0
+    }
+}
+
+impl From<time::Duration> for FragmentTtl {
+    fn from(s: time::Duration) -> Self {
+        FragmentTtl(s.into())
+    }
+}
+
+impl From<FragmentTtl> for time::Duration {
+    fn from(s: FragmentTtl) -> Self {
+        s.0.into()
+    }
+}
@@ -4,7 +4,7 @@ mod node;
 mod secret;
 
 pub use log::{Log, LogEntry, LogOutput};
-pub use mempool::{LogMaxEntries, Mempool, PoolMaxEntries};
+pub use mempool::{FeeReplacementMargin, FragmentTtl, LogMaxEntries, Mempool, PoolMaxEntries};
 pub use node::{
     Explorer, LayersConfig, NodeConfig, P2p, Policy, PreferredListConfig, Rest, TopicsOfInterest,
     TrustedPeer,
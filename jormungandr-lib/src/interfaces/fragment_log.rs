@@ -27,6 +27,27 @@ pub enum FragmentStatus {
     Rejected { reason: String },
     /// The fragment has been added in a block
     InABlock { date: BlockDate, block: Hash },
+    /// the fragment has been replaced in the pool by another fragment
+    /// paying a higher fee
+    Replaced { by: Hash },
+}
+
+/// the result of submitting a fragment to the mempool
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FragmentAdmission {
+    /// the fragment has been accepted in the mempool
+    Accepted,
+    /// the fragment was already known to the node, its log gives its status
+    AlreadyKnown,
+    /// the fragment has been rejected and won't be added in a block
+    Rejected { reason: String },
+}
+
+/// the admission result of a submitted fragment
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FragmentSubmission {
+    pub fragment_id: Hash,
+    pub admission: FragmentAdmission,
 }
 
 /// the log associated to a given fragment
@@ -62,6 +83,15 @@ impl FragmentStatus {
             false
         }
     }
+
+    #[inline]
+    pub fn is_replaced(&self) -> bool {
+        if let FragmentStatus::Replaced { .. } = &self {
+            true
+        } else {
+            false
+        }
+    }
 }
 
 impl FragmentLog {
@@ -92,6 +122,11 @@ impl FragmentLog {
         self.status().is_in_a_block()
     }
 
+    #[inline]
+    pub fn is_replaced(&self) -> bool {
+        self.status().is_replaced()
+    }
+
     /// set the new status
     #[inline]
     pub fn modify(&mut self, new_status: FragmentStatus) {
@@ -0,0 +1,85 @@
+//! The protocol spoken between the nodes and the `jormungandr-lock` service.
+//!
+//! A node sends `acquire <key> <mac>` on a single line before signing the
+//! block of a slot. The key identifies the slot and its leader as
+//! `<epoch>.<slot>-<leader>` and the MAC is the hex-encoded HMAC-SHA256 of
+//! the key with the secret shared by the nodes and the service. The service
+//! answers `granted`, `denied` or `invalid request`.
+
+use cryptoxide::{
+    hmac::Hmac,
+    mac::{Mac, MacResult},
+    sha2::Sha256,
+};
+
+/// the key of the lock of the given slot of the given leader
+pub fn slot_lock_key(epoch: u32, slot: u32, leader: &str) -> String {
+    format!("{}.{}-{}", epoch, slot, leader)
+}
+
+/// the epoch, the slot and the leader of a lock key
+pub fn parse_slot_lock_key(key: &str) -> Option<(u32, u32, &str)> {
+    let mut parts = key.splitn(2, '-');
+    let date = parts.next()?;
+    let leader = parts.next().filter(|leader| !leader.is_empty())?;
+    let mut date = date.splitn(2, '.');
+    let epoch = date.next()?.parse().ok()?;
+    let slot = date.next()?.parse().ok()?;
+    Some((epoch, slot, leader))
+}
+
+/// the hex-encoded MAC authenticating a request for the given key
+pub fn lock_request_mac(secret: &[u8], key: &str) -> String {
+    hex::encode(mac(secret, key))
+}
+
+/// check the hex-encoded MAC of a request for the given key
+pub fn verify_lock_request_mac(secret: &[u8], key: &str, mac_hex: &str) -> bool {
+    match hex::decode(mac_hex) {
+        // the comparison of the MAC results takes a constant time
+        Ok(received) => {
+            received.len() == 32 && MacResult::new(&received) == MacResult::new(&mac(secret, key))
+        }
+        Err(_) => false,
+    }
+}
+
+fn mac(secret: &[u8], key: &str) -> [u8; 32] {
+    let mut hmac = Hmac::new(Sha256::new(), secret);
+    hmac.input(key.as_bytes());
+    let mut code = [0; 32];
+    hmac.raw_result(&mut code);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_lock_key_roundtrip() {
+        let key = slot_lock_key(12, 345, "ed25519_pk1leader");
+        assert_eq!(key, "12.345-ed25519_pk1leader");
+        assert_eq!(
+            parse_slot_lock_key(&key),
+            Some((12, 345, "ed25519_pk1leader"))
+        );
+        assert_eq!(parse_slot_lock_key("12.345"), None);
+        assert_eq!(parse_slot_lock_key("12.345-"), None);
+        assert_eq!(parse_slot_lock_key("12-leader"), None);
+    }
+
+    #[test]
+    fn lock_request_mac_depends_on_the_secret_and_the_key() {
+        let mac = lock_request_mac(b"secret", "12.345-leader");
+        assert!(verify_lock_request_mac(b"secret", "12.345-leader", &mac));
+        assert!(!verify_lock_request_mac(b"other", "12.345-leader", &mac));
+        assert!(!verify_lock_request_mac(b"secret", "12.346-leader", &mac));
+        assert!(!verify_lock_request_mac(b"secret", "12.345-leader", "00"));
+        assert!(!verify_lock_request_mac(
+            b"secret",
+            "12.345-leader",
+            "not hex"
+        ));
+    }
+}
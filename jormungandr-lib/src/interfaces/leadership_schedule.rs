@@ -0,0 +1,16 @@
+use crate::{
+    interfaces::{BlockDate, EnclaveLeaderId},
+    time::SystemTime,
+};
+use serde::{Deserialize, Serialize};
+
+/// a slot of an epoch one of the leaders of the node is elected to create
+/// a block for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeadershipScheduleEntry {
+    pub enclave_leader_id: EnclaveLeaderId,
+    pub scheduled_at_date: BlockDate,
+    /// the wall clock time the slot starts at
+    pub scheduled_at_time: SystemTime,
+}
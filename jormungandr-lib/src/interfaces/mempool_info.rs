@@ -0,0 +1,38 @@
+use crate::{crypto::hash::Hash, interfaces::Value, time::Duration};
+use serde::{Deserialize, Serialize};
+
+/// the content of the mempool: summary statistics over all the pending
+/// fragments and a page of the pending fragments, oldest first
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MempoolInfo {
+    pub summary: MempoolSummary,
+    pub entries: Vec<MempoolEntry>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MempoolSummary {
+    /// number of pending fragments
+    pub fragments: usize,
+    /// maximum number of pending fragments
+    pub capacity: usize,
+    /// sum of the sizes of the pending fragments, in bytes
+    pub total_size: u64,
+    /// sum of the fees paid by the pending fragments
+    pub total_fees: Value,
+    /// time the oldest pending fragment has been waiting for
+    pub max_wait_time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MempoolEntry {
+    pub fragment_id: Hash,
+    /// size of the fragment, in bytes
+    pub size: u32,
+    /// fee paid by the fragment, with the ledger parameters of the tip
+    pub fee: Value,
+    /// time the fragment has been waiting in the mempool for
+    pub wait_time: Duration,
+}
@@ -7,14 +7,18 @@ mod certificate;
 mod committee;
 mod config;
 mod fragment_log;
+mod leader_lock;
 mod leadership_log;
+mod leadership_schedule;
 mod linear_fee;
+mod mempool_info;
 mod old_address;
 mod peer_stats;
 mod ratio;
 mod reward_parameters;
 mod rewards_info;
 mod settings;
+mod signer;
 mod stake;
 mod stake_distribution;
 mod stake_pool_stats;
@@ -38,19 +42,27 @@ pub use self::certificate::{
 };
 pub use self::committee::CommitteeIdDef;
 pub use self::config::*;
-pub use self::fragment_log::{FragmentLog, FragmentOrigin, FragmentStatus};
+pub use self::fragment_log::{
+    FragmentAdmission, FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission,
+};
+pub use self::leader_lock::{
+    lock_request_mac, parse_slot_lock_key, slot_lock_key, verify_lock_request_mac,
+};
 pub use self::leadership_log::{
     EnclaveLeaderId, LeadershipLog, LeadershipLogId, LeadershipLogStatus,
 };
+pub use self::leadership_schedule::LeadershipScheduleEntry;
 pub use self::linear_fee::LinearFeeDef;
+pub use self::mempool_info::{MempoolEntry, MempoolInfo, MempoolSummary};
 pub use self::old_address::OldAddress;
 pub use self::peer_stats::{
-    Info, Logs, PeerRecord, PeerStats, Profile, Record, Strike, Subscription, When,
+    Info, Logs, PeerRecord, PeerScore, PeerStats, Profile, Record, Strike, Subscription, When,
 };
 pub use self::ratio::{ParseRatioError, Ratio};
 pub use self::reward_parameters::RewardParams;
 pub use self::rewards_info::EpochRewardsInfo;
 pub use self::settings::{ParametersDef, RatioDef, SettingsDto, TaxTypeDef, TaxTypeSerde};
+pub use self::signer::{SignerRequest, SignerResponse, SIGNER_PROTOCOL_VERSION};
 pub use self::stake::{Stake, StakeDef};
 pub use self::stake_distribution::{StakeDistribution, StakeDistributionDto};
 pub use self::stake_pool_stats::{Rewards, StakePoolStats};
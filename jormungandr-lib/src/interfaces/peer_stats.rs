@@ -1,4 +1,4 @@
-use crate::time::{SecondsSinceUnixEpoch, SystemTime};
+use crate::time::{Duration, SecondsSinceUnixEpoch, SystemTime};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -13,6 +13,27 @@ pub struct PeerStats {
     pub last_gossip_received: Option<SystemTime>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PeerScore {
+    pub address: String,
+    /// between 0 and 1, the higher the better
+    pub score: f64,
+    /// the decayed number of new blocks and headers received from the peer
+    /// that passed validation
+    pub useful: f64,
+    /// the decayed number of blocks and headers received from the peer
+    /// that failed validation
+    pub invalid: f64,
+    /// the decayed number of blocks announced by the peer
+    pub announcements: f64,
+    /// the decayed number of blocks announced by the peer before any other
+    pub first_announcements: f64,
+    /// the average delay of the announcements of the peer after the first
+    /// announcement of the same block
+    pub announcement_delay: Option<Duration>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PeerRecord {
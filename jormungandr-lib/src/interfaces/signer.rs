@@ -0,0 +1,60 @@
+//! The protocol spoken between the node and a remote signer holding the
+//! KES keys of the stake pools.
+//!
+//! The node connects to the signer over a Unix domain socket. Every
+//! message is a JSON object on its own line: the node sends a request and
+//! waits for the response before sending the next one. The first request
+//! on a connection is always `Hello`, the signer answers with the version
+//! it speaks and the node closes the connection if it is not the same.
+
+use crate::crypto::{hash::Hash, kes::KesPeriods};
+use serde::{Deserialize, Serialize};
+
+/// version of the protocol implemented by this crate
+pub const SIGNER_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum SignerRequest {
+    /// open the session with the given protocol version
+    Hello { version: u32 },
+    /// sign the hex-encoded authenticated data of a block header with the
+    /// KES key of the given stake pool, evolved to the given period first
+    SignGenesisPraos {
+        node_id: Hash,
+        kes_period: u32,
+        data: String,
+    },
+    /// get the periods of the KES key of the given stake pool
+    KesPeriods { node_id: Hash },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum SignerResponse {
+    /// the session is open with the given protocol version
+    Hello {
+        version: u32,
+    },
+    /// the hex-encoded signature
+    Signature {
+        signature: String,
+    },
+    KesPeriods {
+        current: u32,
+        remaining: u32,
+    },
+    /// the request could not be served
+    Error {
+        reason: String,
+    },
+}
+
+impl From<KesPeriods> for SignerResponse {
+    fn from(periods: KesPeriods) -> Self {
+        SignerResponse::KesPeriods {
+            current: periods.current,
+            remaining: periods.remaining,
+        }
+    }
+}
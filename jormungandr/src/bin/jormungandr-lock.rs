@@ -0,0 +1,176 @@
+//! Lock service shared by the nodes running the same leaders, see the
+//! `leadership.lock` setting of the node.
+//!
+//! The nodes connect over TCP and send `acquire <key> <mac>` on a single line
+//! before signing the block of a slot, see
+//! `jormungandr_lib::interfaces::slot_lock_key` for the format of the key.
+//! The requests are authenticated with a secret shared by the nodes and the
+//! service.
+//!
+//! The service remembers the highest slot granted to each leader and answers
+//! `granted` only for a later slot, `denied` otherwise. The granted slots are
+//! written to the state file before answering, so a restarted service never
+//! grants a slot twice.
+
+use jormungandr_lib::interfaces::{parse_slot_lock_key, verify_lock_request_mac};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{self, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use structopt::StructOpt;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+
+#[derive(StructOpt)]
+#[structopt(name = "jormungandr-lock", rename_all = "kebab-case")]
+struct Opts {
+    /// the address to listen on
+    #[structopt(long)]
+    listen: SocketAddr,
+
+    /// the file holding the secret shared with the nodes
+    #[structopt(long, parse(from_os_str))]
+    secret_file: PathBuf,
+
+    /// the file the granted slots are recorded in, created if it does not
+    /// exist
+    #[structopt(long, parse(from_os_str))]
+    state: PathBuf,
+}
+
+/// the highest slot granted to a leader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct GrantedSlot {
+    epoch: u32,
+    slot: u32,
+}
+
+struct Service {
+    secret: Vec<u8>,
+    state_file: PathBuf,
+    granted: Mutex<HashMap<String, GrantedSlot>>,
+}
+
+fn main() {
+    if let Err(error) = run(Opts::from_args()) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(opts: Opts) -> Result<(), Box<dyn Error>> {
+    let secret = read_secret(&opts.secret_file)?;
+    let granted = load_state(&opts.state)?;
+    let service = Arc::new(Service {
+        secret,
+        state_file: opts.state,
+        granted: Mutex::new(granted),
+    });
+
+    let mut runtime = Runtime::new()?;
+    runtime.block_on(async move {
+        let mut listener = TcpListener::bind(opts.listen).await?;
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let service = Arc::clone(&service);
+            tokio::spawn(async move {
+                if let Err(error) = serve(stream, service).await {
+                    eprintln!("connection with {} closed: {}", peer, error);
+                }
+            });
+        }
+    })
+}
+
+fn read_secret(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let secret = fs::read_to_string(path)?;
+    let secret = secret.trim_end_matches(&['\r', '\n'][..]);
+    if secret.is_empty() {
+        return Err(format!("the secret file {} is empty", path.display()).into());
+    }
+    Ok(secret.as_bytes().to_vec())
+}
+
+fn load_state(path: &Path) -> Result<HashMap<String, GrantedSlot>, Box<dyn Error>> {
+    match File::open(path) {
+        Ok(file) => Ok(serde_yaml::from_reader(file)?),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// replace the state file, the new state is written to a temporary file
+/// renamed over the previous one so the state file is never left truncated
+fn save_state(path: &Path, granted: &HashMap<String, GrantedSlot>) -> io::Result<()> {
+    let content =
+        serde_yaml::to_vec(granted).map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = File::create(&temporary)?;
+    file.write_all(&content)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+async fn serve(stream: TcpStream, service: Arc<Service>) -> io::Result<()> {
+    let mut connection = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if connection.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let answer = match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("acquire"), Some(key), Some(mac), None)
+                if verify_lock_request_mac(&service.secret, key, mac) =>
+            {
+                let service = Arc::clone(&service);
+                let key = key.to_owned();
+                // the state file is written before answering
+                match tokio::task::spawn_blocking(move || acquire(&service, &key)).await? {
+                    Ok(true) => "granted\n",
+                    Ok(false) => "denied\n",
+                    Err(error) => {
+                        eprintln!("cannot record the granted slot: {}", error);
+                        "denied\n"
+                    }
+                }
+            }
+            _ => "invalid request\n",
+        };
+        connection.get_mut().write_all(answer.as_bytes()).await?;
+    }
+}
+
+fn acquire(service: &Service, key: &str) -> io::Result<bool> {
+    let (epoch, slot, leader) = match parse_slot_lock_key(key) {
+        Some(parsed) => parsed,
+        None => return Ok(false),
+    };
+    let requested = GrantedSlot { epoch, slot };
+    let mut granted = service.granted.lock().unwrap();
+    if granted.get(leader).map_or(false, |last| requested <= *last) {
+        return Ok(false);
+    }
+    let previous = granted.insert(leader.to_owned(), requested);
+    if let Err(error) = save_state(&service.state_file, &granted) {
+        // not granted if it is not recorded
+        match previous {
+            Some(previous) => granted.insert(leader.to_owned(), previous),
+            None => granted.remove(leader),
+        };
+        return Err(error);
+    }
+    Ok(true)
+}
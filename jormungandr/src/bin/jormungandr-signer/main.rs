@@ -0,0 +1,64 @@
+//! Reference remote signer: holds the KES keys of genesis praos leaders and
+//! signs the block headers of a node configured with `remote_signer`.
+//!
+//! The signer loads node secret files, listens on a Unix domain socket and
+//! serves the requests of `jormungandr_lib::interfaces::SignerRequest`, one
+//! JSON object per line.
+
+#[cfg(unix)]
+mod server;
+
+use std::{error::Error, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "jormungandr-signer", rename_all = "kebab-case")]
+pub struct Opts {
+    /// the node secret files holding the genesis praos leaders, in YAML
+    /// or encrypted with `jcli key encrypt-secret`
+    #[structopt(long = "secret", parse(from_os_str), required = true)]
+    pub secrets: Vec<PathBuf>,
+
+    /// read the passphrase of the encrypted secret files from the given
+    /// file. If not set, the passphrase is read from the
+    /// `JORMUNGANDR_SECRET_PASSPHRASE` environment variable or prompted for.
+    #[structopt(long, parse(from_os_str))]
+    pub secret_passphrase_file: Option<PathBuf>,
+
+    /// evolve the KES keys to the given period at start-up
+    #[structopt(long)]
+    pub kes_period: Option<u32>,
+
+    /// the `kes_update_speed` of the blockchain, the duration of a KES
+    /// period in seconds. When signing, the keys are evolved by at most the
+    /// periods elapsed since they were last evolved, plus one.
+    #[structopt(long)]
+    pub kes_update_speed: u32,
+
+    /// the Unix socket to listen on, only accessible to the user running
+    /// the signer
+    #[structopt(long, parse(from_os_str))]
+    pub socket: PathBuf,
+}
+
+fn main() {
+    if let Err(error) = run(Opts::from_args()) {
+        eprintln!("{}", error);
+        let mut source = error.source();
+        while let Some(error) = source {
+            eprintln!("  |-> {}", error);
+            source = error.source();
+        }
+        std::process::exit(1);
+    }
+}
+
+#[cfg(unix)]
+fn run(opts: Opts) -> Result<(), Box<dyn Error>> {
+    server::run(opts)
+}
+
+#[cfg(not(unix))]
+fn run(_opts: Opts) -> Result<(), Box<dyn Error>> {
+    Err("the remote signer is only supported on Unix".into())
+}
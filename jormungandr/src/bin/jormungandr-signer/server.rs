@@ -0,0 +1,307 @@
+//! the signer serving the requests of the node over a Unix socket
+
+use crate::Opts;
+use chain_core::mempack::{ReadBuf, ReadError, Readable};
+use chain_crypto::{SecretKey, SumEd25519_12, VerificationAlgorithm};
+use chain_impl_mockchain::{
+    block::BlockDate,
+    header::{BlockVersion, Header},
+};
+use jormungandr_lib::{
+    crypto::{hash::Hash, kes, password},
+    interfaces::{NodeSecret, SignerRequest, SignerResponse, SIGNER_PROTOCOL_VERSION},
+};
+use nix::sys::stat::{umask, Mode};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    env,
+    error::Error,
+    fs, io,
+    os::unix::{fs::FileTypeExt, net},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    runtime::Runtime,
+};
+use zeroize::Zeroizing;
+
+/// the keys of the stake pools, indexed by their node id
+type StakePools = Arc<Mutex<HashMap<Hash, StakePool>>>;
+
+struct StakePool {
+    sig_key: SecretKey<SumEd25519_12>,
+    /// when the KES key was last known to be at its current period: when
+    /// it was loaded or last used to sign
+    sig_key_period_at: Instant,
+    /// the last header signed, the signer never signs another header for
+    /// the same slot or for an earlier slot
+    last_signed: Option<Signed>,
+}
+
+struct Signed {
+    date: BlockDate,
+    data: Vec<u8>,
+}
+
+pub fn run(opts: Opts) -> Result<(), Box<dyn Error>> {
+    if opts.kes_update_speed == 0 {
+        return Err("the KES update speed must be at least one second".into());
+    }
+    let mut stake_pools = HashMap::new();
+    let mut passphrase = None;
+    for path in opts.secrets {
+        let mut content = Zeroizing::new(fs::read(&path)?);
+        if password::is_encrypted(&content) {
+            if passphrase.is_none() {
+                passphrase = Some(Zeroizing::new(read_passphrase(
+                    &opts.secret_passphrase_file,
+                )?));
+            }
+            let passphrase = passphrase.as_ref().unwrap();
+            content = password::decrypt(passphrase.as_bytes(), &content)?;
+        }
+        let secret: NodeSecret = serde_yaml::from_slice(&content)?;
+        let genesis = secret
+            .genesis
+            .ok_or_else(|| format!("{} has no genesis praos leader", path.display()))?;
+        let mut sig_key = genesis.sig_key.into_secret_key();
+        if let Some(kes_period) = opts.kes_period {
+            kes::evolve(&mut sig_key, kes_period)?;
+        }
+        let stake_pool = StakePool {
+            sig_key,
+            sig_key_period_at: Instant::now(),
+            last_signed: None,
+        };
+        stake_pools.insert(genesis.node_id, stake_pool);
+    }
+    let stake_pools = Arc::new(Mutex::new(stake_pools));
+    let kes_period_duration = Duration::from_secs(opts.kes_update_speed.into());
+
+    // bound before the runtime starts its threads, the umask is process-wide
+    let listener = bind(&opts.socket)?;
+
+    let mut runtime = Runtime::new()?;
+    runtime.block_on(async move {
+        let mut listener = UnixListener::from_std(listener)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let stake_pools = Arc::clone(&stake_pools);
+            tokio::spawn(async move {
+                if let Err(error) = serve(stream, stake_pools, kes_period_duration).await {
+                    eprintln!("connection closed: {}", error);
+                }
+            });
+        }
+    })
+}
+
+/// Bind the socket with the mode 0600, so it is only accessible to the user
+/// running the signer. A socket left over by a signer that is not running
+/// anymore is removed, any other file at the path is left untouched.
+fn bind(path: &Path) -> Result<net::UnixListener, Box<dyn Error>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => match net::UnixStream::connect(path) {
+            Ok(_) => return Err(format!("a signer is listening on {}", path.display()).into()),
+            Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {
+                fs::remove_file(path)?
+            }
+            Err(error) => return Err(error.into()),
+        },
+        Ok(_) => return Err(format!("{} exists and is not a socket", path.display()).into()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+        Err(error) => return Err(error.into()),
+    }
+    let previous = umask(Mode::from_bits_truncate(0o177));
+    let listener = net::UnixListener::bind(path);
+    umask(previous);
+    Ok(listener?)
+}
+
+/// read the passphrase of the encrypted secrets from the given file, from
+/// the `JORMUNGANDR_SECRET_PASSPHRASE` environment variable or from the
+/// terminal, like the node does
+fn read_passphrase(file: &Option<PathBuf>) -> io::Result<String> {
+    if let Some(file) = file {
+        let passphrase = fs::read_to_string(file)?;
+        Ok(passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned())
+    } else if let Ok(passphrase) = env::var("JORMUNGANDR_SECRET_PASSPHRASE") {
+        Ok(passphrase)
+    } else {
+        rpassword::read_password_from_tty(Some("Passphrase of the node secrets: "))
+    }
+}
+
+async fn serve(
+    stream: UnixStream,
+    stake_pools: StakePools,
+    kes_period_duration: Duration,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the socket is only accessible to the user, this also rejects the
+    // processes of other users sharing a file descriptor of the socket
+    let peer = stream.peer_cred()?;
+    if peer.uid != nix::unistd::geteuid().as_raw() {
+        return Err(format!("refusing the connection of the user {}", peer.uid).into());
+    }
+
+    let mut connection = BufReader::new(stream);
+    let mut line = String::new();
+    let mut open = false;
+    loop {
+        line.clear();
+        if connection.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let response = match serde_json::from_str(&line)? {
+            SignerRequest::Hello { .. } if !open => {
+                open = true;
+                SignerResponse::Hello {
+                    version: SIGNER_PROTOCOL_VERSION,
+                }
+            }
+            _ if !open => refuse("the session has not been opened"),
+            request => handle(request, &stake_pools, kes_period_duration),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        connection.get_mut().write_all(line.as_bytes()).await?;
+    }
+}
+
+fn handle(
+    request: SignerRequest,
+    stake_pools: &StakePools,
+    kes_period_duration: Duration,
+) -> SignerResponse {
+    let mut stake_pools = stake_pools.lock().unwrap();
+    match request {
+        SignerRequest::Hello { .. } => refuse("the session is already open"),
+        SignerRequest::SignGenesisPraos {
+            node_id,
+            kes_period,
+            data,
+        } => {
+            let stake_pool = match stake_pools.get_mut(&node_id) {
+                Some(stake_pool) => stake_pool,
+                None => return unknown_stake_pool(&node_id),
+            };
+            let data = match hex::decode(data) {
+                Ok(data) => data,
+                Err(error) => return refuse(error),
+            };
+            let header = match parse_header(&data) {
+                Ok(header) => header,
+                Err(error) => return refuse(format!("invalid header: {}", error)),
+            };
+            if header.block_version() != BlockVersion::KesVrfproof
+                || header.get_stakepool_id().cloned().map(Hash::from).as_ref() != Some(&node_id)
+            {
+                return refuse(format!("not a header of the stake pool {}", node_id));
+            }
+            let date = header.block_date();
+            if let Some(last_signed) = &stake_pool.last_signed {
+                if date < last_signed.date || (date == last_signed.date && data != last_signed.data)
+                {
+                    return refuse(format!(
+                        "a header has already been signed for {}",
+                        last_signed.date
+                    ));
+                }
+            }
+            let now = Instant::now();
+            let current = kes::period(&stake_pool.sig_key);
+            let max_period = max_kes_period(
+                current,
+                now.saturating_duration_since(stake_pool.sig_key_period_at),
+                kes_period_duration,
+            );
+            if kes_period > max_period {
+                return refuse(format!(
+                    "the KES key is at period {}, it cannot be evolved past the period {} yet",
+                    current, max_period
+                ));
+            }
+            if let Err(error) = kes::evolve(&mut stake_pool.sig_key, kes_period) {
+                return refuse(error);
+            }
+            let signature = stake_pool.sig_key.sign_slice(&data);
+            stake_pool.sig_key_period_at = now;
+            stake_pool.last_signed = Some(Signed { date, data });
+            SignerResponse::Signature {
+                signature: hex::encode(signature.as_ref()),
+            }
+        }
+        SignerRequest::KesPeriods { node_id } => match stake_pools.get(&node_id) {
+            Some(stake_pool) => kes::periods(&stake_pool.sig_key).into(),
+            None => unknown_stake_pool(&node_id),
+        },
+    }
+}
+
+/// The highest period a KES key at the `current` period since `elapsed` can
+/// be evolved to: the periods elapsed since, plus one as the key may have
+/// been at the end of its period. A node asking for a later period would
+/// burn the remaining periods of the key.
+fn max_kes_period(current: u32, elapsed: Duration, kes_period_duration: Duration) -> u32 {
+    let elapsed_periods = elapsed
+        .as_secs()
+        .checked_div(kes_period_duration.as_secs())
+        .unwrap_or(u64::from(u32::MAX));
+    let elapsed_periods = u32::try_from(elapsed_periods).unwrap_or(u32::MAX);
+    current.saturating_add(elapsed_periods).saturating_add(1)
+}
+
+/// Parse the authenticated data of a genesis praos header, which is the
+/// header without its KES signature. The signature is filled with zeros to
+/// read the header.
+fn parse_header(data: &[u8]) -> Result<Header, ReadError> {
+    let mut bytes = data.to_vec();
+    bytes.resize(data.len() + SumEd25519_12::SIGNATURE_SIZE, 0);
+    let mut buf = ReadBuf::from(&bytes[..]);
+    let header = Header::read(&mut buf)?;
+    buf.expect_end()?;
+    Ok(header)
+}
+
+fn unknown_stake_pool(node_id: &Hash) -> SignerResponse {
+    refuse(format!("no keys for the stake pool {}", node_id))
+}
+
+fn refuse(error: impl std::fmt::Display) -> SignerResponse {
+    SignerResponse::Error {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn kes_key_evolves_by_one_period_without_elapsed_periods() {
+        assert_eq!(max_kes_period(4, Duration::from_secs(0), PERIOD), 5);
+        assert_eq!(
+            max_kes_period(4, PERIOD - Duration::from_secs(1), PERIOD),
+            5
+        );
+    }
+
+    #[test]
+    fn kes_key_evolves_by_the_elapsed_periods() {
+        assert_eq!(max_kes_period(4, PERIOD * 3, PERIOD), 8);
+        assert_eq!(max_kes_period(4, PERIOD * 3 + PERIOD / 2, PERIOD), 8);
+    }
+
+    #[test]
+    fn kes_period_does_not_overflow() {
+        assert_eq!(max_kes_period(u32::MAX, PERIOD, PERIOD), u32::MAX);
+        assert_eq!(max_kes_period(0, PERIOD, Duration::from_secs(0)), u32::MAX);
+    }
+}
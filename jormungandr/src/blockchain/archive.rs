@@ -0,0 +1,518 @@
+//! Portable archives of the blocks of the blockchain.
+//!
+//! An archive holds a range of blocks of the main branch, in order, so a
+//! node can be seeded from a file instead of the network. The file is laid
+//! out as:
+//!
+//! ```text
+//! | magic (4) | version (1) | block0 hash (32) | block | ... | block | end (4) | block count (8, BE) | checksum (32) |
+//! ```
+//!
+//! where every block is prefixed by its size (4, BE), the end marker is a
+//! size of 0 and the checksum is the Blake2b256 hash of everything before it.
+//!
+//! The checksum of the whole archive is verified before importing any block,
+//! the blocks are then checked and applied the same way as the blocks
+//! received from the network. A block larger than the maximum size of the
+//! blocks of the blockchain is refused before being read.
+
+use super::{
+    process_new_ref, Blockchain, CheckHeaderProof, Error as BlockchainError, PreCheckedHeader, Ref,
+    StorageError, Tip,
+};
+use crate::blockcfg::{Block, HeaderHash};
+use chain_core::property::{Deserialize, Serialize};
+use cryptoxide::{blake2b::Blake2b, digest::Digest};
+use slog::Logger;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+use thiserror::Error;
+use tokio::stream::StreamExt;
+
+const MAGIC: &[u8; 4] = b"JARC";
+const VERSION: u8 = 1;
+const CHECKSUM_SIZE: usize = 32;
+const HEADER_SIZE: u64 = 4 + 1 + 32;
+const TRAILER_SIZE: u64 = 4 + 8 + CHECKSUM_SIZE as u64;
+/// an upper bound of the size of a block header, the largest being the
+/// genesis praos headers with their VRF proof and KES signature
+const MAX_HEADER_SIZE: u32 = 1024;
+
+const PROCESS_LOGGING_DISTANCE: u64 = 2500;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("I/O error with the archive file")]
+    Io(#[from] io::Error),
+    #[error("not a block archive or unsupported version")]
+    InvalidFormat,
+    #[error("the archive is corrupted, its checksum does not match its contents")]
+    ChecksumMismatch,
+    #[error("the archive holds the blocks of the blockchain of block0 {archive}, not {node}")]
+    Block0Mismatch {
+        archive: HeaderHash,
+        node: HeaderHash,
+    },
+    #[error("block {0} is not in the storage")]
+    BlockNotFound(HeaderHash),
+    #[error("no block of chain length {0} on the main branch")]
+    ChainLengthNotFound(u32),
+    #[error("block {from} is not an ancestor of block {to}")]
+    NotAnAncestor { from: HeaderHash, to: HeaderHash },
+    #[error("cannot read the blocks from the storage")]
+    Storage(#[from] StorageError),
+    #[error("cannot read a block from the storage")]
+    StorageStream(#[source] crate::intercom::Error),
+    #[error("a block of the archive is {size} bytes long, more than the maximum of {max} bytes")]
+    BlockTooLarge { size: u32, max: u32 },
+    #[error("decoding of a block failed")]
+    BlockDecodingFailed(#[source] <Block as Deserialize>::Error),
+    #[error("block {0} of the archive is not connected to the blockchain")]
+    BlockMissingParent(HeaderHash),
+    #[error(
+        "block {0} of the archive is already present, but not on the main branch of the blockchain"
+    )]
+    BlockNotOnBranch(HeaderHash),
+    #[error("block {0} of the archive is not valid")]
+    InvalidBlock(HeaderHash, #[source] BlockchainError),
+    #[error("failed to select the new tip")]
+    ChainSelectionFailed(#[source] BlockchainError),
+}
+
+/// a block of the main branch, given by its hash or its chain length
+#[derive(Debug, Clone)]
+pub enum BlockSelector {
+    Hash(HeaderHash),
+    ChainLength(u32),
+}
+
+impl FromStr for BlockSelector {
+    type Err = <HeaderHash as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(chain_length) => Ok(BlockSelector::ChainLength(chain_length)),
+            Err(_) => s.parse().map(BlockSelector::Hash),
+        }
+    }
+}
+
+/// write the blocks of the main branch from `from` (the block following
+/// block0 by default) to `to` (the tip by default) in an archive file.
+/// Returns the number of exported blocks.
+pub async fn export(
+    blockchain: &Blockchain,
+    tip: Arc<Ref>,
+    from: Option<BlockSelector>,
+    to: Option<BlockSelector>,
+    path: &Path,
+) -> Result<u64, ArchiveError> {
+    let storage = blockchain.storage();
+    let block0 = blockchain.block0().clone();
+    let to = match to {
+        Some(to) => resolve(blockchain, &tip, to).await?,
+        None => tip.hash(),
+    };
+    let from = match from {
+        Some(from) => resolve(blockchain, &tip, from).await?,
+        None => block0,
+    };
+    if !storage.is_ancestor(from, to).await? {
+        return Err(ArchiveError::NotAnAncestor { from, to });
+    }
+
+    let mut writer = ArchiveWriter::create(path, &block0)?;
+    // the blocks are streamed from the block following `from`
+    if from != block0 {
+        let block = storage
+            .get(from)
+            .await?
+            .ok_or(ArchiveError::BlockNotFound(from))?;
+        writer.append(&block)?;
+    }
+    let mut blocks = Box::pin(storage.stream_from_to(from, to).await?);
+    while let Some(block) = blocks.next().await {
+        writer.append(&block.map_err(ArchiveError::StorageStream)?)?;
+    }
+    writer.finish()
+}
+
+/// the hash of the block of the main branch matching the selector
+async fn resolve(
+    blockchain: &Blockchain,
+    tip: &Ref,
+    selector: BlockSelector,
+) -> Result<HeaderHash, ArchiveError> {
+    let chain_length = match selector {
+        BlockSelector::Hash(hash) => return Ok(hash),
+        BlockSelector::ChainLength(chain_length) => chain_length,
+    };
+    let storage = blockchain.storage();
+    for info in storage
+        .get_block_infos_by_chain_length(chain_length.into())
+        .await?
+    {
+        if storage.is_ancestor(info.block_hash, tip.hash()).await? {
+            return Ok(info.block_hash);
+        }
+    }
+    Err(ArchiveError::ChainLengthNotFound(chain_length))
+}
+
+/// check and apply the blocks of an archive file on top of the blockchain.
+/// Returns the number of imported blocks.
+pub async fn import(
+    blockchain: &mut Blockchain,
+    tip: Tip,
+    path: &Path,
+    logger: &Logger,
+) -> Result<u64, ArchiveError> {
+    verify_checksum(path)?;
+    let max_block_size = tip
+        .get_ref()
+        .await
+        .epoch_ledger_parameters()
+        .block_content_max_size
+        .saturating_add(MAX_HEADER_SIZE);
+    let mut reader = ArchiveReader::open(path, max_block_size)?;
+    let block0 = blockchain.block0().clone();
+    if reader.block0 != block0 {
+        return Err(ArchiveError::Block0Mismatch {
+            archive: reader.block0,
+            node: block0,
+        });
+    }
+
+    let main_branch = tip.get_ref().await.hash();
+    let mut count = 0;
+    let mut last_ref = None;
+    while let Some(block) = reader.next_block()? {
+        if block.header.hash() == block0 {
+            continue;
+        }
+        let result = import_block(blockchain, main_branch, block).await;
+        match result {
+            Ok(Some(block_ref)) => last_ref = Some(block_ref),
+            Ok(None) => {}
+            Err(error) => {
+                // keep the blocks imported so far
+                if let Some(block_ref) = last_ref {
+                    if let Err(error) =
+                        process_new_ref(logger, blockchain, tip.clone(), block_ref).await
+                    {
+                        warn!(logger, "cannot select the last imported block"; "error" => ?error);
+                    }
+                }
+                return Err(error);
+            }
+        }
+        count += 1;
+        if count % PROCESS_LOGGING_DISTANCE == 0 {
+            info!(logger, "importing the archive, {} blocks imported", count);
+        }
+    }
+
+    if let Some(block_ref) = last_ref {
+        process_new_ref(logger, blockchain, tip, block_ref)
+            .await
+            .map_err(ArchiveError::ChainSelectionFailed)?;
+    }
+    Ok(count)
+}
+
+/// check and apply a block of an archive. Returns `None` if the block is
+/// already in the storage on the main branch ending at `main_branch` but not
+/// in the cache, for example when it precedes the ledger checkpoint the
+/// blockchain was loaded from.
+async fn import_block(
+    blockchain: &Blockchain,
+    main_branch: HeaderHash,
+    block: Block,
+) -> Result<Option<Arc<Ref>>, ArchiveError> {
+    let hash = block.header.hash();
+    let pre_checked = blockchain
+        .pre_check_header(block.header.clone(), true)
+        .await
+        .map_err(|e| ArchiveError::InvalidBlock(hash, e))?;
+    match pre_checked {
+        PreCheckedHeader::AlreadyPresent {
+            cached_reference: Some(block_ref),
+            ..
+        } => Ok(Some(block_ref)),
+        PreCheckedHeader::AlreadyPresent {
+            cached_reference: None,
+            ..
+        } => {
+            if is_on_main_branch(blockchain, hash, main_branch).await? {
+                Ok(None)
+            } else {
+                Err(ArchiveError::BlockNotOnBranch(hash))
+            }
+        }
+        PreCheckedHeader::MissingParent { .. } => {
+            if is_on_main_branch(blockchain, hash, main_branch).await? {
+                Ok(None)
+            } else {
+                Err(ArchiveError::BlockMissingParent(hash))
+            }
+        }
+        PreCheckedHeader::HeaderWithCache { header, parent_ref } => {
+            let post_checked = blockchain
+                .post_check_header(header, parent_ref, CheckHeaderProof::Enabled)
+                .await
+                .map_err(|e| ArchiveError::InvalidBlock(hash, e))?;
+            let applied = blockchain
+                .apply_and_store_block(post_checked, block)
+                .await
+                .map_err(|e| ArchiveError::InvalidBlock(hash, e))?;
+            Ok(Some(applied.cached_ref()))
+        }
+    }
+}
+
+/// whether the block is in the storage, as an ancestor of `main_branch`
+async fn is_on_main_branch(
+    blockchain: &Blockchain,
+    hash: HeaderHash,
+    main_branch: HeaderHash,
+) -> Result<bool, ArchiveError> {
+    let storage = blockchain.storage();
+    Ok(storage.block_exists(hash).await? && storage.is_ancestor(hash, main_branch).await?)
+}
+
+struct ArchiveWriter {
+    writer: BufWriter<File>,
+    hasher: Blake2b,
+    count: u64,
+}
+
+impl ArchiveWriter {
+    fn create(path: &Path, block0: &HeaderHash) -> io::Result<Self> {
+        let mut archive = ArchiveWriter {
+            writer: BufWriter::new(File::create(path)?),
+            hasher: Blake2b::new(CHECKSUM_SIZE),
+            count: 0,
+        };
+        archive.write(MAGIC)?;
+        archive.write(&[VERSION])?;
+        archive.write(block0.as_ref())?;
+        Ok(archive)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hasher.input(bytes);
+        self.writer.write_all(bytes)
+    }
+
+    fn append(&mut self, block: &Block) -> io::Result<()> {
+        let bytes = block
+            .serialize_as_vec()
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        self.write(&(bytes.len() as u32).to_be_bytes())?;
+        self.write(&bytes)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<u64, ArchiveError> {
+        self.write(&0u32.to_be_bytes())?;
+        self.write(&self.count.to_be_bytes())?;
+        let mut checksum = [0; CHECKSUM_SIZE];
+        self.hasher.result(&mut checksum);
+        self.writer.write_all(&checksum)?;
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        Ok(self.count)
+    }
+}
+
+fn verify_checksum(path: &Path) -> Result<(), ArchiveError> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size < HEADER_SIZE + TRAILER_SIZE {
+        return Err(ArchiveError::InvalidFormat);
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut hasher = Blake2b::new(CHECKSUM_SIZE);
+    let mut contents = (&mut reader).take(size - CHECKSUM_SIZE as u64);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        match contents.read(&mut buffer)? {
+            0 => break,
+            n => hasher.input(&buffer[..n]),
+        }
+    }
+
+    let mut expected = [0; CHECKSUM_SIZE];
+    reader.read_exact(&mut expected)?;
+    let mut checksum = [0; CHECKSUM_SIZE];
+    hasher.result(&mut checksum);
+    if checksum != expected {
+        return Err(ArchiveError::ChecksumMismatch);
+    }
+    Ok(())
+}
+
+struct ArchiveReader {
+    reader: BufReader<File>,
+    block0: HeaderHash,
+    max_block_size: u32,
+    count: u64,
+}
+
+impl ArchiveReader {
+    fn open(path: &Path, max_block_size: u32) -> Result<Self, ArchiveError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; HEADER_SIZE as usize];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(ArchiveError::InvalidFormat);
+        }
+        let mut block0 = [0; 32];
+        block0.copy_from_slice(&header[5..]);
+        Ok(ArchiveReader {
+            reader,
+            block0: HeaderHash::from(block0),
+            max_block_size,
+            count: 0,
+        })
+    }
+
+    /// the next block of the archive, `None` once the end is reached
+    fn next_block(&mut self) -> Result<Option<Block>, ArchiveError> {
+        let mut size = [0; 4];
+        self.reader.read_exact(&mut size)?;
+        let size = u32::from_be_bytes(size);
+        if size == 0 {
+            let mut count = [0; 8];
+            self.reader.read_exact(&mut count)?;
+            if u64::from_be_bytes(count) != self.count {
+                return Err(ArchiveError::InvalidFormat);
+            }
+            return Ok(None);
+        }
+        if size > self.max_block_size {
+            return Err(ArchiveError::BlockTooLarge {
+                size,
+                max: self.max_block_size,
+            });
+        }
+
+        let mut bytes = vec![0; size as usize];
+        self.reader.read_exact(&mut bytes)?;
+        let block =
+            Block::deserialize(bytes.as_slice()).map_err(ArchiveError::BlockDecodingFailed)?;
+        self.count += 1;
+        Ok(Some(block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockDate, BlockVersion, ContentsBuilder, Header};
+    use rand_core::{OsRng, RngCore};
+    use std::{convert::Infallible, fs, path::PathBuf};
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new() -> Self {
+            TempFile(std::env::temp_dir().join(format!("archive-{:016x}", OsRng.next_u64())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// a chain of empty blocks, starting with a block0
+    fn blocks(count: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for slot_id in 0..count {
+            let date = BlockDate { epoch: 0, slot_id };
+            let block = chain_impl_mockchain::block::builder(
+                BlockVersion::Genesis,
+                ContentsBuilder::new().into(),
+                |hdr| {
+                    let hdr = match blocks.last() {
+                        None => hdr.set_genesis(),
+                        Some(parent) => hdr.set_parent(
+                            &parent.header.hash(),
+                            parent.header.chain_length().increase(),
+                        ),
+                    };
+                    let header: Result<Header, Infallible> = Ok(hdr
+                        .set_date(date)
+                        .into_unsigned_header()
+                        .unwrap()
+                        .generalize());
+                    header
+                },
+            )
+            .unwrap();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn write_archive(path: &Path, blocks: &[Block]) -> u64 {
+        let mut writer = ArchiveWriter::create(path, &blocks[0].header.hash()).unwrap();
+        for block in &blocks[1..] {
+            writer.append(block).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn export_import_roundtrip() {
+        let file = TempFile::new();
+        let blocks = blocks(4);
+        assert_eq!(write_archive(&file.0, &blocks), 3);
+
+        verify_checksum(&file.0).unwrap();
+        let mut reader = ArchiveReader::open(&file.0, std::u32::MAX).unwrap();
+        assert_eq!(reader.block0, blocks[0].header.hash());
+        for block in &blocks[1..] {
+            let read = reader.next_block().unwrap().unwrap();
+            assert_eq!(read.header.hash(), block.header.hash());
+            assert_eq!(read.header.chain_length(), block.header.chain_length());
+        }
+        assert!(reader.next_block().unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupted_archive_is_refused() {
+        let file = TempFile::new();
+        write_archive(&file.0, &blocks(3));
+        let mut bytes = fs::read(&file.0).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 1;
+        fs::write(&file.0, &bytes).unwrap();
+
+        match verify_checksum(&file.0) {
+            Err(ArchiveError::ChecksumMismatch) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn block_larger_than_the_maximum_is_refused() {
+        let file = TempFile::new();
+        write_archive(&file.0, &blocks(2));
+
+        let mut reader = ArchiveReader::open(&file.0, 16).unwrap();
+        match reader.next_block() {
+            Err(ArchiveError::BlockTooLarge { max: 16, .. }) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+}
@@ -49,7 +49,11 @@ See Internal documentation for more details: doc/internal_design.md
 [`Branch`]: ./struct.Branch.html
 */
 
-use super::{branch::Branches, reference_cache::RefCache};
+use super::{
+    branch::Branches,
+    ledger_checkpoint::{self, LedgerCheckpoint},
+    reference_cache::RefCache,
+};
 use crate::{
     blockcfg::{
         Block, Block0Error, BlockDate, ChainLength, Epoch, EpochRewardsInfo, Header, HeaderHash,
@@ -458,7 +462,7 @@ impl Blockchain {
         })
     }
 
-    fn apply_block_dry_run(
+    pub fn apply_block_dry_run(
         &self,
         post_checked_header: &PostCheckedHeader,
         block: &Block,
@@ -638,6 +642,9 @@ impl Blockchain {
     /// from the block0 to the `Head` of the storage (the last known block which
     /// made consensus).
     ///
+    /// If there is a valid ledger checkpoint in the storage directory, the
+    /// states are propagated from the newest one instead of the block0.
+    ///
     /// The Future will returns a branch pointing to the `Head`.
     ///
     /// # Errors
@@ -672,16 +679,31 @@ impl Blockchain {
             return Err(ErrorKind::NoTag(MAIN_BRANCH_TAG.to_owned()).into());
         };
 
-        let block0_branch = self.apply_block0(&block0).await?;
+        let mut branch = self.apply_block0(&block0).await?;
+
+        let from = match self.load_ledger_checkpoint(head_hash, logger).await? {
+            Some(checkpoint) => {
+                let time_frame = Arc::clone(branch.get_ref().await.time_frame());
+                let checkpoint_ref = self.apply_ledger_checkpoint(checkpoint, time_frame).await;
+                info!(
+                    logger,
+                    "loaded the ledger checkpoint at {}",
+                    checkpoint_ref.header().description()
+                );
+                let from = checkpoint_ref.hash();
+                let _: Arc<Ref> = branch.update_ref(checkpoint_ref).await;
+                from
+            }
+            None => block0_id,
+        };
 
         let mut block_stream = self
             .storage
-            .stream_from_to(block0_id, head_hash)
+            .stream_from_to(from, head_hash)
             .await
             .map(Box::pin)
             .map_err(|e| Error::with_chain(e, "Cannot iterate blocks from block0 to HEAD"))?;
 
-        let mut branch = block0_branch;
         let mut count = 0u64;
 
         let mut block_processing = std::time::Duration::from_secs(0);
@@ -757,6 +779,117 @@ impl Blockchain {
         Ok(branch)
     }
 
+    /// the newest valid ledger checkpoint of an ancestor of `head`, the
+    /// corrupted checkpoints are skipped.
+    async fn load_ledger_checkpoint(
+        &self,
+        head: HeaderHash,
+        logger: &Logger,
+    ) -> Result<Option<LedgerCheckpoint>> {
+        let directory = match self.storage.directory() {
+            Some(directory) => directory.to_owned(),
+            None => return Ok(None),
+        };
+        let checkpoints = ledger_checkpoint::list(&directory)
+            .map_err(|e| Error::with_chain(e, "Cannot list the ledger checkpoints"))?;
+
+        for checkpoint_file in checkpoints {
+            let checkpoint = match ledger_checkpoint::load(&checkpoint_file) {
+                Ok(checkpoint) => checkpoint,
+                Err(e) => {
+                    warn!(
+                        logger,
+                        "cannot load the ledger checkpoint {}, skipping it",
+                        checkpoint_file.path.display();
+                        "reason" => %e,
+                    );
+                    continue;
+                }
+            };
+            let is_ancestor = self
+                .storage
+                .is_ancestor(checkpoint.block.header.hash(), head)
+                .await
+                .unwrap_or(false);
+            if !is_ancestor {
+                warn!(
+                    logger,
+                    "the ledger checkpoint {} is not on the main branch, skipping it",
+                    checkpoint_file.path.display()
+                );
+                continue;
+            }
+            return Ok(Some(checkpoint));
+        }
+        Ok(None)
+    }
+
+    /// create the `Ref`s of the blocks of the ledger checkpoint, the
+    /// blocks following the checkpoint can then be applied on top of it.
+    ///
+    /// The rewards of the epoch transitions are not in the checkpoint, the
+    /// `Ref`s have no rewards information.
+    async fn apply_ledger_checkpoint(
+        &self,
+        checkpoint: LedgerCheckpoint,
+        time_frame: Arc<TimeFrame>,
+    ) -> Arc<Ref> {
+        use chain_impl_mockchain::chaintypes::ConsensusVersion;
+
+        let LedgerCheckpoint {
+            block,
+            previous_epoch,
+            stake_ledger,
+        } = checkpoint;
+
+        // the leadership of the epoch of the checkpoint block, computed the
+        // same way as in `new_epoch_leadership_from`
+        let epoch = block.header.block_date().epoch;
+        let leadership =
+            if previous_epoch.ledger.consensus_version() == ConsensusVersion::GenesisPraos {
+                Leadership::new(
+                    epoch,
+                    stake_ledger.as_ref().unwrap_or(&previous_epoch.ledger),
+                )
+            } else {
+                Leadership::new(epoch, &previous_epoch.ledger)
+            };
+        let ledger_parameters = Arc::new(leadership.ledger_parameters().clone());
+
+        // only the ledger of the previous epoch is used, for the transition
+        // to the next epoch
+        let previous_epoch_leadership = Leadership::new(
+            previous_epoch.header.block_date().epoch,
+            &previous_epoch.ledger,
+        );
+        let previous_epoch_ledger_parameters =
+            Arc::new(previous_epoch_leadership.ledger_parameters().clone());
+        let previous_epoch_ref = self
+            .create_and_store_reference(
+                previous_epoch.header.hash(),
+                previous_epoch.header,
+                previous_epoch.ledger,
+                Arc::clone(&time_frame),
+                Arc::new(previous_epoch_leadership),
+                None,
+                previous_epoch_ledger_parameters,
+                None,
+            )
+            .await;
+
+        self.create_and_store_reference(
+            block.header.hash(),
+            block.header,
+            block.ledger,
+            time_frame,
+            Arc::new(leadership),
+            None,
+            ledger_parameters,
+            Some(previous_epoch_ref),
+        )
+        .await
+    }
+
     pub async fn get_checkpoints(&self, branch: &Branch) -> Checkpoints {
         Checkpoints::new_from(branch.get_ref().await)
     }
@@ -895,3 +1028,126 @@ pub fn new_epoch_leadership_from(
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockVersion, ContentsBuilder, Fragment};
+    use chain_addr::Discrimination;
+    use chain_impl_mockchain::testing::ledger::ConfigBuilder;
+    use rand_core::{OsRng, RngCore};
+    use std::{
+        convert::Infallible,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("checkpoint-{:016x}", OsRng.next_u64()));
+            fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn genesis_block(slot_id: u32) -> Block {
+        let config = ConfigBuilder::new(0)
+            .with_discrimination(Discrimination::Test)
+            .build();
+        let mut contents = ContentsBuilder::new();
+        contents.push(Fragment::Initial(config));
+        chain_impl_mockchain::block::builder(BlockVersion::Genesis, contents.into(), |hdr| {
+            let header: Result<Header, Infallible> = Ok(hdr
+                .set_genesis()
+                .set_date(BlockDate { epoch: 0, slot_id })
+                .into_unsigned_header()
+                .unwrap()
+                .generalize());
+            header
+        })
+        .unwrap()
+    }
+
+    fn save_checkpoint(directory: &Path, block: &Block) {
+        let ledger = Ledger::new(block.header.hash(), block.contents.iter()).unwrap();
+        ledger_checkpoint::save(
+            directory,
+            (&block.header, &ledger),
+            (&block.header, &ledger),
+            None,
+        )
+        .unwrap();
+    }
+
+    /// a blockchain with the given block0 stored in memory, the ledger
+    /// checkpoints being in the given directory
+    async fn blockchain(directory: &Path, block0: &Block) -> Blockchain {
+        let store = chain_storage::BlockStoreBuilder::memory()
+            .busy_timeout(1000)
+            .build();
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let storage = Storage::new(store, Some(directory.to_owned()), logger);
+        Blockchain::new(block0.header.hash(), storage.clone(), 10, false)
+            .load_from_block0(block0.clone())
+            .await
+            .unwrap();
+        Blockchain::new(block0.header.hash(), storage, 10, false)
+    }
+
+    #[tokio::test]
+    async fn corrupted_ledger_checkpoint_falls_back_to_a_full_replay() {
+        let directory = TempDir::new();
+        let block0 = genesis_block(0);
+        let blockchain = blockchain(&directory.0, &block0).await;
+        let logger = Logger::root(slog::Discard, slog::o!());
+        save_checkpoint(&directory.0, &block0);
+        let head = block0.header.hash();
+        assert!(blockchain
+            .load_ledger_checkpoint(head, &logger)
+            .await
+            .unwrap()
+            .is_some());
+
+        let path = &ledger_checkpoint::list(&directory.0).unwrap()[0].path;
+        let mut bytes = fs::read(path).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 1;
+        fs::write(path, &bytes).unwrap();
+
+        assert!(blockchain
+            .load_ledger_checkpoint(head, &logger)
+            .await
+            .unwrap()
+            .is_none());
+        let branch = blockchain.load_from_storage(block0, &logger).await.unwrap();
+        assert_eq!(branch.get_ref().await.hash(), head);
+    }
+
+    #[tokio::test]
+    async fn ledger_checkpoint_of_another_blockchain_is_skipped() {
+        let directory = TempDir::new();
+        let block0 = genesis_block(0);
+        let blockchain = blockchain(&directory.0, &block0).await;
+        let logger = Logger::root(slog::Discard, slog::o!());
+        // a valid checkpoint, the hash of which does not match any block of
+        // the storage
+        save_checkpoint(&directory.0, &genesis_block(1));
+
+        let head = block0.header.hash();
+        assert!(blockchain
+            .load_ledger_checkpoint(head, &logger)
+            .await
+            .unwrap()
+            .is_none());
+        let branch = blockchain.load_from_storage(block0, &logger).await.unwrap();
+        assert_eq!(branch.get_ref().await.hash(), head);
+    }
+}
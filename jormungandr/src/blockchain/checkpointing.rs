@@ -0,0 +1,124 @@
+//! Periodic ledger checkpoints.
+//!
+//! Loading the blockchain from the storage replays all the blocks from
+//! block0. To restart faster, the node periodically writes a checkpoint of the
+//! ledger of the last block of the newest epoch that is beyond the epoch
+//! stability depth of the tip, see `ledger_checkpoint`. On restart, only the
+//! blocks following the newest valid checkpoint are replayed.
+
+use super::{ledger_checkpoint, Ref, Tip};
+use crate::utils::task::TokioServiceInfo;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    task::{spawn_blocking, JoinError},
+    time::interval,
+};
+
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// the number of checkpoints kept in the storage directory
+const KEPT_CHECKPOINTS: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("cannot write the ledger checkpoint")]
+    Io(#[from] io::Error),
+    #[error("the ledger checkpoint task failed")]
+    Task(#[from] JoinError),
+}
+
+/// periodically write the ledger checkpoints in the storage directory
+pub async fn take_ledger_checkpoints(
+    service_info: TokioServiceInfo,
+    blockchain_tip: Tip,
+    directory: PathBuf,
+) {
+    let logger = service_info.logger().clone();
+    let mut interval = interval(CHECKPOINT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let tip = blockchain_tip.get_ref().await;
+
+        if let Some(candidate) = checkpoint_candidate(&tip) {
+            match write_checkpoint(&directory, &candidate).await {
+                Ok(false) => {}
+                Ok(true) => info!(logger, "ledger checkpoint written";
+                    "block" => %candidate.header().description(),
+                ),
+                Err(error) => {
+                    error!(logger, "cannot write the ledger checkpoint"; "reason" => %error);
+                }
+            }
+        }
+    }
+}
+
+/// the newest last block of an epoch the checkpoint can be taken of
+fn checkpoint_candidate(tip: &Ref) -> Option<Arc<Ref>> {
+    let stability_depth = tip.epoch_ledger_parameters().epoch_stability_depth;
+    let tip_length: u32 = tip.chain_length().into();
+    let limit = tip_length.saturating_sub(stability_depth);
+
+    let mut candidate = tip.last_ref_previous_epoch();
+    while let Some(block) = candidate {
+        if u32::from(block.chain_length()) <= limit {
+            break;
+        }
+        candidate = block.last_ref_previous_epoch();
+    }
+    let block = candidate?;
+
+    // the checkpoint needs the ledgers of the last blocks of the two epochs
+    // before, they are only missing in the first epoch or if the node was
+    // loaded from a checkpoint since, in which case the candidate is not newer
+    // than the checkpoint.
+    let previous_epoch = block.last_ref_previous_epoch()?;
+    if previous_epoch.last_ref_previous_epoch().is_none() && previous_epoch.block_date().epoch > 0 {
+        return None;
+    }
+
+    Some(Arc::clone(block))
+}
+
+/// write the checkpoint of the given block unless there is already a checkpoint
+/// as recent, returns whether the checkpoint has been written
+async fn write_checkpoint(directory: &Path, block: &Ref) -> Result<bool, CheckpointError> {
+    let previous_epoch = block
+        .last_ref_previous_epoch()
+        .expect("checked by checkpoint_candidate");
+    let stake_ledger = previous_epoch
+        .last_ref_previous_epoch()
+        .map(|stake| stake.ledger());
+
+    let directory = directory.to_owned();
+    let chain_length: u32 = block.chain_length().into();
+    let header = block.header().clone();
+    let ledger = block.ledger();
+    let previous_epoch_header = previous_epoch.header().clone();
+    let previous_epoch_ledger = previous_epoch.ledger();
+    let written = spawn_blocking(move || {
+        let newest = ledger_checkpoint::list(&directory)?
+            .first()
+            .map(|checkpoint| checkpoint.chain_length);
+        if newest.map_or(false, |newest| newest >= chain_length) {
+            return Ok(false);
+        }
+        ledger_checkpoint::save(
+            &directory,
+            (&header, &*ledger),
+            (&previous_epoch_header, &*previous_epoch_ledger),
+            stake_ledger.as_deref(),
+        )?;
+        ledger_checkpoint::remove_old(&directory, KEPT_CHECKPOINTS)?;
+        Ok::<_, io::Error>(true)
+    })
+    .await??;
+    Ok(written)
+}
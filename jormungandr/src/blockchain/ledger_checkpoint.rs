@@ -0,0 +1,207 @@
+//! Ledger checkpoints written in the storage directory.
+//!
+//! Loading the blockchain from the storage replays the blocks from block0,
+//! which takes a long time on a long blockchain. A checkpoint holds the state
+//! needed to replay the blocks from the last block of an epoch instead:
+//!
+//! * the header of that block and the ledger after it;
+//! * the header of the last block of the epoch before and the ledger after
+//!   it, used for the transition to the next epoch;
+//! * the ledger after the last block of the epoch before that one, the stake
+//!   distribution of the epoch of the checkpoint block is computed from it.
+//!
+//! The leadership of the epochs and the multiverse of the blocks are not
+//! written: the leadership is computed again from these ledgers and the
+//! multiverse is filled with the blocks replayed after the checkpoint. The
+//! rewards of the epoch transitions before the checkpoint are not written
+//! either, they are not available anymore after a restart.
+//!
+//! The checkpoints are named after the chain length of their block. A file is
+//! laid out as:
+//!
+//! ```text
+//! | magic (4) | version (1) | block | previous epoch block | has stake ledger (1) | stake ledger | hash (32) |
+//! ```
+//!
+//! where every header and ledger is prefixed by its size (8, BE) and the
+//! hash is the Blake2b256 hash of everything before it.
+
+use crate::blockcfg::{ChainLength, Header, Ledger};
+use chain_core::property::{Deserialize, Serialize};
+use chain_crypto::Blake2b256;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 4] = b"JLCK";
+/// the version 1 had no integrity hash and a single checkpoint file
+const VERSION: u8 = 2;
+const FILE_PREFIX: &str = "ledger-checkpoint.";
+const HASH_SIZE: usize = 32;
+
+pub struct CheckpointBlock {
+    pub header: Header,
+    pub ledger: Ledger,
+}
+
+pub struct LedgerCheckpoint {
+    pub block: CheckpointBlock,
+    pub previous_epoch: CheckpointBlock,
+    pub stake_ledger: Option<Ledger>,
+}
+
+/// a checkpoint file of the storage directory
+pub struct CheckpointFile {
+    pub chain_length: u32,
+    pub path: PathBuf,
+}
+
+/// write the checkpoint of the given block, the file only appears once it is
+/// completely written
+pub fn save(
+    directory: &Path,
+    block: (&Header, &Ledger),
+    previous_epoch: (&Header, &Ledger),
+    stake_ledger: Option<&Ledger>,
+) -> io::Result<()> {
+    let chain_length: u32 = block.0.chain_length().into();
+    let path = directory.join(format!("{}{}", FILE_PREFIX, chain_length));
+    let tmp_path = directory.join(format!("{}{}.tmp", FILE_PREFIX, chain_length));
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    write_item(&mut bytes, block.0)?;
+    write_item(&mut bytes, block.1)?;
+    write_item(&mut bytes, previous_epoch.0)?;
+    write_item(&mut bytes, previous_epoch.1)?;
+    match stake_ledger {
+        Some(ledger) => {
+            bytes.push(1);
+            write_item(&mut bytes, ledger)?;
+        }
+        None => bytes.push(0),
+    }
+    let hash = Blake2b256::new(&bytes);
+    bytes.extend_from_slice(hash.as_ref());
+
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+    fs::rename(tmp_path, path)
+}
+
+/// the checkpoints of the storage directory, the newest first
+pub fn list(directory: &Path) -> io::Result<Vec<CheckpointFile>> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut checkpoints = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let chain_length = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| name.starts_with(FILE_PREFIX))
+            .and_then(|name| name[FILE_PREFIX.len()..].parse().ok());
+        if let Some(chain_length) = chain_length {
+            checkpoints.push(CheckpointFile { chain_length, path });
+        }
+    }
+    checkpoints.sort_by(|a, b| b.chain_length.cmp(&a.chain_length));
+    Ok(checkpoints)
+}
+
+/// remove the checkpoints older than the `keep` newest ones
+pub fn remove_old(directory: &Path, keep: usize) -> io::Result<()> {
+    for checkpoint in list(directory)?.into_iter().skip(keep) {
+        fs::remove_file(checkpoint.path)?;
+    }
+    Ok(())
+}
+
+/// read a checkpoint, checking its integrity
+pub fn load(checkpoint: &CheckpointFile) -> io::Result<LedgerCheckpoint> {
+    let mut bytes = Vec::new();
+    File::open(&checkpoint.path)?.read_to_end(&mut bytes)?;
+
+    if bytes.len() < MAGIC.len() + 1 + HASH_SIZE {
+        return Err(invalid_data("the ledger checkpoint is truncated"));
+    }
+    let (contents, hash) = bytes.split_at(bytes.len() - HASH_SIZE);
+    if Blake2b256::new(contents).as_ref() != hash {
+        return Err(invalid_data("the ledger checkpoint is corrupted"));
+    }
+    if &contents[..4] != MAGIC || contents[4] != VERSION {
+        return Err(invalid_data(
+            "not a ledger checkpoint or unsupported version",
+        ));
+    }
+
+    let mut reader = &contents[5..];
+    let block = CheckpointBlock {
+        header: read_item(&mut reader)?,
+        ledger: read_item(&mut reader)?,
+    };
+    let previous_epoch = CheckpointBlock {
+        header: read_item(&mut reader)?,
+        ledger: read_item(&mut reader)?,
+    };
+    let mut has_stake_ledger = [0];
+    reader.read_exact(&mut has_stake_ledger)?;
+    let stake_ledger = if has_stake_ledger[0] == 1 {
+        Some(read_item(&mut reader)?)
+    } else {
+        None
+    };
+
+    if block.header.chain_length() != ChainLength::from(checkpoint.chain_length) {
+        return Err(invalid_data(
+            "the ledger checkpoint does not match its file name",
+        ));
+    }
+
+    Ok(LedgerCheckpoint {
+        block,
+        previous_epoch,
+        stake_ledger,
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_item<W, T>(writer: &mut W, item: &T) -> io::Result<()>
+where
+    W: Write,
+    T: Serialize,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    let bytes = item
+        .serialize_as_vec()
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    writer.write_all(&(bytes.len() as u64).to_be_bytes())?;
+    writer.write_all(&bytes)
+}
+
+fn read_item<R, T>(reader: &mut R) -> io::Result<T>
+where
+    R: Read,
+    T: Deserialize,
+    T::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    let mut size = [0; 8];
+    reader.read_exact(&mut size)?;
+    let mut bytes = vec![0; u64::from_be_bytes(size) as usize];
+    reader.read_exact(&mut bytes)?;
+    T::deserialize(bytes.as_slice())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
@@ -1,13 +1,17 @@
+mod archive;
 mod branch;
 mod candidate;
 mod chain;
 mod chain_selection;
+mod checkpointing;
 mod checkpoints;
+mod ledger_checkpoint;
 mod multiverse;
 mod process;
 mod reference;
 mod reference_cache;
 mod storage;
+mod storage_check;
 mod tip;
 
 // Constants
@@ -25,16 +29,19 @@ mod chunk_sizes {
 // Re-exports
 
 pub use self::{
+    archive::{export, import, ArchiveError, BlockSelector},
     branch::Branch,
     chain::{
-        new_epoch_leadership_from, Blockchain, CheckHeaderProof, Error, ErrorKind,
-        PreCheckedHeader, MAIN_BRANCH_TAG,
+        new_epoch_leadership_from, pre_verify_link, Blockchain, CheckHeaderProof, Error, ErrorKind,
+        HeaderChainVerifyError, PreCheckedHeader, MAIN_BRANCH_TAG,
     },
     chain_selection::{compare_against, ComparisonResult},
+    checkpointing::take_ledger_checkpoints,
     checkpoints::Checkpoints,
     multiverse::Multiverse,
     process::{process_new_ref, Process},
     reference::Ref,
-    storage::Storage,
+    storage::{replace_truncated_database, Storage, BLOCKSTORE_FILE_NAME},
+    storage_check::{check_storage, StorageCheckError, StorageCheckReport},
     tip::Tip,
 };
@@ -9,7 +9,7 @@ use crate::{
     blockchain::Checkpoints,
     intercom::{self, BlockMsg, ExplorerMsg, NetworkMsg, PropagateMsg, TransactionMsg},
     log,
-    network::p2p::Address,
+    network::p2p::{Address, BlockOutcome},
     stats_counter::StatsCounter,
     utils::{
         async_msg::{self, MessageBox, MessageQueue},
@@ -136,13 +136,14 @@ impl Process {
                         blockchain_tip.clone(),
                         header,
                         node_id,
+                        network_msg_box,
                         pull_headers_scheduler.clone(),
                         get_next_block_scheduler.clone(),
                         logger.clone(),
                     ),
                 )
             }
-            BlockMsg::NetworkBlocks(handle) => {
+            BlockMsg::NetworkBlocks(handle, node_id) => {
                 info!(info.logger(), "receiving block stream from network");
 
                 let logger = info.logger().clone();
@@ -159,12 +160,13 @@ impl Process {
                         explorer_msg_box,
                         get_next_block_scheduler,
                         handle,
+                        node_id,
                         stats_counter,
                         logger,
                     ),
                 );
             }
-            BlockMsg::ChainHeaders(handle) => {
+            BlockMsg::ChainHeaders(handle, node_id) => {
                 info!(info.logger(), "receiving header stream from network");
 
                 let logger = info.logger().new(o!(log::KEY_SUB_TASK => "chain_pull"));
@@ -177,6 +179,7 @@ impl Process {
                         logger,
                         blockchain,
                         handle,
+                        node_id,
                         pull_headers_scheduler,
                         network_msg_box,
                     ),
@@ -261,6 +264,40 @@ fn try_request_fragment_removal(
     tx_msg_box.try_send(TransactionMsg::RemoveTransactions(fragment_ids, status))
 }
 
+/// report the result of the validation of data received from a node to
+/// the network task, to score the node
+fn report_peer_outcome(
+    network_msg_box: &mut MessageBox<NetworkMsg>,
+    node_id: Address,
+    outcome: BlockOutcome,
+    logger: &Logger,
+) {
+    network_msg_box
+        .try_send(NetworkMsg::ScorePeer(node_id, outcome))
+        .unwrap_or_else(
+            |err| error!(logger, "cannot send the peer outcome to network"; "reason" => %err),
+        );
+}
+
+/// whether the block or header failed validation, as opposed to not being
+/// processable by the node yet
+fn is_invalid_block_error(err: &chain::Error) -> bool {
+    matches!(
+        err.0,
+        ErrorKind::Ledger(_) | ErrorKind::BlockHeaderVerificationFailed(_)
+    )
+}
+
+/// whether the header stream failed validation
+fn is_invalid_header_error(err: &candidate::Error) -> bool {
+    match err {
+        candidate::Error::Blockchain(e) => is_invalid_block_error(e),
+        candidate::Error::BrokenHeaderChain(_)
+        | candidate::Error::HeaderChainVerificationFailed(_) => true,
+        _ => false,
+    }
+}
+
 /// this function will re-process the tip against the different branches
 /// this is because a branch may have become more interesting with time
 /// moving forward and branches may have been dismissed
@@ -449,14 +486,25 @@ async fn process_block_announcement(
     blockchain_tip: Tip,
     header: Header,
     node_id: Address,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut get_next_block_scheduler: GetNextBlockScheduler,
     logger: Logger,
 ) -> Result<(), Error> {
-    let pre_checked = blockchain
-        .pre_check_header(header, false)
-        .await
-        .map_err(|err| Error::with_chain(err, "cannot process block announcement"))?;
+    let pre_checked = match blockchain.pre_check_header(header, false).await {
+        Ok(pre_checked) => pre_checked,
+        Err(err) => {
+            if is_invalid_block_error(&err) {
+                report_peer_outcome(
+                    &mut network_msg_box,
+                    node_id,
+                    BlockOutcome::Invalid,
+                    &logger,
+                );
+            }
+            return Err(Error::with_chain(err, "cannot process block announcement"));
+        }
+    };
     match pre_checked {
         PreCheckedHeader::AlreadyPresent { .. } => {
             debug!(logger, "block is already present");
@@ -501,16 +549,19 @@ async fn process_network_blocks(
     mut blockchain: Blockchain,
     blockchain_tip: Tip,
     mut tx_msg_box: MessageBox<TransactionMsg>,
-    network_msg_box: MessageBox<NetworkMsg>,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     mut explorer_msg_box: Option<MessageBox<ExplorerMsg>>,
     mut get_next_block_scheduler: GetNextBlockScheduler,
     handle: intercom::RequestStreamHandle<Block, ()>,
+    node_id: Option<Address>,
     stats_counter: StatsCounter,
     logger: Logger,
 ) -> Result<(), Error> {
     let (mut stream, reply) = handle.into_stream_and_reply();
     let mut candidate = None;
     let mut latest_block: Option<Arc<Block>> = None;
+    let mut applied_blocks = 0;
+    let mut invalid_block = false;
 
     let maybe_updated: Option<Arc<Ref>> = loop {
         let (maybe_block, stream_tail) = stream.into_future().await;
@@ -529,6 +580,7 @@ async fn process_network_blocks(
                 match res {
                     Ok(Some(r)) => {
                         stats_counter.add_block_recv_cnt(1);
+                        applied_blocks += 1;
                         stream = stream_tail;
                         candidate = Some(r);
                     }
@@ -542,6 +594,7 @@ async fn process_network_blocks(
                             "validation of an incoming block failed";
                             "reason" => ?e,
                         );
+                        invalid_block = is_invalid_block_error(&e);
                         reply.reply_error(network_block_error_into_reply(e));
                         break candidate;
                     }
@@ -554,6 +607,25 @@ async fn process_network_blocks(
         }
     };
 
+    if let Some(node_id) = node_id {
+        if applied_blocks > 0 {
+            report_peer_outcome(
+                &mut network_msg_box,
+                node_id.clone(),
+                BlockOutcome::Useful(applied_blocks),
+                &logger,
+            );
+        }
+        if invalid_block {
+            report_peer_outcome(
+                &mut network_msg_box,
+                node_id,
+                BlockOutcome::Invalid,
+                &logger,
+            );
+        }
+    }
+
     match maybe_updated {
         Some(new_block_ref) => {
             let r = process_and_propagate_new_ref(
@@ -692,6 +764,7 @@ async fn process_chain_headers(
     logger: Logger,
     blockchain: Blockchain,
     handle: intercom::RequestStreamHandle<Header, ()>,
+    node_id: Option<Address>,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut network_msg_box: MessageBox<NetworkMsg>,
 ) {
@@ -703,6 +776,16 @@ async fn process_chain_headers(
                 "error processing an incoming header stream";
                 "reason" => %e,
             );
+            if let Some(node_id) = node_id {
+                if is_invalid_header_error(&e) {
+                    report_peer_outcome(
+                        &mut network_msg_box,
+                        node_id,
+                        BlockOutcome::Invalid,
+                        &logger,
+                    );
+                }
+            }
             reply.reply_error(chain_header_error_into_reply(e));
         }
         Ok((header_ids, _maybe_remainder)) => {
@@ -713,6 +796,17 @@ async fn process_chain_headers(
                     |e| error!(logger, "get blocks schedule completion failed"; "reason" => ?e),
                 );
 
+            if let Some(node_id) = node_id {
+                if !header_ids.is_empty() {
+                    report_peer_outcome(
+                        &mut network_msg_box,
+                        node_id,
+                        BlockOutcome::Useful(header_ids.len() as u32),
+                        &logger,
+                    );
+                }
+            }
+
             if header_ids.is_empty() {
                 ()
             } else {
@@ -1,17 +1,21 @@
 use crate::{
     blockcfg::{Block, HeaderHash},
     intercom::{self, ReplySendError, ReplyStreamHandle},
-    start_up::{NodeStorage, NodeStorageConnection},
+    start_up::{NodeStorage, NodeStorageConnection, BLOCKSTORE_BUSY_TIMEOUT},
 };
-use chain_storage::{for_path_to_nth_ancestor, BlockInfo};
+use chain_storage::{for_path_to_nth_ancestor, BlockInfo, BlockStoreBuilder};
 use futures::{prelude::*, ready, stream::FusedStream};
 use pin_utils::{unsafe_pinned, unsafe_unpinned};
 use r2d2::{ManageConnection, Pool};
 use slog::Logger;
 use tokio::task::spawn_blocking;
 
+use std::collections::HashSet;
 use std::convert::identity;
 use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -22,6 +26,13 @@ const BLOCK_STREAM_BUFFER_SIZE: usize = 32;
 // before priming the pump again.
 const PUMP_PRESSURE_MARGIN: usize = 4;
 
+/// name of the database of the blocks in the storage directory
+pub const BLOCKSTORE_FILE_NAME: &str = "blocks.sqlite";
+
+/// the database written by `Storage::truncate`, replacing the database of the
+/// blocks the next time the storage is opened
+const TRUNCATED_BLOCKSTORE_FILE_NAME: &str = "blocks.sqlite.truncated";
+
 pub use chain_storage::Error as StorageError;
 
 async fn run_blocking_with_connection<F, T, E>(pool: Pool<ConnectionManager>, f: F) -> Result<T, E>
@@ -104,6 +115,8 @@ impl ManageConnection for ConnectionManager {
 #[derive(Clone)]
 pub struct Storage {
     pool: Pool<ConnectionManager>,
+    /// the storage directory, `None` if the blocks are stored in memory
+    directory: Option<PathBuf>,
     logger: Logger,
 }
 
@@ -142,11 +155,19 @@ enum StreamingError {
 }
 
 impl Storage {
-    pub fn new(storage: NodeStorage, logger: Logger) -> Self {
+    pub fn new(storage: NodeStorage, directory: Option<PathBuf>, logger: Logger) -> Self {
         let manager = ConnectionManager::new(storage);
         let pool = Pool::builder().build(manager).unwrap();
 
-        Storage { pool, logger }
+        Storage {
+            pool,
+            directory,
+            logger,
+        }
+    }
+
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
     }
 
     async fn run<F, T, E>(&self, f: F) -> Result<T, E>
@@ -214,6 +235,20 @@ impl Storage {
         .await
     }
 
+    pub async fn get_blocks_with_infos_by_chain_length(
+        &self,
+        chain_length: u64,
+    ) -> Result<Vec<(Block, BlockInfo<HeaderHash>)>, StorageError> {
+        self.run(
+            move |connection| match connection.get_blocks_by_chain_length(chain_length) {
+                Err(StorageError::BlockNotFound) => Ok(Vec::new()),
+                Ok(r) => Ok(r),
+                Err(e) => Err(e),
+            },
+        )
+        .await
+    }
+
     pub async fn get_block_infos_by_chain_length(
         &self,
         chain_length: u64,
@@ -237,6 +272,85 @@ impl Storage {
         .await
     }
 
+    /// Check if `ancestor` is an ancestor of `descendant`, or the same block.
+    pub async fn is_ancestor(
+        &self,
+        ancestor: HeaderHash,
+        descendant: HeaderHash,
+    ) -> Result<bool, StorageError> {
+        self.run(move |connection| {
+            connection
+                .is_ancestor(&ancestor, &descendant)
+                .map(|distance| distance.is_some())
+        })
+        .await
+    }
+
+    /// Truncate the storage to the blocks with a chain length lower than
+    /// `below` for which `keep` is true, and the given tags.
+    ///
+    /// chain-storage cannot remove blocks: the blocks kept are copied, by
+    /// chain length, into a new database of the storage directory which
+    /// replaces the current one the next time the storage is opened, see
+    /// `replace_truncated_database`. The blocks the parent of which is not
+    /// kept are not copied. Returns the number of blocks kept.
+    pub async fn truncate<F>(
+        &self,
+        below: u64,
+        keep: F,
+        tags: Vec<(String, HeaderHash)>,
+    ) -> Result<u64, StorageError>
+    where
+        F: Fn(&HeaderHash) -> bool + Send + 'static,
+    {
+        let directory = self.directory.clone().ok_or_else(|| {
+            StorageError::BackendError("the blocks are not stored in a directory".into())
+        })?;
+        self.run(move |connection| {
+            let path = directory.join(format!("{}.new", TRUNCATED_BLOCKSTORE_FILE_NAME));
+            // left by an interrupted truncation
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| StorageError::BackendError(e.into()))?;
+            }
+            let truncated = BlockStoreBuilder::file(path.clone())
+                .busy_timeout(BLOCKSTORE_BUSY_TIMEOUT)
+                .build();
+            let mut truncated_connection = truncated.connect()?;
+
+            let mut kept = HashSet::new();
+            for chain_length in 0..below {
+                let blocks = match connection.get_blocks_by_chain_length(chain_length) {
+                    Ok(blocks) => blocks,
+                    Err(StorageError::BlockNotFound) => Vec::new(),
+                    Err(e) => return Err(e),
+                };
+                if blocks.is_empty() {
+                    break;
+                }
+                for (block, info) in blocks {
+                    let hash = info.block_hash;
+                    if !keep(&hash) || (chain_length > 0 && !kept.contains(&info.parent_id())) {
+                        continue;
+                    }
+                    truncated_connection.put_block(&block)?;
+                    kept.insert(hash);
+                }
+            }
+            for (tag, hash) in tags {
+                if kept.contains(&hash) {
+                    truncated_connection.put_tag(&tag, &hash)?;
+                }
+            }
+
+            drop(truncated_connection);
+            drop(truncated);
+            fs::rename(&path, directory.join(TRUNCATED_BLOCKSTORE_FILE_NAME))
+                .map_err(|e| StorageError::BackendError(e.into()))?;
+            Ok(kept.len() as u64)
+        })
+        .await
+    }
+
     /// Return values:
     /// - `Ok(stream)` - `from` is ancestor of `to`, returns blocks between them
     /// - `Err(CannotIterate)` - `from` is not ancestor of `to`
@@ -568,3 +682,23 @@ where
         })
     }
 }
+
+/// Replace the database of the blocks of the storage directory with the
+/// database written by `Storage::truncate`, if any. Must be called before the
+/// storage is opened. Returns whether the database has been replaced.
+pub fn replace_truncated_database(directory: &Path) -> Result<bool, io::Error> {
+    let truncated = directory.join(TRUNCATED_BLOCKSTORE_FILE_NAME);
+    if !truncated.exists() {
+        return Ok(false);
+    }
+    // the journals of the replaced database must not be applied to the
+    // truncated one
+    for suffix in &["-journal", "-wal", "-shm"] {
+        let journal = directory.join(format!("{}{}", BLOCKSTORE_FILE_NAME, suffix));
+        if journal.exists() {
+            fs::remove_file(journal)?;
+        }
+    }
+    fs::rename(truncated, directory.join(BLOCKSTORE_FILE_NAME))?;
+    Ok(true)
+}
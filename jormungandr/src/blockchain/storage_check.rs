@@ -0,0 +1,625 @@
+//! Offline integrity check of the block storage.
+//!
+//! A node stopped in the middle of a write, by a power loss for example, can
+//! leave the storage with blocks that cannot be read or do not link to their
+//! parent, which only shows up later as storage errors while running. The
+//! check walks the blocks of the storage by chain length from block0 and
+//! verifies, for every block:
+//!
+//! * that its header can be read and hashes to the hash it is stored under,
+//!   and that its contents match the hash and size given by the header;
+//! * that its chain length and parent match the information stored with it;
+//! * that its parent is a consistent block of the previous chain length.
+//!
+//! The tip tag must point to a consistent block.
+//!
+//! The repair truncates the storage back to the last chain length at which
+//! the blocks of the main branch, the ancestors of the tip, are consistent
+//! and moves the tip there if needed. The inconsistent blocks of the other
+//! branches do not truncate the main branch, they are left out of the
+//! truncated storage. The truncated storage replaces the current one the next
+//! time the node opens the storage.
+
+use super::{Storage, StorageError, MAIN_BRANCH_TAG};
+use crate::blockcfg::HeaderHash;
+use jormungandr_lib::crypto::hash::Hash;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StorageCheckError {
+    #[error("the storage check requires a storage directory")]
+    NoStorageDirectory,
+    #[error("block0 of the storage is not consistent, the storage cannot be repaired")]
+    Block0Inconsistent,
+    #[error("cannot repair the storage")]
+    Repair(#[source] StorageError),
+}
+
+/// an inconsistency found in the storage
+#[derive(Debug, Error)]
+pub enum Issue {
+    #[error("block0 of the storage is {found}, expected {expected}")]
+    Block0Mismatch {
+        found: HeaderHash,
+        expected: HeaderHash,
+    },
+    #[error("the blocks of chain length {chain_length} cannot be read: {reason}")]
+    Unreadable { chain_length: u64, reason: String },
+    #[error("the contents of block {hash} (chain length {chain_length}) are missing")]
+    MissingContents { chain_length: u64, hash: HeaderHash },
+    #[error("the contents of block {hash} (chain length {chain_length}) do not match the block")]
+    ContentsMismatch { chain_length: u64, hash: HeaderHash },
+    #[error("the parent of block {hash} (chain length {chain_length}) is not in the storage")]
+    MissingParent { chain_length: u64, hash: HeaderHash },
+    #[error("the tag {tag} cannot be read: {reason}")]
+    UnreadableTag { tag: String, reason: String },
+    #[error("the tag {tag} is missing")]
+    MissingTag { tag: String },
+    #[error("the tag {tag} points to block {hash} which is missing or not consistent")]
+    DanglingTag { tag: String, hash: HeaderHash },
+}
+
+impl Issue {
+    /// the chain length of the inconsistent block, if the issue is about
+    /// a block
+    fn chain_length(&self) -> Option<u64> {
+        match self {
+            Issue::Block0Mismatch { .. } => Some(0),
+            Issue::Unreadable { chain_length, .. }
+            | Issue::MissingContents { chain_length, .. }
+            | Issue::ContentsMismatch { chain_length, .. }
+            | Issue::MissingParent { chain_length, .. } => Some(*chain_length),
+            Issue::UnreadableTag { .. } | Issue::MissingTag { .. } | Issue::DanglingTag { .. } => {
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageCheckReport {
+    pub blocks: u64,
+    /// the tip of the storage, after the repair if any
+    pub tip: Option<Hash>,
+    pub issues: Vec<String>,
+    /// `None` if the storage was not repaired
+    pub repair: Option<StorageRepair>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageRepair {
+    /// the blocks of this chain length and above have been removed
+    pub truncated_from: Option<u64>,
+    pub removed_blocks: u64,
+    pub tip: Hash,
+}
+
+/// the consistent blocks found by the walk
+struct Walk {
+    blocks: u64,
+    issues: Vec<Issue>,
+    /// the parents of all the blocks the information of which could be
+    /// read, consistent or not, and their chain length
+    parents: HashMap<HeaderHash, (HeaderHash, u64)>,
+    consistent: HashSet<HeaderHash>,
+    /// the highest chain length with consistent blocks
+    last_chain_length: u64,
+}
+
+/// check the blocks and the tip of the storage, truncating it back to the
+/// last consistent chain length if `repair` is set
+pub async fn check_storage(
+    storage: &Storage,
+    block0: HeaderHash,
+    repair: bool,
+) -> Result<StorageCheckReport, StorageCheckError> {
+    // the repair writes the truncated storage in the storage directory
+    if storage.directory().is_none() {
+        return Err(StorageCheckError::NoStorageDirectory);
+    }
+
+    let mut walk = walk_blocks(storage, block0).await;
+
+    let head = match storage.get_tag(MAIN_BRANCH_TAG.to_owned()).await {
+        Ok(Some(head)) => {
+            if !walk.consistent.contains(&head) {
+                walk.issues.push(Issue::DanglingTag {
+                    tag: MAIN_BRANCH_TAG.to_owned(),
+                    hash: head,
+                });
+            }
+            Some(head)
+        }
+        Ok(None) => {
+            walk.issues.push(Issue::MissingTag {
+                tag: MAIN_BRANCH_TAG.to_owned(),
+            });
+            None
+        }
+        Err(e) => {
+            walk.issues.push(Issue::UnreadableTag {
+                tag: MAIN_BRANCH_TAG.to_owned(),
+                reason: e.to_string(),
+            });
+            None
+        }
+    };
+
+    let truncated_from = main_branch_truncation(&walk, head);
+    let issues = walk.issues.iter().map(ToString::to_string).collect();
+
+    let repair = if repair && !walk.issues.is_empty() {
+        if truncated_from == Some(0) {
+            return Err(StorageCheckError::Block0Inconsistent);
+        }
+        Some(repair_storage(storage, &walk, head, truncated_from).await?)
+    } else {
+        None
+    };
+
+    let tip = match &repair {
+        Some(repair) => Some(repair.tip),
+        None => head.map(Into::into),
+    };
+
+    Ok(StorageCheckReport {
+        blocks: walk.blocks,
+        tip,
+        issues,
+        repair,
+    })
+}
+
+/// the chain length from which the main branch, ending at `head`, is not
+/// consistent. If the main branch cannot be followed, from the lowest chain
+/// length of the issues.
+fn main_branch_truncation(walk: &Walk, head: Option<HeaderHash>) -> Option<u64> {
+    let lowest_issue = walk.issues.iter().filter_map(Issue::chain_length).min();
+    let mut first_inconsistent = None;
+    let mut current = match head {
+        Some(head) => head,
+        None => return lowest_issue,
+    };
+    loop {
+        match walk.parents.get(&current) {
+            Some((parent, chain_length)) => {
+                if !walk.consistent.contains(&current) {
+                    first_inconsistent = Some(*chain_length);
+                }
+                if *chain_length == 0 {
+                    return first_inconsistent;
+                }
+                current = *parent;
+            }
+            None => return first_inconsistent.or(lowest_issue),
+        }
+    }
+}
+
+async fn walk_blocks(storage: &Storage, block0: HeaderHash) -> Walk {
+    let mut walk = Walk {
+        blocks: 0,
+        issues: Vec::new(),
+        parents: HashMap::new(),
+        consistent: HashSet::new(),
+        last_chain_length: 0,
+    };
+    let mut previous_level: HashSet<HeaderHash> = HashSet::new();
+    let mut chain_length = 0;
+
+    loop {
+        let infos = match storage.get_block_infos_by_chain_length(chain_length).await {
+            Ok(infos) => infos,
+            Err(e) => {
+                walk.issues.push(Issue::Unreadable {
+                    chain_length,
+                    reason: e.to_string(),
+                });
+                break;
+            }
+        };
+        if infos.is_empty() {
+            break;
+        }
+        // above a chain length with no consistent block, the blocks are
+        // descendants of inconsistent blocks: only their parents are kept, to
+        // follow the main branch
+        let blocks: HashMap<HeaderHash, _> = if chain_length > 0 && previous_level.is_empty() {
+            HashMap::new()
+        } else {
+            match storage
+                .get_blocks_with_infos_by_chain_length(chain_length)
+                .await
+            {
+                Ok(blocks) => blocks
+                    .into_iter()
+                    .map(|(block, info)| (info.block_hash, block))
+                    .collect(),
+                Err(e) => {
+                    walk.issues.push(Issue::Unreadable {
+                        chain_length,
+                        reason: e.to_string(),
+                    });
+                    break;
+                }
+            }
+        };
+
+        let mut level = HashSet::new();
+        for info in infos {
+            let hash = info.block_hash;
+            let parent = info.parent_id();
+            walk.blocks += 1;
+            walk.parents.insert(hash, (parent, chain_length));
+
+            if chain_length == 0 {
+                if hash != block0 {
+                    walk.issues.push(Issue::Block0Mismatch {
+                        found: hash,
+                        expected: block0,
+                    });
+                    continue;
+                }
+            } else if !previous_level.contains(&parent) {
+                // the descendants of an inconsistent block are not reported
+                if !walk.parents.contains_key(&parent) {
+                    walk.issues
+                        .push(Issue::MissingParent { chain_length, hash });
+                }
+                continue;
+            }
+
+            match blocks.get(&hash) {
+                Some(block) => {
+                    let header = &block.header;
+                    let (content_hash, content_size) = block.contents.compute_hash_size();
+                    if header.hash() != hash
+                        || u64::from(u32::from(header.chain_length())) != chain_length
+                        || (chain_length > 0 && header.block_parent_hash() != parent)
+                        || header.block_content_hash() != content_hash
+                        || header.block_content_size() != content_size
+                    {
+                        walk.issues
+                            .push(Issue::ContentsMismatch { chain_length, hash });
+                        continue;
+                    }
+                }
+                None => {
+                    walk.issues
+                        .push(Issue::MissingContents { chain_length, hash });
+                    continue;
+                }
+            }
+            level.insert(hash);
+        }
+
+        if !level.is_empty() {
+            walk.consistent.extend(level.iter().cloned());
+            walk.last_chain_length = chain_length;
+        }
+        previous_level = level;
+        chain_length += 1;
+    }
+
+    walk
+}
+
+async fn repair_storage(
+    storage: &Storage,
+    walk: &Walk,
+    head: Option<HeaderHash>,
+    truncated_from: Option<u64>,
+) -> Result<StorageRepair, StorageCheckError> {
+    let below = truncated_from.unwrap_or(std::u64::MAX);
+
+    // the tip goes back to its newest consistent ancestor that is kept,
+    // or to a block of the last consistent chain length
+    let mut tip = None;
+    let mut current = head;
+    while let Some(hash) = current {
+        match walk.parents.get(&hash) {
+            Some((parent, chain_length)) => {
+                if *chain_length < below && walk.consistent.contains(&hash) {
+                    tip = Some(hash);
+                    break;
+                }
+                current = if *chain_length > 0 {
+                    Some(*parent)
+                } else {
+                    None
+                };
+            }
+            None => break,
+        }
+    }
+    let tip = match tip {
+        Some(tip) => tip,
+        None => {
+            let last_chain_length = walk.last_chain_length.min(below.saturating_sub(1));
+            walk.parents
+                .iter()
+                .find(|(hash, (_, chain_length))| {
+                    *chain_length == last_chain_length && walk.consistent.contains(hash)
+                })
+                .map(|(hash, _)| *hash)
+                .ok_or(StorageCheckError::Repair(StorageError::BlockNotFound))?
+        }
+    };
+
+    let removed_blocks = match truncated_from {
+        Some(from) => {
+            let consistent = walk.consistent.clone();
+            let kept = storage
+                .truncate(
+                    from,
+                    move |hash| consistent.contains(hash),
+                    vec![(MAIN_BRANCH_TAG.to_owned(), tip)],
+                )
+                .await
+                .map_err(StorageCheckError::Repair)?;
+            walk.blocks.saturating_sub(kept)
+        }
+        None => {
+            storage
+                .put_tag(MAIN_BRANCH_TAG.to_owned(), tip)
+                .await
+                .map_err(StorageCheckError::Repair)?;
+            0
+        }
+    };
+
+    Ok(StorageRepair {
+        truncated_from,
+        removed_blocks,
+        tip: tip.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{
+        Block, BlockDate, BlockVersion, ConfigParams, ContentsBuilder, Fragment, Header,
+    };
+    use crate::blockchain::{replace_truncated_database, BLOCKSTORE_FILE_NAME};
+    use rand_core::{OsRng, RngCore};
+    use std::convert::Infallible;
+
+    /// an empty block following `parent`, or a block0
+    fn block(parent: Option<&Block>, slot_id: u32) -> Block {
+        let date = BlockDate { epoch: 0, slot_id };
+        chain_impl_mockchain::block::builder(
+            BlockVersion::Genesis,
+            ContentsBuilder::new().into(),
+            |hdr| {
+                let hdr = match parent {
+                    None => hdr.set_genesis(),
+                    Some(parent) => hdr.set_parent(
+                        &parent.header.hash(),
+                        parent.header.chain_length().increase(),
+                    ),
+                };
+                let header: Result<Header, Infallible> = Ok(hdr
+                    .set_date(date)
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize());
+                header
+            },
+        )
+        .unwrap()
+    }
+
+    /// a chain of empty blocks, starting with a block0
+    fn chain(count: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for slot_id in 0..count {
+            let block = block(blocks.last(), slot_id);
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    /// an in-memory storage with the given blocks; the storage directory is
+    /// not created
+    async fn storage(blocks: &[Block]) -> Storage {
+        let store = chain_storage::BlockStoreBuilder::memory()
+            .busy_timeout(1000)
+            .build();
+        let directory =
+            std::env::temp_dir().join(format!("storage-check-{:016x}", OsRng.next_u64()));
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let storage = Storage::new(store, Some(directory), logger);
+        for block in blocks {
+            storage.put_block(block.clone()).await.unwrap();
+        }
+        storage
+    }
+
+    fn walk(blocks: &[(&Block, bool)]) -> Walk {
+        let mut walk = Walk {
+            blocks: 0,
+            issues: Vec::new(),
+            parents: HashMap::new(),
+            consistent: HashSet::new(),
+            last_chain_length: 0,
+        };
+        for (block, consistent) in blocks {
+            let hash = block.header.hash();
+            let chain_length = u64::from(u32::from(block.header.chain_length()));
+            walk.blocks += 1;
+            walk.parents
+                .insert(hash, (block.header.block_parent_hash(), chain_length));
+            if *consistent {
+                walk.consistent.insert(hash);
+                walk.last_chain_length = walk.last_chain_length.max(chain_length);
+            } else {
+                walk.issues
+                    .push(Issue::ContentsMismatch { chain_length, hash });
+            }
+        }
+        walk
+    }
+
+    #[test]
+    fn side_fork_inconsistency_does_not_truncate_the_main_branch() {
+        let main = chain(4);
+        let fork = block(Some(&main[0]), 10);
+        let walk = walk(&[
+            (&main[0], true),
+            (&fork, false),
+            (&main[1], true),
+            (&main[2], true),
+            (&main[3], true),
+        ]);
+
+        assert_eq!(
+            main_branch_truncation(&walk, Some(main[3].header.hash())),
+            None
+        );
+        // without a tip to follow, the lowest issue is used
+        assert_eq!(main_branch_truncation(&walk, None), Some(1));
+    }
+
+    #[test]
+    fn main_branch_inconsistency_truncates() {
+        let main = chain(4);
+        let fork = block(Some(&main[1]), 10);
+        let walk = walk(&[
+            (&main[0], true),
+            (&main[1], true),
+            (&fork, true),
+            (&main[2], false),
+            (&main[3], false),
+        ]);
+
+        assert_eq!(
+            main_branch_truncation(&walk, Some(main[3].header.hash())),
+            Some(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn consistent_storage_has_no_issue() {
+        let blocks = chain(4);
+        let storage = storage(&blocks).await;
+        let tip = blocks[3].header.hash();
+        storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), tip)
+            .await
+            .unwrap();
+
+        let report = check_storage(&storage, blocks[0].header.hash(), true)
+            .await
+            .unwrap();
+        assert_eq!(report.blocks, 4);
+        assert!(report.issues.is_empty());
+        assert!(report.repair.is_none());
+        assert_eq!(report.tip, Some(tip.into()));
+    }
+
+    #[tokio::test]
+    async fn corrupted_contents_are_detected() {
+        let blocks = chain(3);
+        let mut contents = ContentsBuilder::new();
+        contents.push(Fragment::Initial(ConfigParams::new()));
+        let corrupted = Block {
+            header: blocks[2].header.clone(),
+            contents: contents.into(),
+        };
+        let storage = storage(&[blocks[0].clone(), blocks[1].clone(), corrupted]).await;
+
+        let walk = walk_blocks(&storage, blocks[0].header.hash()).await;
+        assert_eq!(walk.blocks, 3);
+        assert_eq!(walk.issues.len(), 1);
+        match &walk.issues[0] {
+            Issue::ContentsMismatch { chain_length, hash } => {
+                assert_eq!(*chain_length, 2);
+                assert_eq!(*hash, blocks[2].header.hash());
+            }
+            issue => panic!("unexpected issue {}", issue),
+        }
+        assert!(!walk.consistent.contains(&blocks[2].header.hash()));
+        assert_eq!(walk.last_chain_length, 1);
+    }
+
+    #[tokio::test]
+    async fn missing_tip_is_repaired() {
+        let blocks = chain(3);
+        let storage = storage(&blocks).await;
+
+        let report = check_storage(&storage, blocks[0].header.hash(), true)
+            .await
+            .unwrap();
+        assert_eq!(report.issues.len(), 1);
+        let repair = report.repair.unwrap();
+        assert_eq!(repair.truncated_from, None);
+        assert_eq!(repair.removed_blocks, 0);
+        let tip = blocks[2].header.hash();
+        assert_eq!(repair.tip, tip.into());
+        assert_eq!(
+            storage.get_tag(MAIN_BRANCH_TAG.to_owned()).await.unwrap(),
+            Some(tip)
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_block0_is_not_repaired() {
+        let blocks = chain(2);
+        let storage = storage(&blocks).await;
+        storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), blocks[1].header.hash())
+            .await
+            .unwrap();
+
+        let other_block0 = block(None, 1).header.hash();
+        let report = check_storage(&storage, other_block0, false).await.unwrap();
+        assert!(report.repair.is_none());
+        assert!(!report.issues.is_empty());
+        match check_storage(&storage, other_block0, true).await {
+            Err(StorageCheckError::Block0Inconsistent) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn main_branch_inconsistency_is_truncated() {
+        let main = chain(4);
+        let fork = block(Some(&main[1]), 10);
+        let mut blocks = main.clone();
+        blocks.push(fork.clone());
+        let storage = storage(&blocks).await;
+        let directory = storage.directory().unwrap().to_owned();
+        std::fs::create_dir(&directory).unwrap();
+
+        let walk = walk(&[
+            (&main[0], true),
+            (&main[1], true),
+            (&fork, true),
+            (&main[2], false),
+            (&main[3], false),
+        ]);
+        let repair = repair_storage(&storage, &walk, Some(main[3].header.hash()), Some(2)).await;
+        let replaced = replace_truncated_database(&directory);
+        let store = chain_storage::BlockStoreBuilder::file(directory.join(BLOCKSTORE_FILE_NAME))
+            .busy_timeout(1000)
+            .build();
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let truncated = Storage::new(store, Some(directory.clone()), logger);
+        let tag = truncated.get_tag(MAIN_BRANCH_TAG.to_owned()).await;
+        let blocks = truncated.get_blocks_by_chain_length(2).await;
+        drop(truncated);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let repair = repair.unwrap();
+        assert_eq!(repair.truncated_from, Some(2));
+        assert_eq!(repair.removed_blocks, 3);
+        let tip = main[1].header.hash();
+        assert_eq!(repair.tip, tip.into());
+        assert!(replaced.unwrap());
+        assert_eq!(tag.unwrap(), Some(tip));
+        assert!(blocks.unwrap().is_empty());
+    }
+}
@@ -21,6 +21,12 @@ impl Logs {
         self.entries.contains(&fragment_id)
     }
 
+    /// Returns the log of the fragment, without marking it as recently used
+    pub fn get(&self, fragment_id: FragmentId) -> Option<&FragmentLog> {
+        let fragment_id: Hash = fragment_id.into();
+        self.entries.peek(&fragment_id)
+    }
+
     pub fn exist_all(&self, fragment_ids: impl IntoIterator<Item = FragmentId>) -> Vec<bool> {
         fragment_ids
             .into_iter()
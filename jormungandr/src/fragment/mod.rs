@@ -1,12 +1,17 @@
 mod entry;
 mod logs;
+mod persistence;
 mod pool;
 mod process;
 pub mod selection;
+mod spending;
+#[cfg(test)]
+mod test_utils;
 
 pub use self::entry::PoolEntry;
 pub use self::logs::Logs;
-pub use self::pool::Pool;
+pub use self::persistence::Persistence;
+pub use self::pool::{Pool, Submitted};
 pub use self::process::Process;
 
 pub use crate::blockcfg::{Fragment, FragmentId};
@@ -0,0 +1,225 @@
+//! Saving and restoring the content of the mempool and the fragment logs
+//! so they survive a restart of the node.
+//!
+//! The snapshot is written atomically: it is first written to a temporary
+//! file which is then renamed over the previous snapshot.
+
+use crate::fragment::Fragment;
+use chain_core::property::{Deserialize as _, Serialize as _};
+use jormungandr_lib::{interfaces::FragmentLog, time::SystemTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// name of the snapshot file in the node storage directory
+pub const MEMPOOL_FILE_NAME: &str = "mempool.json";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot access the mempool snapshot {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid mempool snapshot format")]
+    Format(#[from] serde_json::Error),
+    #[error("invalid fragment encoding in the mempool snapshot")]
+    FragmentHex(#[from] hex::FromHexError),
+    #[error("cannot encode or decode a fragment of the mempool snapshot")]
+    FragmentEncoding(#[source] io::Error),
+}
+
+/// The saved content of the mempool. Both lists are ordered from the
+/// oldest to the most recent entry.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub fragments: Vec<PendingFragment>,
+    pub logs: Vec<FragmentLog>,
+}
+
+/// a fragment of the mempool along with the time it entered the mempool,
+/// so its time to live carries on across the restart
+#[derive(Debug)]
+pub struct PendingFragment {
+    pub fragment: Fragment,
+    pub inserted_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    fragments: Vec<PendingFragmentFile>,
+    logs: Vec<FragmentLog>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PendingFragmentFile {
+    fragment: String,
+    inserted_at: SystemTime,
+}
+
+#[derive(Clone)]
+pub struct Persistence {
+    path: PathBuf,
+}
+
+impl Persistence {
+    /// persist the mempool in the given storage directory
+    pub fn new<P: AsRef<Path>>(storage_dir: P) -> Self {
+        Persistence {
+            path: storage_dir.as_ref().join(MEMPOOL_FILE_NAME),
+        }
+    }
+
+    /// Load the snapshot, if there is one.
+    pub fn load(&self) -> Result<Option<Snapshot>, Error> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(self.io_error(source)),
+        };
+        let file: SnapshotFile = serde_json::from_slice(&content)?;
+
+        let fragments = file
+            .fragments
+            .iter()
+            .map(|pending| {
+                let bytes = hex::decode(&pending.fragment)?;
+                let fragment =
+                    Fragment::deserialize(bytes.as_slice()).map_err(Error::FragmentEncoding)?;
+                Ok(PendingFragment {
+                    fragment,
+                    inserted_at: pending.inserted_at,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Some(Snapshot {
+            fragments,
+            logs: file.logs,
+        }))
+    }
+
+    /// Save the snapshot, replacing the previous one.
+    pub fn save(&self, snapshot: Snapshot) -> Result<(), Error> {
+        let fragments = snapshot
+            .fragments
+            .iter()
+            .map(|pending| {
+                let fragment = pending
+                    .fragment
+                    .serialize_as_vec()
+                    .map(hex::encode)
+                    .map_err(Error::FragmentEncoding)?;
+                Ok(PendingFragmentFile {
+                    fragment,
+                    inserted_at: pending.inserted_at,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        let file = SnapshotFile {
+            fragments,
+            logs: snapshot.logs,
+        };
+        let content = serde_json::to_vec(&file)?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, content).map_err(|source| Error::Io {
+            path: tmp_path.clone(),
+            source,
+        })?;
+        fs::rename(&tmp_path, &self.path).map_err(|source| self.io_error(source))
+    }
+
+    fn io_error(&self, source: io::Error) -> Error {
+        Error::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragment::test_utils::{fee, TestLedger, Wallet};
+    use crate::fragment::FragmentId;
+    use chain_core::property::Fragment as _;
+    use jormungandr_lib::interfaces::{FragmentOrigin, FragmentStatus};
+    use rand_core::{OsRng, RngCore};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("mempool-{:016x}", OsRng.next_u64()));
+            fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn nothing_to_load_without_a_snapshot() {
+        let dir = TempDir::new();
+        assert!(Persistence::new(&dir.0).load().unwrap().is_none());
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let fragment = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(1000 - fee(1, 1))],
+        );
+        let inserted_at = SystemTime::from_secs_since_epoch(1_600_000_000);
+        let pending_log = FragmentLog::new(fragment.id().into(), FragmentOrigin::Rest);
+        let mut rejected_log = FragmentLog::new(
+            FragmentId::hash_bytes(b"rejected").into(),
+            FragmentOrigin::Network,
+        );
+        rejected_log.modify(FragmentStatus::Rejected {
+            reason: "invalid".to_owned(),
+        });
+        let logs = vec![pending_log, rejected_log];
+
+        let dir = TempDir::new();
+        let persistence = Persistence::new(&dir.0);
+        persistence
+            .save(Snapshot {
+                fragments: vec![PendingFragment {
+                    fragment: fragment.clone(),
+                    inserted_at,
+                }],
+                logs: logs.clone(),
+            })
+            .unwrap();
+        let snapshot = persistence.load().unwrap().unwrap();
+
+        assert_eq!(snapshot.fragments.len(), 1);
+        assert_eq!(snapshot.fragments[0].fragment.id(), fragment.id());
+        assert_eq!(snapshot.fragments[0].inserted_at, inserted_at);
+        assert_eq!(snapshot.logs, logs);
+        assert!(!dir.0.join("mempool.json.tmp").exists());
+    }
+
+    #[test]
+    fn invalid_snapshot_is_an_error() {
+        let dir = TempDir::new();
+        fs::write(dir.0.join(MEMPOOL_FILE_NAME), b"{\"fragments\": [").unwrap();
+        assert!(matches!(
+            Persistence::new(&dir.0).load(),
+            Err(Error::Format(_))
+        ));
+    }
+}
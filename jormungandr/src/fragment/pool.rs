@@ -1,17 +1,89 @@
 use crate::{
-    blockcfg::{BlockDate, Ledger, LedgerParameters},
+    blockcfg::{BlockDate, Ledger, LedgerParameters, Value},
+    blockchain::Ref,
     fragment::{
-        selection::{FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams, OldestFirst},
+        persistence::{PendingFragment, Snapshot},
+        selection::{
+            fragment_fee, FeePerByte, FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams,
+            OldestFirst,
+        },
+        spending::Spending,
         Fragment, FragmentId, Logs,
     },
     intercom::{NetworkMsg, PropagateMsg},
     utils::async_msg::MessageBox,
 };
 use chain_core::property::Fragment as _;
-use chain_impl_mockchain::{fragment::Contents, transaction::Transaction};
+use chain_impl_mockchain::{fragment::Contents, ledger, transaction::Transaction};
 use futures::sink::SinkExt;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use jormungandr_lib::{
+    interfaces::{
+        FragmentAdmission, FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission,
+        MempoolEntry, MempoolInfo, MempoolSummary,
+    },
+    time,
+};
 use slog::Logger;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AdmissionError {
+    #[error("the fragment is not accepted in the mempool")]
+    NotAccepted,
+    #[error("the transaction is not balanced")]
+    Unbalanced,
+    #[error("conflicts with the pending fragment {pending}, the fee {fee} needs to exceed {threshold} to replace it")]
+    FeeTooLowToReplace {
+        pending: FragmentId,
+        fee: Value,
+        threshold: Value,
+    },
+    #[error("the account witness does not match any upcoming spending counter")]
+    InvalidAccountWitness,
+    #[error("the fragment cannot be applied to the ledger of the tip")]
+    Ledger(#[from] ledger::Error),
+}
+
+/// the admission result of a submitted fragment
+pub struct Submitted {
+    pub fragment_id: FragmentId,
+    pub admission: FragmentAdmission,
+    /// the transactions the fragment spends the outputs of which this node
+    /// has never seen, if the fragment was rejected
+    pub unknown_inputs: Vec<FragmentId>,
+    /// the fragment was rejected for witnesses that do not match, which
+    /// does not depend on the ledger it is checked against
+    pub invalid_witness: bool,
+}
+
+impl Submitted {
+    pub fn submission(&self) -> FragmentSubmission {
+        FragmentSubmission {
+            fragment_id: self.fragment_id.into(),
+            admission: self.admission.clone(),
+        }
+    }
+}
+
+impl AdmissionError {
+    /// whether the fragment was rejected for a witness not matching its
+    /// signing key
+    pub fn is_invalid_witness(&self) -> bool {
+        match self {
+            AdmissionError::InvalidAccountWitness => true,
+            AdmissionError::Ledger(error) => matches!(
+                error,
+                ledger::Error::UtxoInvalidSignature { .. }
+                    | ledger::Error::AccountInvalidSignature { .. }
+                    | ledger::Error::MultisigInvalidSignature { .. }
+                    | ledger::Error::ExpectingUtxoWitness { .. }
+                    | ledger::Error::ExpectingAccountWitness { .. }
+            ),
+            _ => false,
+        }
+    }
+}
 
 pub struct Pool {
     logs: Logs,
@@ -20,10 +92,16 @@ pub struct Pool {
 }
 
 impl Pool {
-    pub fn new(max_entries: usize, logs: Logs, network_msg_box: MessageBox<NetworkMsg>) -> Self {
+    pub fn new(
+        max_entries: usize,
+        fee_replacement_margin: u64,
+        ttl: Duration,
+        logs: Logs,
+        network_msg_box: MessageBox<NetworkMsg>,
+    ) -> Self {
         Pool {
             logs,
-            pool: internal::Pool::new(max_entries),
+            pool: internal::Pool::new(max_entries, fee_replacement_margin, ttl),
             network_msg_box,
         }
     }
@@ -32,31 +110,99 @@ impl Pool {
         &mut self.logs
     }
 
-    /// Returns number of registered fragments
+    /// Check the fragments against the given tip before registering them
+    /// and propagating them to the network. Fragments failing the admission
+    /// checks are logged as rejected and are not propagated.
+    ///
+    /// Returns the admission result of each fragment
     pub async fn insert_and_propagate_all(
         &mut self,
         origin: FragmentOrigin,
-        mut fragments: Vec<Fragment>,
+        fragments: Vec<Fragment>,
+        tip: &Ref,
         logger: Logger,
-    ) -> Result<usize, ()> {
-        fragments.retain(is_fragment_valid);
-        if fragments.is_empty() {
-            return Ok(0);
-        }
+    ) -> Result<Vec<Submitted>, ()> {
         let mut network_msg_box = self.network_msg_box.clone();
-        let fragment_ids = fragments.iter().map(Fragment::id).collect::<Vec<_>>();
-        let fragments_exist_in_logs = self.logs.exist_all(fragment_ids);
-        let new_fragments = fragments
-            .into_iter()
-            .zip(fragments_exist_in_logs)
-            .filter(|(_, exists_in_logs)| !exists_in_logs)
-            .map(|(fragment, _)| fragment);
-        let new_fragments = self.pool.insert_all(new_fragments);
-        let count = new_fragments.len();
-        let fragment_logs = new_fragments
-            .iter()
-            .map(move |fragment| FragmentLog::new(fragment.id().into(), origin))
-            .collect::<Vec<_>>();
+        let ledger = tip.ledger();
+        let ledger_params = tip.epoch_ledger_parameters();
+        let block_date = tip.block_date();
+
+        let mut new_fragments = Vec::new();
+        let mut submissions = Vec::with_capacity(fragments.len());
+        for fragment in fragments {
+            let fragment_id = fragment.id();
+            let submission = |admission| Submitted {
+                fragment_id,
+                admission,
+                unknown_inputs: Vec::new(),
+                invalid_witness: false,
+            };
+            if self.logs.exists(fragment_id) {
+                submissions.push(submission(FragmentAdmission::AlreadyKnown));
+                continue;
+            }
+            let mut log = FragmentLog::new(fragment_id.into(), origin);
+            match self
+                .pool
+                .admit(&fragment, &ledger, ledger_params, block_date)
+            {
+                Ok(admission) => {
+                    for replaced in admission.replaces.iter() {
+                        debug!(logger, "fragment replaced in the mempool";
+                            "fragment_id" => %replaced,
+                            "replaced_by" => %fragment_id,
+                        );
+                        let dependents = self.pool.remove_with_dependents(replaced);
+                        self.logs.modify(
+                            *replaced,
+                            FragmentStatus::Replaced {
+                                by: fragment_id.into(),
+                            },
+                        );
+                        self.reject_dependents(replaced, dependents, "replaced", &logger);
+                    }
+                    self.evict_if_full(&logger);
+                    match self.pool.insert(fragment, admission.spending) {
+                        Some(fragment) => {
+                            new_fragments.push(fragment);
+                            submissions.push(submission(FragmentAdmission::Accepted));
+                        }
+                        None => {
+                            submissions.push(submission(FragmentAdmission::AlreadyKnown));
+                            continue;
+                        }
+                    }
+                }
+                Err(error) => {
+                    let reason = rejection_reason(&error);
+                    debug!(logger, "fragment rejected by the mempool";
+                        "fragment_id" => %fragment_id,
+                        "reason" => &reason,
+                    );
+                    let unknown_inputs = match error {
+                        AdmissionError::Ledger(_) => self.unknown_inputs(&fragment, &ledger),
+                        _ => Vec::new(),
+                    };
+                    // a fragment from the network spending outputs this node
+                    // has never seen is not logged, so it can be admitted
+                    // once the missing fragments have been fetched
+                    let is_orphan = origin == FragmentOrigin::Network && !unknown_inputs.is_empty();
+                    submissions.push(Submitted {
+                        unknown_inputs,
+                        invalid_witness: error.is_invalid_witness(),
+                        ..submission(FragmentAdmission::Rejected {
+                            reason: reason.clone(),
+                        })
+                    });
+                    if is_orphan {
+                        continue;
+                    }
+                    log.modify(FragmentStatus::Rejected { reason });
+                }
+            }
+            self.logs.insert(log);
+        }
+
         for fragment in new_fragments.into_iter() {
             let fragment_msg = NetworkMsg::Propagate(PropagateMsg::Fragment(fragment));
             network_msg_box
@@ -64,8 +210,227 @@ impl Pool {
                 .await
                 .map_err(|e| error!(logger, "cannot propagate fragment to network: {}", e))?;
         }
-        self.logs.insert_all(fragment_logs);
-        Ok(count)
+        Ok(submissions)
+    }
+
+    /// The transactions the given fragment spends the outputs of which this
+    /// node has never seen: their outputs are not in the ledger and they
+    /// are not in the fragment logs.
+    fn unknown_inputs(&self, fragment: &Fragment, ledger: &Ledger) -> Vec<FragmentId> {
+        let mut unknown_inputs = Vec::new();
+        for pointer in Spending::new(fragment).utxos() {
+            let transaction_id = pointer.transaction_id;
+            let is_known = ledger
+                .utxo_out(transaction_id, pointer.output_index)
+                .is_some()
+                || self.logs.exists(transaction_id);
+            if !is_known && !unknown_inputs.contains(&transaction_id) {
+                unknown_inputs.push(transaction_id);
+            }
+        }
+        unknown_inputs
+    }
+
+    /// Take a snapshot of the pending fragments and of the logs, ordered
+    /// from the oldest to the most recent entry.
+    pub fn snapshot(&self) -> Snapshot {
+        let now = Instant::now();
+        let system_now = std::time::SystemTime::now();
+        let mut fragments: Vec<PendingFragment> = self
+            .pool
+            .fragments()
+            .map(|fragment| {
+                let wait_time = self.pool.wait_time(&fragment.id(), now);
+                PendingFragment {
+                    fragment: fragment.clone(),
+                    inserted_at: (system_now - wait_time).into(),
+                }
+            })
+            .collect();
+        fragments.reverse();
+        let mut logs: Vec<FragmentLog> = self.logs.logs().cloned().collect();
+        logs.reverse();
+        Snapshot { fragments, logs }
+    }
+
+    /// Restore a snapshot taken with `snapshot`. The saved fragments are
+    /// checked again against the given tip: the ones that are no longer
+    /// valid are logged as rejected. The restored fragments are not
+    /// propagated to the network.
+    ///
+    /// Returns number of restored fragments
+    pub fn restore(&mut self, snapshot: Snapshot, tip: &Ref, logger: &Logger) -> usize {
+        let ledger = tip.ledger();
+        let ledger_params = tip.epoch_ledger_parameters();
+        let block_date = tip.block_date();
+
+        self.logs.insert_all(snapshot.logs);
+
+        let now = Instant::now();
+        let system_now = std::time::SystemTime::now();
+        let mut count = 0;
+        for PendingFragment {
+            fragment,
+            inserted_at,
+        } in snapshot.fragments
+        {
+            let fragment_id = fragment.id();
+            let status = match self
+                .pool
+                .admit(&fragment, &ledger, ledger_params, block_date)
+            {
+                // conflicting fragments cannot have been saved together,
+                // this may only happen if the snapshot was tampered with
+                Ok(ref admission) if !admission.replaces.is_empty() => FragmentStatus::Rejected {
+                    reason: "conflicts with another restored fragment".to_owned(),
+                },
+                Ok(admission) => {
+                    // the time spent in the mempool before the restart counts
+                    // towards the time to live of the fragment
+                    let wait_time = system_now
+                        .duration_since(inserted_at.into())
+                        .unwrap_or_default();
+                    let inserted_at = now.checked_sub(wait_time).unwrap_or(now);
+                    self.evict_if_full(logger);
+                    if self
+                        .pool
+                        .insert_at(fragment, admission.spending, inserted_at)
+                        .is_some()
+                    {
+                        count += 1;
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    let reason = rejection_reason(&error);
+                    debug!(logger, "restored fragment rejected by the mempool";
+                        "fragment_id" => %fragment_id,
+                        "reason" => &reason,
+                    );
+                    FragmentStatus::Rejected { reason }
+                }
+            };
+            self.logs.modify(fragment_id, status);
+        }
+        count
+    }
+
+    /// Make room for a new fragment: the oldest fragment of a full pool is
+    /// evicted along with its dependents, they are logged as rejected.
+    fn evict_if_full(&mut self, logger: &Logger) {
+        if let Some((evicted, dependents)) = self.pool.remove_oldest_if_full() {
+            debug!(logger, "fragment evicted from the full mempool"; "fragment_id" => %evicted);
+            self.logs.modify(
+                evicted,
+                FragmentStatus::Rejected {
+                    reason: format!(
+                        "evicted from the mempool full with {} fragments",
+                        self.pool.capacity()
+                    ),
+                },
+            );
+            self.reject_dependents(&evicted, dependents, "evicted", logger);
+        }
+    }
+
+    /// Log as rejected the dependents removed along with the given fragment
+    fn reject_dependents(
+        &mut self,
+        fragment_id: &FragmentId,
+        dependents: Vec<FragmentId>,
+        removal: &str,
+        logger: &Logger,
+    ) {
+        let reason = format!(
+            "spends the outputs of the {} fragment {}",
+            removal, fragment_id
+        );
+        for dependent in dependents {
+            debug!(logger, "dependent of a {} fragment removed from the mempool", removal;
+                "fragment_id" => %dependent,
+                "parent" => %fragment_id,
+            );
+            self.logs.modify(
+                dependent,
+                FragmentStatus::Rejected {
+                    reason: reason.clone(),
+                },
+            );
+        }
+    }
+
+    /// Returns the pending fragments among the given ones
+    pub fn get_all(&self, fragment_ids: impl IntoIterator<Item = FragmentId>) -> Vec<Fragment> {
+        fragment_ids
+            .into_iter()
+            .filter_map(|fragment_id| self.pool.get(&fragment_id).cloned())
+            .collect()
+    }
+
+    /// Summarize the content of the pool and list the pending fragments
+    /// from the given offset, oldest first.
+    pub fn info(&self, offset: usize, limit: usize) -> MempoolInfo {
+        let now = Instant::now();
+        let mut total_size = 0u64;
+        let mut total_fees = Value::zero();
+        let mut entries = Vec::new();
+        // the pool iterates from the most recent to the oldest fragment
+        let fragments: Vec<&Fragment> = self.pool.fragments().collect();
+        for (index, fragment) in fragments.iter().rev().enumerate() {
+            let fragment_raw = fragment.to_raw();
+            let size = fragment_raw.size_bytes_plus_size() as u32;
+            let fee = fragment_fee(fragment);
+            total_size += size as u64;
+            total_fees = Value(total_fees.0.saturating_add(fee.0));
+            if index >= offset && entries.len() < limit {
+                let fragment_id = fragment_raw.id();
+                entries.push(MempoolEntry {
+                    fragment_id: fragment_id.into(),
+                    size,
+                    fee: fee.into(),
+                    wait_time: self.pool.wait_time(&fragment_id, now).into(),
+                });
+            }
+        }
+        let max_wait_time = fragments
+            .last()
+            .map(|oldest| self.pool.wait_time(&oldest.id(), now).into());
+
+        MempoolInfo {
+            summary: MempoolSummary {
+                fragments: fragments.len(),
+                capacity: self.pool.capacity(),
+                total_size,
+                total_fees: total_fees.into(),
+                max_wait_time,
+            },
+            entries,
+        }
+    }
+
+    /// Remove the fragments that have been in the pool for longer than the
+    /// time to live of the pool, along with their dependents, they are
+    /// logged as rejected.
+    ///
+    /// Returns number of expired fragments
+    pub fn remove_expired(&mut self, logger: &Logger) -> usize {
+        let expired = self.pool.remove_expired(Instant::now());
+        let reason = format!(
+            "expired after {} in the mempool",
+            time::Duration::from(self.pool.ttl())
+        );
+        let count = expired.len();
+        for (fragment_id, dependents) in expired {
+            debug!(logger, "fragment expired in the mempool"; "fragment_id" => %fragment_id);
+            self.logs.modify(
+                fragment_id,
+                FragmentStatus::Rejected {
+                    reason: reason.clone(),
+                },
+            );
+            self.reject_dependents(&fragment_id, dependents, "expired", logger);
+        }
+        count
     }
 
     pub fn remove_added_to_block(&mut self, fragment_ids: Vec<FragmentId>, status: FragmentStatus) {
@@ -87,79 +452,857 @@ impl Pool {
                 selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
                 selection_alg.finalize()
             }
+            FragmentSelectionAlgorithmParams::FeePerByte => {
+                let mut selection_alg = FeePerByte::new();
+                selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
+                selection_alg.finalize()
+            }
         }
     }
 }
 
-fn is_fragment_valid(fragment: &Fragment) -> bool {
+fn check_fragment(fragment: &Fragment) -> Result<(), AdmissionError> {
     match fragment {
         // never valid in the pool, only acceptable in genesis
-        Fragment::Initial(_) => false,
-        Fragment::OldUtxoDeclaration(_) => false,
+        Fragment::Initial(_) => Err(AdmissionError::NotAccepted),
+        Fragment::OldUtxoDeclaration(_) => Err(AdmissionError::NotAccepted),
         // general transactions stuff
-        Fragment::Transaction(ref tx) => is_transaction_valid(tx),
-        Fragment::StakeDelegation(ref tx) => is_transaction_valid(tx),
-        Fragment::OwnerStakeDelegation(ref tx) => is_transaction_valid(tx),
-        Fragment::PoolRegistration(ref tx) => is_transaction_valid(tx),
-        Fragment::PoolRetirement(ref tx) => is_transaction_valid(tx),
+        Fragment::Transaction(ref tx) => check_transaction(tx),
+        Fragment::StakeDelegation(ref tx) => check_transaction(tx),
+        Fragment::OwnerStakeDelegation(ref tx) => check_transaction(tx),
+        Fragment::PoolRegistration(ref tx) => check_transaction(tx),
+        Fragment::PoolRetirement(ref tx) => check_transaction(tx),
         // disabled for now
-        Fragment::PoolUpdate(_) => false,
-        Fragment::UpdateProposal(_) => false,
-        Fragment::UpdateVote(_) => false,
-        Fragment::VotePlan(_) => false,
-        Fragment::VoteCast(_) => false,
+        Fragment::PoolUpdate(_) => Err(AdmissionError::NotAccepted),
+        Fragment::UpdateProposal(_) => Err(AdmissionError::NotAccepted),
+        Fragment::UpdateVote(_) => Err(AdmissionError::NotAccepted),
+        Fragment::VotePlan(_) => Err(AdmissionError::NotAccepted),
+        Fragment::VoteCast(_) => Err(AdmissionError::NotAccepted),
     }
 }
 
-fn is_transaction_valid<E>(tx: &Transaction<E>) -> bool {
-    tx.verify_possibly_balanced().is_ok()
+fn check_transaction<E>(tx: &Transaction<E>) -> Result<(), AdmissionError> {
+    tx.verify_possibly_balanced()
+        .map_err(|_| AdmissionError::Unbalanced)
+}
+
+/// format an error along with its source, to be used as the reason of a
+/// rejection in the fragment logs
+pub(super) fn rejection_reason(error: &dyn std::error::Error) -> String {
+    if let Some(source) = error.source() {
+        format!("{}: {}", error, source)
+    } else {
+        error.to_string()
+    }
 }
 
 pub(super) mod internal {
     use super::*;
+    use crate::fragment::spending::{AccountSpending, Spending};
+    use chain_impl_mockchain::{account, transaction::UtxoPointer};
     use lru::LruCache;
+    use std::collections::{BTreeMap, HashMap, HashSet};
 
     pub struct Pool {
         entries: LruCache<FragmentId, Fragment>,
+        /// what the pooled fragments spend
+        spendings: HashMap<FragmentId, Spending>,
+        /// the UTxO inputs of the pooled fragments, indexed by the id of the
+        /// transaction and the index of the output they spend
+        spent_outputs: HashMap<FragmentId, HashMap<u8, FragmentId>>,
+        /// the account inputs of the pooled fragments, indexed by account
+        /// and spending counter once the counter has been resolved
+        account_counters: HashMap<account::Identifier, BTreeMap<u32, FragmentId>>,
+        /// the pooled fragments spending from each account, whether their
+        /// spending counter is resolved or not
+        account_debits: HashMap<account::Identifier, HashSet<FragmentId>>,
+        /// the pooled fragments paying to each account
+        account_credits: HashMap<account::Identifier, HashSet<FragmentId>>,
+        /// when the pooled fragments were inserted
+        inserted_at: HashMap<FragmentId, Instant>,
+        /// minimum fee increase, in percent, to replace a pending fragment
+        fee_replacement_margin: u64,
+        /// how long a fragment is kept in the pool
+        ttl: Duration,
+    }
+
+    /// the outcome of a successful admission in the pool
+    pub struct Admission {
+        /// what the admitted fragment spends
+        pub spending: Spending,
+        /// the pending fragments the admitted fragment replaces
+        pub replaces: Vec<FragmentId>,
     }
 
     impl Pool {
-        pub fn new(max_entries: usize) -> Self {
+        pub fn new(max_entries: usize, fee_replacement_margin: u64, ttl: Duration) -> Self {
             Pool {
                 entries: LruCache::new(max_entries),
+                spendings: HashMap::new(),
+                spent_outputs: HashMap::new(),
+                account_counters: HashMap::new(),
+                account_debits: HashMap::new(),
+                account_credits: HashMap::new(),
+                inserted_at: HashMap::new(),
+                fee_replacement_margin,
+                ttl,
+            }
+        }
+
+        pub fn ttl(&self) -> Duration {
+            self.ttl
+        }
+
+        /// the maximum number of fragments in the pool
+        pub fn capacity(&self) -> usize {
+            self.entries.cap()
+        }
+
+        /// how long the given pooled fragment has been in the pool for, in
+        /// whole seconds
+        pub fn wait_time(&self, fragment_id: &FragmentId, now: Instant) -> Duration {
+            self.inserted_at
+                .get(fragment_id)
+                .map(|inserted_at| now.saturating_duration_since(*inserted_at).as_secs())
+                .map(Duration::from_secs)
+                .unwrap_or_default()
+        }
+
+        /// Check the fragment can enter the pool: it is valid on its own,
+        /// it does not conflict with the pooled fragments, or pays enough
+        /// to replace them, and unless it depends on pooled fragments, it
+        /// applies to the given ledger.
+        pub fn admit(
+            &self,
+            fragment: &Fragment,
+            ledger: &Ledger,
+            ledger_params: &LedgerParameters,
+            block_date: BlockDate,
+        ) -> Result<Admission, AdmissionError> {
+            check_fragment(fragment)?;
+
+            let mut spending = Spending::new(fragment);
+            spending.resolve_counters(ledger);
+
+            let conflicts = spending
+                .utxos()
+                .filter_map(|pointer| self.utxo_spender(pointer))
+                .chain(
+                    spending
+                        .accounts()
+                        .filter_map(|account| self.account_spender(account)),
+                );
+            let mut replaces = Vec::new();
+            for conflict in conflicts {
+                if !replaces.contains(conflict) {
+                    replaces.push(*conflict);
+                }
+            }
+
+            if !replaces.is_empty() {
+                let fee = fragment_fee(fragment);
+                for pending in replaces.iter() {
+                    let threshold = self
+                        .entries
+                        .peek(pending)
+                        .map(fragment_fee)
+                        .map(|pending_fee| self.replacement_threshold(pending_fee))
+                        .unwrap_or_else(Value::zero);
+                    if fee <= threshold {
+                        return Err(AdmissionError::FeeTooLowToReplace {
+                            pending: *pending,
+                            fee,
+                            threshold,
+                        });
+                    }
+                }
             }
+
+            let is_pending = |id: &FragmentId| self.entries.contains(id) && !replaces.contains(id);
+            let involves_pending =
+                |index: &HashMap<account::Identifier, HashSet<FragmentId>>,
+                 account: &account::Identifier| {
+                    index
+                        .get(account)
+                        .map_or(false, |ids| ids.iter().any(|id| !replaces.contains(id)))
+                };
+            let is_ready = spending.is_ready(
+                ledger,
+                is_pending,
+                |account| involves_pending(&self.account_credits, account),
+                |account| involves_pending(&self.account_debits, account),
+            );
+            if is_ready {
+                ledger.apply_fragment(ledger_params, fragment, block_date)?;
+            } else {
+                // the fragment is waiting on pending fragments, only the
+                // witnesses of the existing accounts can be checked
+                let invalid_witness = spending.accounts().any(|account| {
+                    account.counter().is_none() && ledger.accounts().get_state(account.id()).is_ok()
+                });
+                if invalid_witness {
+                    return Err(AdmissionError::InvalidAccountWitness);
+                }
+            }
+
+            Ok(Admission { spending, replaces })
+        }
+
+        /// the fee a fragment needs to exceed to replace a pending fragment
+        /// paying the given fee
+        fn replacement_threshold(&self, fee: Value) -> Value {
+            let threshold = fee.0 as u128 * (100 + self.fee_replacement_margin as u128) / 100;
+            Value(threshold.min(u64::MAX as u128) as u64)
         }
 
         /// Returns clone of fragment if it was registered
-        pub fn insert(&mut self, fragment: Fragment) -> Option<Fragment> {
+        pub fn insert(&mut self, fragment: Fragment, spending: Spending) -> Option<Fragment> {
+            self.insert_at(fragment, spending, Instant::now())
+        }
+
+        /// Register the fragment as inserted at the given time, which
+        /// its time to live counts from.
+        ///
+        /// Returns clone of fragment if it was registered
+        pub fn insert_at(
+            &mut self,
+            fragment: Fragment,
+            spending: Spending,
+            inserted_at: Instant,
+        ) -> Option<Fragment> {
             let fragment_id = fragment.id();
             if self.entries.contains(&fragment_id) {
                 None
             } else {
+                self.remove_oldest_if_full();
+                for pointer in spending.utxos() {
+                    self.spent_outputs
+                        .entry(pointer.transaction_id)
+                        .or_default()
+                        .insert(pointer.output_index, fragment_id);
+                }
+                for account in spending.accounts() {
+                    if let Some(counter) = account.counter() {
+                        self.account_counters
+                            .entry(account.id().clone())
+                            .or_default()
+                            .insert(counter, fragment_id);
+                    }
+                    self.account_debits
+                        .entry(account.id().clone())
+                        .or_default()
+                        .insert(fragment_id);
+                }
+                for account in spending.credited_accounts() {
+                    self.account_credits
+                        .entry(account.clone())
+                        .or_default()
+                        .insert(fragment_id);
+                }
+                self.spendings.insert(fragment_id, spending);
+                self.inserted_at.insert(fragment_id, inserted_at);
                 self.entries.put(fragment_id, fragment.clone());
                 Some(fragment)
             }
         }
 
-        /// Returns clones of registered fragments
-        pub fn insert_all(
-            &mut self,
-            fragments: impl IntoIterator<Item = Fragment>,
-        ) -> Vec<Fragment> {
-            fragments
-                .into_iter()
-                .filter_map(|fragment| self.insert(fragment))
-                .collect()
-        }
-
         pub fn remove_all(&mut self, fragment_ids: impl IntoIterator<Item = FragmentId>) {
             for fragment_id in fragment_ids {
-                self.entries.pop(&fragment_id);
+                self.remove(&fragment_id);
+            }
+        }
+
+        pub fn remove(&mut self, fragment_id: &FragmentId) -> Option<Fragment> {
+            let fragment = self.entries.pop(fragment_id)?;
+            self.unindex(fragment_id);
+            Some(fragment)
+        }
+
+        /// Remove the fragment along with the pooled fragments spending its
+        /// outputs, recursively, as they cannot be applied without it.
+        /// Returns the ids of the removed dependents.
+        pub fn remove_with_dependents(&mut self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let mut removed = Vec::new();
+            let mut to_remove = vec![*fragment_id];
+            while let Some(id) = to_remove.pop() {
+                if let Some(outputs) = self.spent_outputs.get(&id) {
+                    to_remove.extend(outputs.values().cloned());
+                }
+                if self.remove(&id).is_some() && id != *fragment_id {
+                    removed.push(id);
+                }
+            }
+            removed
+        }
+
+        /// Remove the oldest fragment, along with its dependents, if the
+        /// pool is full. Returns the id of the removed fragment and the ids
+        /// of its removed dependents.
+        pub fn remove_oldest_if_full(&mut self) -> Option<(FragmentId, Vec<FragmentId>)> {
+            if self.entries.len() < self.entries.cap() {
+                return None;
+            }
+            let fragment_id = *self.entries.peek_lru()?.0;
+            let dependents = self.remove_with_dependents(&fragment_id);
+            Some((fragment_id, dependents))
+        }
+
+        /// Remove the fragments inserted more than the time to live ago,
+        /// along with their dependents. Returns the ids of the expired
+        /// fragments, oldest first, and the ids of their removed dependents.
+        pub fn remove_expired(&mut self, now: Instant) -> Vec<(FragmentId, Vec<FragmentId>)> {
+            let ttl = self.ttl;
+            let mut expired: Vec<(FragmentId, Instant)> = self
+                .inserted_at
+                .iter()
+                .filter(|(_, inserted_at)| now.saturating_duration_since(**inserted_at) >= ttl)
+                .map(|(fragment_id, inserted_at)| (*fragment_id, *inserted_at))
+                .collect();
+            expired.sort_by_key(|(_, inserted_at)| *inserted_at);
+            let mut removed = Vec::with_capacity(expired.len());
+            for (fragment_id, _) in expired {
+                // already removed as a dependent of an older expired fragment
+                if !self.entries.contains(&fragment_id) {
+                    continue;
+                }
+                let dependents = self.remove_with_dependents(&fragment_id);
+                removed.push((fragment_id, dependents));
+            }
+            removed
+        }
+
+        fn unindex(&mut self, fragment_id: &FragmentId) {
+            self.inserted_at.remove(fragment_id);
+            let spending = match self.spendings.remove(fragment_id) {
+                Some(spending) => spending,
+                None => return,
+            };
+            for pointer in spending.utxos() {
+                if let Some(outputs) = self.spent_outputs.get_mut(&pointer.transaction_id) {
+                    if outputs.get(&pointer.output_index) == Some(fragment_id) {
+                        outputs.remove(&pointer.output_index);
+                    }
+                    if outputs.is_empty() {
+                        self.spent_outputs.remove(&pointer.transaction_id);
+                    }
+                }
+            }
+            for account in spending.accounts() {
+                remove_from_index(&mut self.account_debits, account.id(), fragment_id);
+                let counter = match account.counter() {
+                    Some(counter) => counter,
+                    None => continue,
+                };
+                if let Some(counters) = self.account_counters.get_mut(account.id()) {
+                    if counters.get(&counter) == Some(fragment_id) {
+                        counters.remove(&counter);
+                    }
+                    if counters.is_empty() {
+                        self.account_counters.remove(account.id());
+                    }
+                }
+            }
+            for account in spending.credited_accounts() {
+                remove_from_index(&mut self.account_credits, account, fragment_id);
+            }
+        }
+
+        /// the pooled fragment spending the given output, if any
+        fn utxo_spender(&self, pointer: &UtxoPointer) -> Option<&FragmentId> {
+            self.spent_outputs
+                .get(&pointer.transaction_id)
+                .and_then(|outputs| outputs.get(&pointer.output_index))
+        }
+
+        /// the pooled fragment using the same spending counter of the
+        /// account, if any
+        fn account_spender(&self, account: &AccountSpending) -> Option<&FragmentId> {
+            let counter = account.counter()?;
+            self.account_counters
+                .get(account.id())
+                .and_then(|counters| counters.get(&counter))
+        }
+
+        pub fn get(&self, fragment_id: &FragmentId) -> Option<&Fragment> {
+            self.entries.peek(fragment_id)
+        }
+
+        /// Iterate over the fragments, from the most recently inserted
+        /// to the oldest one
+        pub fn fragments(&self) -> impl Iterator<Item = &Fragment> {
+            self.entries.iter().map(|(_, value)| value)
+        }
+
+        /// Resolve the spending counters of the account inputs of the given
+        /// pooled fragment against the given ledger.
+        fn resolve_spending_counters(&mut self, fragment_id: &FragmentId, ledger: &Ledger) {
+            let spending = match self.spendings.get_mut(fragment_id) {
+                Some(spending) => spending,
+                None => return,
+            };
+            for (account, counter) in spending.resolve_counters(ledger) {
+                self.account_counters
+                    .entry(account)
+                    .or_default()
+                    .insert(counter, *fragment_id);
+            }
+        }
+
+        /// Check whether the predecessors of the given pooled fragment are
+        /// already applied to the ledger: the fragments producing the outputs
+        /// it spends are no longer pending and its account inputs use the
+        /// current spending counters. The spending counters not resolved yet
+        /// are resolved against the ledger first, as it may include the
+        /// predecessors of the fragment.
+        pub fn is_ready(&mut self, fragment_id: &FragmentId, ledger: &Ledger) -> bool {
+            self.resolve_spending_counters(fragment_id, ledger);
+            let spending = match self.spendings.get(fragment_id) {
+                Some(spending) => spending,
+                None => return true,
+            };
+            let involves_other = |index: &HashMap<account::Identifier, HashSet<FragmentId>>,
+                                  account: &account::Identifier| {
+                index
+                    .get(account)
+                    .map_or(false, |ids| ids.iter().any(|id| id != fragment_id))
+            };
+            spending.is_ready(
+                ledger,
+                |id| self.entries.contains(id),
+                |account| involves_other(&self.account_credits, account),
+                |account| involves_other(&self.account_debits, account),
+            )
+        }
+
+        /// The pooled fragments that may depend on the given fragment: the
+        /// fragments spending its outputs, and the fragments spending from
+        /// the accounts it pays to or spends from.
+        pub fn successors(&self, fragment_id: &FragmentId) -> Vec<FragmentId> {
+            let mut successors = self
+                .spent_outputs
+                .get(fragment_id)
+                .map(|outputs| outputs.values().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            if let Some(spending) = self.spendings.get(fragment_id) {
+                let accounts = spending
+                    .accounts()
+                    .map(AccountSpending::id)
+                    .chain(spending.credited_accounts());
+                for account in accounts {
+                    let debits = self.account_debits.get(account).into_iter().flatten();
+                    for id in debits {
+                        if id != fragment_id && !successors.contains(id) {
+                            successors.push(*id);
+                        }
+                    }
+                }
+            }
+            successors
+        }
+    }
+
+    fn remove_from_index(
+        index: &mut HashMap<account::Identifier, HashSet<FragmentId>>,
+        account: &account::Identifier,
+        fragment_id: &FragmentId,
+    ) {
+        if let Some(ids) = index.get_mut(account) {
+            ids.remove(fragment_id);
+            if ids.is_empty() {
+                index.remove(account);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragment::test_utils::{fee, TestLedger, Wallet, TTL};
+
+    #[test]
+    fn valid_transaction_is_admitted() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let tx = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(1000 - fee(1, 1))],
+        );
+        let mut pool = test_ledger.pool(&[]);
+
+        assert!(test_ledger.admit(&mut pool, &tx).unwrap().is_empty());
+        assert!(pool.get(&tx.id()).is_some());
+    }
+
+    #[test]
+    fn unbalanced_transaction_is_rejected() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let tx = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(2000)],
+        );
+        let pool = test_ledger.pool(&[]);
+
+        let result = pool.admit(
+            &tx,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        assert!(matches!(result, Err(AdmissionError::Unbalanced)));
+    }
+
+    #[test]
+    fn transaction_not_paying_the_fee_is_rejected() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let tx = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(1000)],
+        );
+        let pool = test_ledger.pool(&[]);
+
+        let result = pool.admit(
+            &tx,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        assert!(matches!(result, Err(AdmissionError::Ledger(_))));
+    }
+
+    #[test]
+    fn transaction_with_a_bad_witness_is_rejected() {
+        let alice = Wallet::utxo();
+        let mallory = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        // mallory signs for the output of alice
+        let tx = test_ledger.transaction(
+            &[mallory.utxo_input(test_ledger.funds, 0, 1000)],
+            &[mallory.output(1000 - fee(1, 1))],
+        );
+        let pool = test_ledger.pool(&[]);
+
+        let result = pool.admit(
+            &tx,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        match result {
+            Err(error @ AdmissionError::Ledger(_)) => assert!(error.is_invalid_witness()),
+            _ => panic!("the fragment is not rejected by the ledger"),
+        }
+    }
+
+    #[test]
+    fn account_witness_for_no_upcoming_counter_is_rejected() {
+        let alice = Wallet::account();
+        let bob = Wallet::account();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let tx = test_ledger.transaction(
+            &[alice.account_input(1000, 100 + fee(1, 1))],
+            &[bob.output(100)],
+        );
+        let pool = test_ledger.pool(&[]);
+
+        let result = pool.admit(
+            &tx,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        match result {
+            Err(error @ AdmissionError::InvalidAccountWitness) => {
+                assert!(error.is_invalid_witness())
+            }
+            _ => panic!("the account witness is not rejected"),
+        }
+    }
+
+    #[test]
+    fn spending_an_unknown_output_is_rejected() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let unknown = FragmentId::hash_bytes(b"unknown");
+        let tx = test_ledger.transaction(
+            &[alice.utxo_input(unknown, 0, 1000)],
+            &[bob.output(1000 - fee(1, 1))],
+        );
+        let pool = test_ledger.pool(&[]);
+
+        let result = pool.admit(
+            &tx,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        match result {
+            Err(error @ AdmissionError::Ledger(_)) => assert!(!error.is_invalid_witness()),
+            _ => panic!("the fragment is not rejected by the ledger"),
+        }
+    }
+
+    #[test]
+    fn conflicting_transaction_paying_the_same_fee_is_rejected() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let carol = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let input = || alice.utxo_input(test_ledger.funds, 0, 1000);
+        let pending = test_ledger.transaction(&[input()], &[bob.output(1000 - fee(1, 1))]);
+        let double_spend = test_ledger.transaction(&[input()], &[carol.output(1000 - fee(1, 1))]);
+        let pool = test_ledger.pool(&[&pending]);
+
+        let result = pool.admit(
+            &double_spend,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+        );
+        match result {
+            Err(AdmissionError::FeeTooLowToReplace { pending: id, .. }) => {
+                assert_eq!(id, pending.id())
+            }
+            _ => panic!("the double spend is not rejected"),
+        }
+    }
+
+    #[test]
+    fn higher_fee_replaces_pending_utxo_spend() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let input = || alice.utxo_input(test_ledger.funds, 0, 1000);
+        let pending = test_ledger.transaction(&[input()], &[bob.output(1000 - fee(1, 1))]);
+        // the fee grows with the number of outputs, 14 exceeds 12 by more
+        // than the margin
+        let value = 1000 - fee(1, 3);
+        let replacement = test_ledger.transaction(
+            &[input()],
+            &[bob.output(value - 2), bob.output(1), bob.output(1)],
+        );
+        let mut pool = test_ledger.pool(&[&pending]);
+
+        let replaced = test_ledger.admit(&mut pool, &replacement).unwrap();
+
+        assert_eq!(replaced, vec![pending.id()]);
+        assert!(pool.get(&pending.id()).is_none());
+        assert!(pool.get(&replacement.id()).is_some());
+    }
+
+    #[test]
+    fn fee_increase_within_the_margin_does_not_replace() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let input = || alice.utxo_input(test_ledger.funds, 0, 1000);
+        let pending = test_ledger.transaction(&[input()], &[bob.output(1000 - fee(1, 1))]);
+        // 13 does not exceed 12 by more than 10%
+        let value = 1000 - fee(1, 2);
+        let replacement =
+            test_ledger.transaction(&[input()], &[bob.output(value - 1), bob.output(1)]);
+        let mut pool = test_ledger.pool(&[&pending]);
+
+        let result = test_ledger.admit(&mut pool, &replacement);
+
+        match result {
+            Err(AdmissionError::FeeTooLowToReplace {
+                fee: paid,
+                threshold,
+                ..
+            }) => {
+                assert_eq!(paid, Value(fee(1, 2)));
+                assert_eq!(threshold, Value(fee(1, 1) * 110 / 100));
             }
+            _ => panic!("the replacement is admitted"),
         }
+        assert!(pool.get(&pending.id()).is_some());
+    }
+
+    #[test]
+    fn higher_fee_replaces_pending_account_spend() {
+        let alice = Wallet::account();
+        let bob = Wallet::account();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let pending = test_ledger.transaction(
+            &[alice.account_input(0, 100 + fee(1, 1))],
+            &[bob.output(100)],
+        );
+        let replacement = test_ledger.transaction(
+            &[alice.account_input(0, 100 + fee(1, 3))],
+            &[bob.output(98), bob.output(1), bob.output(1)],
+        );
+        let mut pool = test_ledger.pool(&[&pending]);
+
+        let replaced = test_ledger.admit(&mut pool, &replacement).unwrap();
+
+        assert_eq!(replaced, vec![pending.id()]);
+        assert!(pool.get(&pending.id()).is_none());
+        assert!(pool.get(&replacement.id()).is_some());
+    }
+
+    #[test]
+    fn replacement_evicts_the_dependents_of_the_replaced_fragment() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let carol = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let input = || alice.utxo_input(test_ledger.funds, 0, 1000);
+        let value = 1000 - fee(1, 1);
+        let pending = test_ledger.transaction(&[input()], &[bob.output(value)]);
+        let dependent = test_ledger.transaction(
+            &[bob.utxo_input(pending.id(), 0, value)],
+            &[carol.output(value - fee(1, 1))],
+        );
+        let value = 1000 - fee(1, 3);
+        let replacement = test_ledger.transaction(
+            &[input()],
+            &[carol.output(value - 2), carol.output(1), carol.output(1)],
+        );
+        let mut pool = test_ledger.pool(&[&pending, &dependent]);
+
+        test_ledger.admit(&mut pool, &replacement).unwrap();
+
+        assert!(pool.get(&pending.id()).is_none());
+        assert!(pool.get(&dependent.id()).is_none());
+        assert!(pool.get(&replacement.id()).is_some());
+    }
 
-        pub fn remove_oldest(&mut self) -> Option<Fragment> {
-            self.entries.pop_lru().map(|(_, value)| value)
+    #[test]
+    fn fragments_expire_after_the_time_to_live() {
+        let alice = Wallet::account();
+        let bob = Wallet::account();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let value = 100 + fee(1, 1);
+        let old = test_ledger.transaction(&[alice.account_input(0, value)], &[bob.output(100)]);
+        let recent = test_ledger.transaction(&[alice.account_input(1, value)], &[bob.output(100)]);
+        let mut pool = test_ledger.pool(&[]);
+        let start = Instant::now();
+        for (fragment, inserted_at) in &[(&old, start), (&recent, start + TTL / 2)] {
+            let admission = pool
+                .admit(
+                    fragment,
+                    &test_ledger.ledger,
+                    &test_ledger.params,
+                    test_ledger.date,
+                )
+                .unwrap();
+            pool.insert_at((*fragment).clone(), admission.spending, *inserted_at);
         }
+
+        let now = start + TTL;
+        assert_eq!(pool.remove_expired(now), vec![(old.id(), Vec::new())]);
+        assert!(pool.get(&old.id()).is_none());
+        assert!(pool.get(&recent.id()).is_some());
+        assert!(pool.remove_expired(now).is_empty());
+        assert_eq!(
+            pool.remove_expired(now + TTL / 2),
+            vec![(recent.id(), Vec::new())]
+        );
+        assert_eq!(pool.fragments().count(), 0);
+    }
+
+    #[test]
+    fn expired_fragments_are_removed_with_their_dependents() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let carol = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let value = 1000 - fee(1, 1);
+        let parent = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(value)],
+        );
+        let dependent = test_ledger.transaction(
+            &[bob.utxo_input(parent.id(), 0, value)],
+            &[carol.output(value - fee(1, 1))],
+        );
+        let mut pool = test_ledger.pool(&[]);
+        let start = Instant::now();
+        for (fragment, inserted_at) in &[(&parent, start), (&dependent, start + TTL / 2)] {
+            let admission = pool
+                .admit(
+                    fragment,
+                    &test_ledger.ledger,
+                    &test_ledger.params,
+                    test_ledger.date,
+                )
+                .unwrap();
+            pool.insert_at((*fragment).clone(), admission.spending, *inserted_at);
+        }
+
+        assert_eq!(
+            pool.remove_expired(start + TTL),
+            vec![(parent.id(), vec![dependent.id()])]
+        );
+        assert_eq!(pool.fragments().count(), 0);
+    }
+
+    #[test]
+    fn full_pool_evicts_the_oldest_fragment_with_its_dependents() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let carol = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000), (&carol, 1000)]);
+        let value = 1000 - fee(1, 1);
+        let parent = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(value)],
+        );
+        let dependent = test_ledger.transaction(
+            &[bob.utxo_input(parent.id(), 0, value)],
+            &[alice.output(value - fee(1, 1))],
+        );
+        let other = test_ledger.transaction(
+            &[carol.utxo_input(test_ledger.funds, 1, 1000)],
+            &[bob.output(value)],
+        );
+        let mut pool = Pool::new(2, 0, TTL);
+        test_ledger.admit(&mut pool, &parent).unwrap();
+        assert!(pool.remove_oldest_if_full().is_none());
+        test_ledger.admit(&mut pool, &dependent).unwrap();
+
+        assert_eq!(
+            pool.remove_oldest_if_full(),
+            Some((parent.id(), vec![dependent.id()]))
+        );
+        test_ledger.admit(&mut pool, &other).unwrap();
+        assert_eq!(pool.fragments().count(), 1);
+        assert!(pool.get(&other.id()).is_some());
+    }
+
+    #[test]
+    fn wait_time_counts_whole_seconds_since_insertion() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let tx = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(1000 - fee(1, 1))],
+        );
+        let mut pool = test_ledger.pool(&[]);
+        let admission = pool
+            .admit(
+                &tx,
+                &test_ledger.ledger,
+                &test_ledger.params,
+                test_ledger.date,
+            )
+            .unwrap();
+        let inserted_at = Instant::now();
+        pool.insert_at(tx.clone(), admission.spending, inserted_at);
+        let now = inserted_at + Duration::from_millis(2500);
+
+        assert_eq!(pool.wait_time(&tx.id(), now), Duration::from_secs(2));
+        assert_eq!(
+            pool.wait_time(&FragmentId::hash_bytes(b"unknown"), now),
+            Duration::default()
+        );
     }
 }
@@ -1,27 +1,63 @@
 use crate::{
-    fragment::{Logs, Pool},
-    intercom::{NetworkMsg, TransactionMsg},
+    blockchain::Tip,
+    fragment::{Logs, Persistence, Pool, Submitted},
+    intercom::{NetworkMsg, ReplySendError, TransactionMsg},
     stats_counter::StatsCounter,
     utils::{
         async_msg::{MessageBox, MessageQueue},
         task::TokioServiceInfo,
     },
 };
-use tokio::stream::StreamExt;
+use futures::{channel::oneshot, sink::SinkExt};
+use jormungandr_lib::interfaces::{FragmentAdmission, FragmentOrigin};
+use slog::Logger;
+use std::time::Duration;
+use tokio::{
+    stream::{self, StreamExt},
+    task::spawn_blocking,
+};
+
+/// interval between two snapshots of the mempool when it is persistent
+const PERSISTENCE_INTERVAL: Duration = Duration::from_secs(30);
+/// interval between two removals of the expired fragments
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(10);
 
 pub struct Process {
     pool: Pool,
+    tip: Tip,
+    persistence: Option<Persistence>,
+}
+
+enum Input {
+    Message(TransactionMsg),
+    Persist,
+    RemoveExpired,
+    /// the node shuts down, the sender is notified once the mempool is saved
+    Shutdown(oneshot::Sender<()>),
+    Closed,
 }
 
 impl Process {
     pub fn new(
         pool_max_entries: usize,
         logs_max_entries: usize,
+        fee_replacement_margin: u64,
+        ttl: Duration,
         network_msg_box: MessageBox<NetworkMsg>,
+        tip: Tip,
+        persistence: Option<Persistence>,
     ) -> Self {
         let logs = Logs::new(logs_max_entries);
         Process {
-            pool: Pool::new(pool_max_entries, logs, network_msg_box),
+            pool: Pool::new(
+                pool_max_entries,
+                fee_replacement_margin,
+                ttl,
+                logs,
+                network_msg_box,
+            ),
+            tip,
+            persistence,
         }
     }
 
@@ -29,29 +65,103 @@ impl Process {
         self,
         service_info: TokioServiceInfo,
         stats_counter: StatsCounter,
-        mut input: MessageQueue<TransactionMsg>,
+        input: MessageQueue<TransactionMsg>,
+        shutdown: oneshot::Receiver<oneshot::Sender<()>>,
     ) -> Result<(), ()> {
         let mut pool = self.pool;
+        let tip = self.tip;
+        let persistence = self.persistence;
+        let logger = service_info.logger();
+
+        if let Some(persistence) = &persistence {
+            match persistence.load() {
+                Ok(Some(snapshot)) => {
+                    let tip_ref = tip.get_ref().await;
+                    let count = pool.restore(snapshot, &tip_ref, logger);
+                    info!(logger, "mempool restored"; "fragments" => count);
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    warn!(logger, "cannot restore the mempool, starting with an empty one"; "reason" => %error);
+                }
+            }
+        }
 
-        while let Some(input_result) = input.next().await {
+        let persist_ticks = tokio::time::interval(PERSISTENCE_INTERVAL)
+            .map(|_| Input::Persist)
+            .filter(|_| persistence.is_some());
+        let expiry_ticks =
+            tokio::time::interval(EXPIRY_SWEEP_INTERVAL).map(|_| Input::RemoveExpired);
+        let mut input = input
+            .map(Input::Message)
+            .chain(stream::once(Input::Closed))
+            .merge(persist_ticks)
+            .merge(expiry_ticks)
+            .merge(
+                futures::stream::once(shutdown).filter_map(|saved| saved.ok().map(Input::Shutdown)),
+            );
+
+        let mut saved = None;
+        while let Some(input) = input.next().await {
+            let input_result = match input {
+                Input::Message(msg) => msg,
+                Input::Persist => {
+                    save(&pool, &persistence, logger).await;
+                    continue;
+                }
+                Input::RemoveExpired => {
+                    let count = pool.remove_expired(logger);
+                    if count > 0 {
+                        debug!(logger, "expired fragments removed from the mempool"; "count" => count);
+                    }
+                    continue;
+                }
+                Input::Shutdown(notifier) => {
+                    saved = Some(notifier);
+                    break;
+                }
+                Input::Closed => break,
+            };
             match input_result {
                 TransactionMsg::SendTransaction(origin, txs) => {
-                    // Note that we cannot use apply_block here, since we don't have a valid context to which to apply
-                    // those blocks. one valid tx in a given context, could be invalid in another. for example
-                    // fee calculations, existence utxo / account solvency.
-
-                    // FIXME/TODO check that the txs are valid within themselves with basic requirements (e.g. inputs >= outputs).
-                    // we also want to keep a basic capability to filter away repetitive queries or definitely discarded txid.
+                    // The fragments are checked against the ledger of the current tip before being
+                    // registered. One valid tx in a given context could be invalid in another (for
+                    // example fee calculations, existence utxo / account solvency), so the fragments
+                    // are checked again when selected for a block.
 
                     // This interface only makes sense for messages coming from arbitrary users (like transaction, certificates),
                     // for other message we don't want to receive them through this interface, and possibly
                     // put them in another pool.
 
                     let stats_counter = stats_counter.clone();
+                    let tip_ref = tip.get_ref().await;
 
-                    pool.insert_and_propagate_all(origin, txs, service_info.logger().clone())
+                    pool.insert_and_propagate_all(origin, txs, &tip_ref, logger.clone())
                         .await
-                        .map(move |count| stats_counter.add_tx_recv_cnt(count))?;
+                        .map(move |submissions| {
+                            stats_counter.add_tx_recv_cnt(accepted_count(&submissions))
+                        })?;
+                }
+                TransactionMsg::SubmitTransactions(origin, txs, reply_handle) => {
+                    let tip_ref = tip.get_ref().await;
+                    let submissions = pool
+                        .insert_and_propagate_all(origin, txs, &tip_ref, logger.clone())
+                        .await?;
+                    stats_counter.add_tx_recv_cnt(accepted_count(&submissions));
+                    reply_handle.reply_ok(submissions.iter().map(Submitted::submission).collect());
+                }
+                TransactionMsg::ReceiveFragments(txs, reply_handle) => {
+                    let tip_ref = tip.get_ref().await;
+                    let submissions = pool
+                        .insert_and_propagate_all(
+                            FragmentOrigin::Network,
+                            txs,
+                            &tip_ref,
+                            logger.clone(),
+                        )
+                        .await?;
+                    stats_counter.add_tx_recv_cnt(accepted_count(&submissions));
+                    reply_handle.reply_ok(submissions);
                 }
                 TransactionMsg::RemoveTransactions(fragment_ids, status) => {
                     pool.remove_added_to_block(fragment_ids, status);
@@ -60,6 +170,31 @@ impl Process {
                     let logs = pool.logs().logs().cloned().collect();
                     reply_handle.reply_ok(logs);
                 }
+                TransactionMsg::GetStatuses(fragment_ids, reply_handle) => {
+                    let logs = pool.logs();
+                    let statuses = fragment_ids
+                        .into_iter()
+                        .filter_map(|fragment_id| logs.get(fragment_id).cloned())
+                        .collect();
+                    reply_handle.reply_ok(statuses);
+                }
+                TransactionMsg::GetFragments(fragment_ids, mut reply_handle) => {
+                    let fragments = pool.get_all(fragment_ids);
+                    service_info.spawn_fallible("get fragments", async move {
+                        for fragment in fragments {
+                            reply_handle.send(Ok(fragment)).await?;
+                        }
+                        Ok::<(), ReplySendError>(())
+                    });
+                }
+                TransactionMsg::GetMempool {
+                    offset,
+                    limit,
+                    reply_handle,
+                } => {
+                    let info = pool.info(offset, limit);
+                    reply_handle.reply_ok(info);
+                }
                 TransactionMsg::SelectTransactions {
                     ledger,
                     block_date,
@@ -73,6 +208,31 @@ impl Process {
             }
         }
 
+        save(&pool, &persistence, logger).await;
+        if let Some(saved) = saved {
+            let _ = saved.send(());
+        }
         Ok(())
     }
 }
+
+fn accepted_count(submissions: &[Submitted]) -> usize {
+    submissions
+        .iter()
+        .filter(|submission| submission.admission == FragmentAdmission::Accepted)
+        .count()
+}
+
+/// Save a snapshot of the mempool. The snapshot is taken on the spot, it is
+/// encoded and written on a blocking thread.
+async fn save(pool: &Pool, persistence: &Option<Persistence>, logger: &Logger) {
+    if let Some(persistence) = persistence {
+        let persistence = persistence.clone();
+        let snapshot = pool.snapshot();
+        match spawn_blocking(move || persistence.save(snapshot)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => error!(logger, "cannot save the mempool"; "reason" => %error),
+            Err(error) => error!(logger, "the mempool saving task failed"; "reason" => %error),
+        }
+    }
+}
@@ -1,11 +1,17 @@
 use super::logs::Logs;
-use super::pool::internal::Pool;
+use super::pool::{internal::Pool, rejection_reason};
 use crate::{
-    blockcfg::{BlockDate, Contents, ContentsBuilder, Ledger, LedgerParameters},
-    fragment::FragmentId,
+    blockcfg::{BlockDate, Contents, ContentsBuilder, Ledger, LedgerParameters, Value},
+    fragment::{Fragment, FragmentId},
 };
 use chain_core::property::Fragment as _;
+use chain_impl_mockchain::transaction::Transaction;
 use jormungandr_lib::interfaces::FragmentStatus;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
 
 pub enum SelectionOutput {
     Commit { fragment_id: FragmentId },
@@ -27,28 +33,36 @@ pub trait FragmentSelectionAlgorithm {
     fn finalize(self) -> Contents;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FragmentSelectionAlgorithmParams {
+    /// select the fragments in the order they were received
     OldestFirst,
+    /// select the fragments paying the highest fee per byte first
+    FeePerByte,
+}
+
+impl Default for FragmentSelectionAlgorithmParams {
+    fn default() -> Self {
+        FragmentSelectionAlgorithmParams::OldestFirst
+    }
 }
 
 pub struct OldestFirst {
-    builder: ContentsBuilder,
-    current_total_size: u32,
+    selected: Selected,
 }
 
 impl OldestFirst {
     pub fn new() -> Self {
         OldestFirst {
-            builder: ContentsBuilder::new(),
-            current_total_size: 0,
+            selected: Selected::new(),
         }
     }
 }
 
 impl FragmentSelectionAlgorithm for OldestFirst {
     fn finalize(self) -> Contents {
-        self.builder.into()
+        self.selected.builder.into()
     }
 
     fn select(
@@ -58,38 +72,399 @@ impl FragmentSelectionAlgorithm for OldestFirst {
         block_date: BlockDate,
         logs: &mut Logs,
         pool: &mut Pool,
+    ) {
+        // the pool iterates from the most recently received fragment
+        // to the oldest one.
+        let candidates = pool
+            .fragments()
+            .enumerate()
+            .map(|(age, fragment)| Candidate::new(fragment, age))
+            .collect();
+
+        self.selected
+            .select_candidates(candidates, ledger, ledger_params, block_date, logs, pool)
+    }
+}
+
+/// Select the fragments paying the highest fee per byte of block space
+/// first. Fragments of equal priority are selected in the order they were
+/// received.
+pub struct FeePerByte {
+    selected: Selected,
+}
+
+impl FeePerByte {
+    pub fn new() -> Self {
+        FeePerByte {
+            selected: Selected::new(),
+        }
+    }
+}
+
+impl FragmentSelectionAlgorithm for FeePerByte {
+    fn finalize(self) -> Contents {
+        self.selected.builder.into()
+    }
+
+    fn select(
+        &mut self,
+        ledger: &Ledger,
+        ledger_params: &LedgerParameters,
+        block_date: BlockDate,
+        logs: &mut Logs,
+        pool: &mut Pool,
+    ) {
+        let candidates = pool
+            .fragments()
+            .enumerate()
+            .map(|(age, fragment)| {
+                let Candidate { id, size, .. } = Candidate::new(fragment, age);
+                let priority = FeeRate {
+                    fee: fragment_fee(fragment),
+                    size,
+                    age,
+                };
+                Candidate { priority, id, size }
+            })
+            .collect();
+
+        self.selected
+            .select_candidates(candidates, ledger, ledger_params, block_date, logs, pool)
+    }
+}
+
+/// an entry of the priority queue of fragments to select
+struct Candidate<P> {
+    priority: P,
+    id: FragmentId,
+    size: u32,
+}
+
+/// the fee paid per byte of block space, fragments with the same fee rate
+/// are ordered by age
+struct FeeRate {
+    fee: Value,
+    size: u32,
+    /// the rank of the fragment in the pool, the higher the older
+    age: usize,
+}
+
+impl Candidate<usize> {
+    fn new(fragment: &Fragment, age: usize) -> Self {
+        let fragment_raw = fragment.to_raw(); // TODO: replace everything to FragmentRaw in the node
+        Candidate {
+            priority: age,
+            id: fragment_raw.id(),
+            size: fragment_raw.size_bytes_plus_size() as u32,
+        }
+    }
+}
+
+impl<P: Ord> PartialEq for Candidate<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<P: Ord> Eq for Candidate<P> {}
+
+impl<P: Ord> PartialOrd for Candidate<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P: Ord> Ord for Candidate<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+    }
+}
+
+impl PartialEq for FeeRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FeeRate {}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare `fee / size` without losing precision to a division
+        let lhs = self.fee.0 as u128 * other.size as u128;
+        let rhs = other.fee.0 as u128 * self.size as u128;
+        lhs.cmp(&rhs).then_with(|| self.age.cmp(&other.age))
+    }
+}
+
+/// the contents of the block being built
+struct Selected {
+    builder: ContentsBuilder,
+    current_total_size: u32,
+}
+
+impl Selected {
+    fn new() -> Self {
+        Selected {
+            builder: ContentsBuilder::new(),
+            current_total_size: 0,
+        }
+    }
+
+    /// Apply the candidates to the ledger by order of priority until the
+    /// block is full.
+    ///
+    /// A candidate whose predecessors (the fragments producing the outputs
+    /// it spends, creating its accounts or using the previous spending
+    /// counters of its accounts) are not applied yet stays in the pool. It
+    /// is reconsidered once one of its predecessors has been applied.
+    /// Candidates that do not fit in the remaining space of the block are
+    /// left in the pool for the next block.
+    fn select_candidates<P: Ord>(
+        &mut self,
+        mut candidates: BinaryHeap<Candidate<P>>,
+        ledger: &Ledger,
+        ledger_params: &LedgerParameters,
+        block_date: BlockDate,
+        logs: &mut Logs,
+        pool: &mut Pool,
     ) {
         let mut ledger_simulation = ledger.clone();
+        let mut parked = HashMap::new();
 
-        while let Some(fragment) = pool.remove_oldest() {
-            let id = fragment.id();
-            let fragment_raw = fragment.to_raw(); // TODO: replace everything to FragmentRaw in the node
-            let fragment_size = fragment_raw.size_bytes_plus_size() as u32;
-            let total_size = self.current_total_size + fragment_size;
-
-            if total_size <= ledger_params.block_content_max_size {
-                match ledger_simulation.apply_fragment(ledger_params, &fragment, block_date) {
-                    Ok(ledger_new) => {
-                        self.builder.push(fragment);
-                        ledger_simulation = ledger_new;
-                    }
-                    Err(error) => {
-                        use std::error::Error as _;
-                        let error = if let Some(source) = error.source() {
-                            format!("{}: {}", error, source)
-                        } else {
-                            error.to_string()
-                        };
-                        logs.modify(id, FragmentStatus::Rejected { reason: error })
-                    }
-                }
+        while let Some(candidate) = candidates.pop() {
+            if !pool.is_ready(&candidate.id, &ledger_simulation) {
+                parked.insert(candidate.id, candidate);
+                continue;
+            }
 
-                self.current_total_size = total_size;
+            let total_size = self.current_total_size + candidate.size;
+            if total_size > ledger_params.block_content_max_size {
+                continue;
+            }
+
+            let successors = pool.successors(&candidate.id);
+            let fragment = match pool.remove(&candidate.id) {
+                Some(fragment) => fragment,
+                None => continue,
+            };
 
-                if total_size == ledger_params.block_content_max_size {
-                    break;
+            match ledger_simulation.apply_fragment(ledger_params, &fragment, block_date) {
+                Ok(ledger_new) => {
+                    self.builder.push(fragment);
+                    ledger_simulation = ledger_new;
+                    self.current_total_size = total_size;
+                    candidates.extend(successors.iter().filter_map(|id| parked.remove(id)));
                 }
+                Err(error) => logs.modify(
+                    candidate.id,
+                    FragmentStatus::Rejected {
+                        reason: rejection_reason(&error),
+                    },
+                ),
+            }
+
+            if self.current_total_size == ledger_params.block_content_max_size {
+                break;
             }
         }
     }
 }
+
+/// compute the fee the given fragment actually pays: the value of its inputs
+/// not spent by its outputs. Fragments that are not transactions do not pay
+/// fees.
+pub fn fragment_fee(fragment: &Fragment) -> Value {
+    match fragment {
+        Fragment::Transaction(tx) => paid_fee(tx),
+        Fragment::OwnerStakeDelegation(tx) => paid_fee(tx),
+        Fragment::StakeDelegation(tx) => paid_fee(tx),
+        Fragment::PoolRegistration(tx) => paid_fee(tx),
+        Fragment::PoolRetirement(tx) => paid_fee(tx),
+        Fragment::PoolUpdate(tx) => paid_fee(tx),
+        Fragment::VotePlan(tx) => paid_fee(tx),
+        Fragment::VoteCast(tx) => paid_fee(tx),
+        Fragment::Initial(_)
+        | Fragment::OldUtxoDeclaration(_)
+        | Fragment::UpdateProposal(_)
+        | Fragment::UpdateVote(_) => Value::zero(),
+    }
+}
+
+fn paid_fee<P>(tx: &Transaction<P>) -> Value {
+    let tx = tx.as_slice();
+    match (tx.total_input(), tx.total_output()) {
+        (Ok(input), Ok(output)) => input.checked_sub(output).unwrap_or_else(|_| Value::zero()),
+        _ => Value::zero(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragment::test_utils::{fee, TestLedger, Wallet};
+
+    fn fee_rate(fee: u64, size: u32, age: usize) -> FeeRate {
+        FeeRate {
+            fee: Value(fee),
+            size,
+            age,
+        }
+    }
+
+    #[test]
+    fn fee_rate_orders_by_fee_per_byte() {
+        // 10 per byte against 7.5 per byte, the larger fee pays less per byte
+        assert!(fee_rate(100, 10, 0) > fee_rate(150, 20, 0));
+        // the comparison does not lose precision to an integer division
+        assert!(fee_rate(101, 100, 0) > fee_rate(100, 100, 0));
+        assert!(fee_rate(199, 200, 0) < fee_rate(100, 100, 0));
+        // a fragment not paying any fee comes last
+        assert!(fee_rate(0, 10, 5) < fee_rate(1, 1000, 0));
+    }
+
+    #[test]
+    fn fee_rate_ties_are_broken_by_age() {
+        // same fee per byte, the oldest fragment first
+        assert!(fee_rate(100, 10, 2) > fee_rate(200, 20, 1));
+        assert!(fee_rate(100, 10, 1) == fee_rate(200, 20, 1));
+    }
+
+    #[test]
+    fn candidates_are_popped_by_priority() {
+        let id = |byte: u8| FragmentId::hash_bytes(&[byte]);
+        let candidates = vec![
+            (fee_rate(10, 10, 0), 0),
+            (fee_rate(50, 10, 1), 1),
+            (fee_rate(20, 10, 3), 2),
+            (fee_rate(20, 10, 2), 3),
+        ];
+        let mut heap: BinaryHeap<Candidate<FeeRate>> = candidates
+            .into_iter()
+            .map(|(priority, byte)| Candidate {
+                priority,
+                id: id(byte),
+                size: 10,
+            })
+            .collect();
+        let mut popped = Vec::new();
+        while let Some(candidate) = heap.pop() {
+            popped.push(candidate.id);
+        }
+        assert_eq!(popped, vec![id(1), id(2), id(3), id(0)]);
+    }
+
+    /// select the contents of a block from the pool, trying the given
+    /// fragments in order
+    fn select_in_order(
+        test_ledger: &TestLedger,
+        pool: &mut Pool,
+        order: &[&Fragment],
+    ) -> Vec<FragmentId> {
+        let candidates = order
+            .iter()
+            .enumerate()
+            .map(|(rank, fragment)| Candidate::new(fragment, order.len() - rank))
+            .collect();
+        let mut selected = Selected::new();
+        selected.select_candidates(
+            candidates,
+            &test_ledger.ledger,
+            &test_ledger.params,
+            test_ledger.date,
+            &mut Logs::new(100),
+            pool,
+        );
+        let contents: Contents = selected.builder.into();
+        contents.iter().map(|fragment| fragment.id()).collect()
+    }
+
+    #[test]
+    fn utxo_chain_is_selected_in_dependency_order() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let first_value = 1000 - fee(1, 1);
+        let first = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(first_value)],
+        );
+        let second = test_ledger.transaction(
+            &[bob.utxo_input(first.id(), 0, first_value)],
+            &[alice.output(first_value - fee(1, 1))],
+        );
+        let mut pool = test_ledger.pool(&[&first, &second]);
+
+        let selected = select_in_order(&test_ledger, &mut pool, &[&second, &first]);
+
+        assert_eq!(selected, vec![first.id(), second.id()]);
+    }
+
+    #[test]
+    fn account_chain_is_selected_in_spending_counter_order() {
+        let alice = Wallet::account();
+        let bob = Wallet::account();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let transactions: Vec<Fragment> = (0..3)
+            .map(|counter| {
+                test_ledger.transaction(
+                    &[alice.account_input(counter, 100 + fee(1, 1))],
+                    &[bob.output(100)],
+                )
+            })
+            .collect();
+        let mut pool = test_ledger.pool(&transactions.iter().collect::<Vec<_>>());
+
+        let order: Vec<&Fragment> = transactions.iter().rev().collect();
+        let selected = select_in_order(&test_ledger, &mut pool, &order);
+
+        let expected: Vec<FragmentId> = transactions.iter().map(|tx| tx.id()).collect();
+        assert_eq!(selected, expected);
+    }
+
+    #[test]
+    fn account_created_by_a_pending_fragment_is_spent_after_it() {
+        let alice = Wallet::utxo();
+        let bob = Wallet::account();
+        let carol = Wallet::utxo();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let credit_value = 1000 - fee(1, 1);
+        let credit = test_ledger.transaction(
+            &[alice.utxo_input(test_ledger.funds, 0, 1000)],
+            &[bob.output(credit_value)],
+        );
+        let spend = test_ledger.transaction(
+            &[bob.account_input(0, 100 + fee(1, 1))],
+            &[carol.output(100)],
+        );
+        let mut pool = test_ledger.pool(&[&credit, &spend]);
+
+        let selected = select_in_order(&test_ledger, &mut pool, &[&spend, &credit]);
+
+        assert_eq!(selected, vec![credit.id(), spend.id()]);
+    }
+
+    #[test]
+    fn fragment_waiting_on_a_missing_predecessor_is_not_selected() {
+        let alice = Wallet::account();
+        let bob = Wallet::account();
+        let test_ledger = TestLedger::new(&[(&alice, 1000)]);
+        let value = 100 + fee(1, 1);
+        let first = test_ledger.transaction(&[alice.account_input(0, value)], &[bob.output(100)]);
+        let second = test_ledger.transaction(&[alice.account_input(1, value)], &[bob.output(100)]);
+        let mut pool = test_ledger.pool(&[&first, &second]);
+        // the predecessor leaves the pool without being selected
+        pool.remove(&first.id());
+
+        let selected = select_in_order(&test_ledger, &mut pool, &[&second]);
+
+        assert!(selected.is_empty());
+        assert!(pool.get(&second.id()).is_some());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::blockcfg::{Fragment, FragmentId, Ledger};
+use chain_addr::Kind;
+use chain_crypto::Verification;
+use chain_impl_mockchain::{
+    account::{self, SpendingCounter},
+    transaction::{
+        InputEnum, Transaction, TransactionSignDataHash, UtxoPointer, Witness, WitnessAccountData,
+    },
+};
+
+/// how far beyond the current spending counter of an account we look
+/// for the counter an account input has been signed with.
+const SPENDING_COUNTER_LOOKAHEAD: u32 = 32;
+
+/// The resources a fragment consumes: the UTxOs it spends and the accounts
+/// it debits, along with the accounts it credits, which it may create.
+///
+/// The spending counter of an account input is not part of the
+/// transaction, it is only committed to by the witness. It is resolved
+/// against a ledger by checking the witness against the counters following
+/// the current counter of the account.
+#[derive(Default)]
+pub struct Spending {
+    utxos: Vec<UtxoPointer>,
+    accounts: Vec<AccountSpending>,
+    credited_accounts: Vec<account::Identifier>,
+    sign_data_hash: Option<TransactionSignDataHash>,
+}
+
+pub struct AccountSpending {
+    id: account::Identifier,
+    witness: account::Witness,
+    counter: Option<u32>,
+}
+
+impl Spending {
+    pub fn new(fragment: &Fragment) -> Self {
+        match fragment {
+            Fragment::Transaction(tx) => Self::from_transaction(tx),
+            Fragment::OwnerStakeDelegation(tx) => Self::from_transaction(tx),
+            Fragment::StakeDelegation(tx) => Self::from_transaction(tx),
+            Fragment::PoolRegistration(tx) => Self::from_transaction(tx),
+            Fragment::PoolRetirement(tx) => Self::from_transaction(tx),
+            Fragment::PoolUpdate(tx) => Self::from_transaction(tx),
+            Fragment::VotePlan(tx) => Self::from_transaction(tx),
+            Fragment::VoteCast(tx) => Self::from_transaction(tx),
+            Fragment::Initial(_)
+            | Fragment::OldUtxoDeclaration(_)
+            | Fragment::UpdateProposal(_)
+            | Fragment::UpdateVote(_) => Spending::default(),
+        }
+    }
+
+    fn from_transaction<P>(tx: &Transaction<P>) -> Self {
+        let tx = tx.as_slice();
+        let mut utxos = Vec::new();
+        let mut accounts = Vec::new();
+
+        for (input, witness) in tx.inputs().iter().zip(tx.witnesses().iter()) {
+            match (input.to_enum(), witness) {
+                (InputEnum::UtxoInput(pointer), _) => utxos.push(pointer),
+                (InputEnum::AccountInput(id, _), Witness::Account(witness)) => {
+                    // multisig accounts are not tracked
+                    if let Some(id) = id.to_single_account() {
+                        accounts.push(AccountSpending {
+                            id,
+                            witness,
+                            counter: None,
+                        })
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut credited_accounts = Vec::new();
+        for output in tx.outputs().iter() {
+            if let Kind::Account(public_key) = output.address.kind() {
+                let id = account::Identifier::from(public_key.clone());
+                if !credited_accounts.contains(&id) {
+                    credited_accounts.push(id);
+                }
+            }
+        }
+
+        Spending {
+            utxos,
+            accounts,
+            credited_accounts,
+            sign_data_hash: Some(tx.transaction_sign_data_hash()),
+        }
+    }
+
+    /// the outputs spent by the fragment
+    pub fn utxos(&self) -> impl Iterator<Item = &UtxoPointer> {
+        self.utxos.iter()
+    }
+
+    /// the account inputs of the fragment
+    pub fn accounts(&self) -> impl Iterator<Item = &AccountSpending> {
+        self.accounts.iter()
+    }
+
+    /// the accounts the outputs of the fragment pay to
+    pub fn credited_accounts(&self) -> impl Iterator<Item = &account::Identifier> {
+        self.credited_accounts.iter()
+    }
+
+    /// Resolve the spending counters of the account inputs against the given
+    /// ledger. Returns the newly resolved account inputs.
+    pub fn resolve_counters(&mut self, ledger: &Ledger) -> Vec<(account::Identifier, u32)> {
+        let sign_data_hash = match &self.sign_data_hash {
+            Some(sign_data_hash) => sign_data_hash,
+            None => return Vec::new(),
+        };
+        let block0_hash = ledger.get_static_parameters().block0_initial_hash;
+
+        let mut resolved = Vec::new();
+        for account in self.accounts.iter_mut().filter(|a| a.counter.is_none()) {
+            let current = match ledger.accounts().get_state(&account.id) {
+                Ok(state) => u32::from(state.counter),
+                // the account may be created by a fragment not yet in a block
+                Err(_) => continue,
+            };
+            let public_key = account.id.clone().into();
+            let witness = &account.witness;
+            let mut candidates = current..current.saturating_add(SPENDING_COUNTER_LOOKAHEAD);
+            account.counter = candidates.find(|counter| {
+                let data = WitnessAccountData::new(
+                    &block0_hash,
+                    sign_data_hash,
+                    &SpendingCounter::from(*counter),
+                );
+                witness.verify(&public_key, &data) == Verification::Success
+            });
+            if let Some(counter) = account.counter {
+                resolved.push((account.id.clone(), counter));
+            }
+        }
+        resolved
+    }
+
+    /// Check the fragment can be applied on top of the given ledger as far
+    /// as its dependencies are concerned: the fragments producing the
+    /// spent outputs are not pending anymore and the spending counters of
+    /// the accounts match the ledger's.
+    ///
+    /// An account input waits on the pending fragments involving the same
+    /// account when it cannot be checked against the ledger yet:
+    /// `is_credit_pending` tells whether a pending fragment pays to an
+    /// account, which may create it, and `is_debit_pending` whether another
+    /// pending fragment spends from an account, which moves its spending
+    /// counter forward.
+    ///
+    /// A fragment using a spending counter that has already been used, or
+    /// whose predecessors are all applied, is considered ready so that the
+    /// ledger rejects it if it is invalid.
+    pub fn is_ready(
+        &self,
+        ledger: &Ledger,
+        is_pending: impl Fn(&FragmentId) -> bool,
+        is_credit_pending: impl Fn(&account::Identifier) -> bool,
+        is_debit_pending: impl Fn(&account::Identifier) -> bool,
+    ) -> bool {
+        let utxos_ready = self
+            .utxos
+            .iter()
+            .all(|pointer| !is_pending(&pointer.transaction_id));
+
+        let accounts_ready = self.accounts.iter().all(|account| {
+            match (account.counter, ledger.accounts().get_state(&account.id)) {
+                (Some(counter), Ok(state)) => counter <= u32::from(state.counter),
+                // the counter is beyond the reach of the ledger's counter,
+                // the pending spendings of the account may bring it closer
+                (None, Ok(_)) => !is_debit_pending(&account.id),
+                // the account may be created by a pending fragment
+                (_, Err(_)) => !is_credit_pending(&account.id),
+            }
+        });
+
+        utxos_ready && accounts_ready
+    }
+}
+
+impl AccountSpending {
+    pub fn id(&self) -> &account::Identifier {
+        &self.id
+    }
+
+    /// the spending counter of the input, if it was resolved
+    pub fn counter(&self) -> Option<u32> {
+        self.counter
+    }
+}
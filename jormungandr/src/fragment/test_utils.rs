@@ -0,0 +1,190 @@
+//! Ledgers and transactions for the tests of the mempool.
+
+use crate::blockcfg::{BlockDate, HeaderHash, Ledger, LedgerParameters, Value};
+use crate::fragment::pool::{internal::Pool, AdmissionError};
+use crate::fragment::{Fragment, FragmentId};
+use chain_addr::{Address, Discrimination, Kind};
+use chain_core::property::Fragment as _;
+use chain_crypto::{Ed25519, PublicKey, SecretKey};
+use chain_impl_mockchain::{
+    account::{self, SpendingCounter},
+    fee::LinearFee,
+    testing::ledger::ConfigBuilder,
+    transaction::{
+        Input, InputEnum, Output, TxBuilder, UnspecifiedAccountIdentifier, UtxoPointer, Witness,
+    },
+};
+use std::time::Duration;
+
+pub const FEE_CONSTANT: u64 = 10;
+pub const FEE_COEFFICIENT: u64 = 1;
+/// the minimum fee increase, in percent, to replace a pooled fragment
+pub const FEE_REPLACEMENT_MARGIN: u64 = 10;
+pub const TTL: Duration = Duration::from_secs(3600);
+
+/// the fee of a transaction with the given number of inputs and outputs
+pub fn fee(inputs: usize, outputs: usize) -> u64 {
+    FEE_CONSTANT + FEE_COEFFICIENT * (inputs + outputs) as u64
+}
+
+pub struct Wallet {
+    secret_key: SecretKey<Ed25519>,
+    address: Address,
+}
+
+impl Wallet {
+    fn new(kind: impl FnOnce(PublicKey<Ed25519>) -> Kind) -> Self {
+        let secret_key = SecretKey::generate(rand_core::OsRng);
+        let address = Address(Discrimination::Test, kind(secret_key.to_public()));
+        Wallet {
+            secret_key,
+            address,
+        }
+    }
+
+    pub fn utxo() -> Self {
+        Wallet::new(Kind::Single)
+    }
+
+    pub fn account() -> Self {
+        Wallet::new(Kind::Account)
+    }
+
+    pub fn account_id(&self) -> account::Identifier {
+        self.secret_key.to_public().into()
+    }
+
+    pub fn output(&self, value: u64) -> Output<Address> {
+        Output {
+            address: self.address.clone(),
+            value: Value(value),
+        }
+    }
+
+    /// spend the given output of a transaction paying to this wallet
+    pub fn utxo_input(&self, transaction_id: FragmentId, output_index: u8, value: u64) -> Spend {
+        Spend {
+            wallet: self,
+            input: InputEnum::UtxoInput(UtxoPointer {
+                transaction_id,
+                output_index,
+                value: Value(value),
+            }),
+            counter: None,
+        }
+    }
+
+    /// spend from the account of this wallet with the given spending counter
+    pub fn account_input(&self, counter: u32, value: u64) -> Spend {
+        Spend {
+            wallet: self,
+            input: InputEnum::AccountInput(
+                UnspecifiedAccountIdentifier::from_single_account(self.account_id()),
+                Value(value),
+            ),
+            counter: Some(counter),
+        }
+    }
+}
+
+pub struct Spend<'a> {
+    wallet: &'a Wallet,
+    input: InputEnum,
+    counter: Option<u32>,
+}
+
+pub struct TestLedger {
+    pub block0_hash: HeaderHash,
+    pub ledger: Ledger,
+    pub params: LedgerParameters,
+    pub date: BlockDate,
+    /// the block 0 transaction giving the initial funds, its outputs are
+    /// in the order of the funds
+    pub funds: FragmentId,
+}
+
+impl TestLedger {
+    /// a ledger giving the initial funds to the given wallets in block 0
+    pub fn new(funds: &[(&Wallet, u64)]) -> Self {
+        let block0_hash = HeaderHash::hash_bytes(b"block0");
+        let config = ConfigBuilder::new(0)
+            .with_discrimination(Discrimination::Test)
+            .with_fee(LinearFee::new(FEE_CONSTANT, FEE_COEFFICIENT, 0))
+            .build();
+        let outputs: Vec<_> = funds
+            .iter()
+            .map(|(wallet, value)| wallet.output(*value))
+            .collect();
+        let funds = transaction(&block0_hash, &[], &outputs);
+        let ledger = Ledger::new(block0_hash, vec![&Fragment::Initial(config), &funds]).unwrap();
+        TestLedger {
+            block0_hash,
+            params: ledger.get_ledger_parameters(),
+            date: ledger.date(),
+            ledger,
+            funds: funds.id(),
+        }
+    }
+
+    pub fn transaction(&self, inputs: &[Spend], outputs: &[Output<Address>]) -> Fragment {
+        transaction(&self.block0_hash, inputs, outputs)
+    }
+
+    /// a pool with the given fragments admitted in order against this ledger
+    pub fn pool(&self, fragments: &[&Fragment]) -> Pool {
+        let mut pool = Pool::new(100, FEE_REPLACEMENT_MARGIN, TTL);
+        for fragment in fragments {
+            self.admit(&mut pool, fragment).unwrap();
+        }
+        pool
+    }
+
+    /// admit the fragment in the pool against this ledger and insert it in
+    /// place of the fragments it replaces, removing their dependents.
+    /// Returns the replaced fragments.
+    pub fn admit(
+        &self,
+        pool: &mut Pool,
+        fragment: &Fragment,
+    ) -> Result<Vec<FragmentId>, AdmissionError> {
+        let admission = pool.admit(fragment, &self.ledger, &self.params, self.date)?;
+        for replaced in admission.replaces.iter() {
+            pool.remove_with_dependents(replaced);
+        }
+        pool.insert(fragment.clone(), admission.spending);
+        Ok(admission.replaces)
+    }
+}
+
+fn transaction(
+    block0_hash: &HeaderHash,
+    inputs: &[Spend],
+    outputs: &[Output<Address>],
+) -> Fragment {
+    let tx_inputs: Vec<Input> = inputs
+        .iter()
+        .map(|spend| Input::from_enum(spend.input.clone()))
+        .collect();
+    let builder = TxBuilder::new()
+        .set_nopayload()
+        .set_ios(&tx_inputs, outputs);
+    let sign_data_hash = builder.get_auth_data_for_witness().hash();
+    let witnesses: Vec<Witness> = inputs
+        .iter()
+        .map(|spend| {
+            let secret_key = &spend.wallet.secret_key;
+            match spend.counter {
+                Some(counter) => Witness::new_account(
+                    block0_hash,
+                    &sign_data_hash,
+                    SpendingCounter::from(counter),
+                    |data| secret_key.sign(data),
+                ),
+                None => {
+                    Witness::new_utxo(block0_hash, &sign_data_hash, |data| secret_key.sign(data))
+                }
+            }
+        })
+        .collect();
+    Fragment::Transaction(builder.set_witnesses(&witnesses).set_payload_auth(&()))
+}
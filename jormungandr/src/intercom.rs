@@ -2,12 +2,14 @@ use crate::blockcfg::{
     Block, BlockDate, Fragment, FragmentId, Header, HeaderHash, Ledger, LedgerParameters,
 };
 use crate::blockchain::Checkpoints;
-use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-use crate::network::p2p::{comm::PeerInfo, Address};
+use crate::fragment::{selection::FragmentSelectionAlgorithmParams, Submitted};
+use crate::network::p2p::{comm::PeerInfo, Address, BlockOutcome};
 use crate::utils::async_msg::{self, MessageBox, MessageQueue};
 use chain_impl_mockchain::fragment::Contents as FragmentContents;
 use chain_network::error as net_error;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use jormungandr_lib::interfaces::{
+    FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission, MempoolInfo,
+};
 
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
@@ -458,8 +460,30 @@ pub fn stream_request<T, R>(
 #[derive(Debug)]
 pub enum TransactionMsg {
     SendTransaction(FragmentOrigin, Vec<Fragment>),
+    /// same as `SendTransaction`, replying with the admission result of
+    /// each fragment
+    SubmitTransactions(
+        FragmentOrigin,
+        Vec<Fragment>,
+        ReplyHandle<Vec<FragmentSubmission>>,
+    ),
+    /// fragments received from a peer, replying with the admission result
+    /// of each fragment
+    ReceiveFragments(Vec<Fragment>, ReplyHandle<Vec<Submitted>>),
     RemoveTransactions(Vec<FragmentId>, FragmentStatus),
     GetLogs(ReplyHandle<Vec<FragmentLog>>),
+    /// get the logs of the given fragments, unknown fragments are omitted
+    GetStatuses(Vec<FragmentId>, ReplyHandle<Vec<FragmentLog>>),
+    /// get the given fragments from the pool, fragments not in the pool
+    /// are omitted
+    GetFragments(Vec<FragmentId>, ReplyStreamHandle<Fragment>),
+    /// get the summary of the pool and the pending fragments from the
+    /// given offset, oldest first
+    GetMempool {
+        offset: usize,
+        limit: usize,
+        reply_handle: ReplyHandle<MempoolInfo>,
+    },
     SelectTransactions {
         ledger: Ledger,
         block_date: BlockDate,
@@ -518,12 +542,13 @@ pub enum BlockMsg {
     LeadershipBlock(Block),
     /// A untrusted block Header has been received from the network task
     AnnouncedBlock(Header, Address),
-    /// A stream of untrusted blocks has been received from the network task.
-    NetworkBlocks(RequestStreamHandle<Block, ()>),
+    /// A stream of untrusted blocks has been received from the network task,
+    /// from the given node if it is known.
+    NetworkBlocks(RequestStreamHandle<Block, ()>, Option<Address>),
     /// The stream of headers for missing chain blocks has been received
     /// from the network in response to a PullHeaders request or a Missing
-    /// solicitation event.
-    ChainHeaders(RequestStreamHandle<Header, ()>),
+    /// solicitation event, from the given node if it is known.
+    ChainHeaders(RequestStreamHandle<Header, ()>, Option<Address>),
 }
 
 /// Propagation requests for the network task.
@@ -545,6 +570,9 @@ pub enum NetworkMsg {
         to: HeaderHash,
     },
     PeerInfo(ReplyHandle<Vec<PeerInfo>>),
+    /// The result of the validation of blocks or headers received from
+    /// the given node, to score it.
+    ScorePeer(Address, BlockOutcome),
 }
 
 /// Messages to the explorer task
@@ -0,0 +1,196 @@
+//! Block production dry run.
+//!
+//! Evaluates the leadership of the node's leaders for the epoch of the tip of
+//! the stored blockchain or the following one, then builds and signs a
+//! candidate block for the first slot of the schedule following the tip,
+//! exactly as the leadership task would. The candidate block is checked
+//! against the blockchain but it is neither stored nor broadcast.
+//!
+//! The node does not run its fragment pool during a dry run, so the
+//! candidate block has no contents. With a remote signer only the schedule
+//! is reported: the signer would refuse to sign the actual block of a slot
+//! it has signed a candidate block for.
+
+use crate::{
+    blockcfg::{ContentsBuilder, LeaderOutput},
+    blockchain::{new_epoch_leadership_from, Blockchain, CheckHeaderProof, Error, Ref},
+    leadership::{
+        enclave::{Enclave, EnclaveError, LeaderEvent},
+        process::{sign_block, slot_kes_period},
+    },
+};
+use chain_time::{
+    era::{EpochPosition, EpochSlotOffset},
+    Epoch,
+};
+use jormungandr_lib::{
+    crypto::hash::Hash,
+    interfaces::{BlockDate, EnclaveLeaderId, LeadershipScheduleEntry},
+    time::SystemTime,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DryRunError {
+    #[error("epoch {epoch} is before the epoch of the tip of the stored blockchain ({tip_epoch})")]
+    EpochInThePast { epoch: u32, tip_epoch: u32 },
+    #[error(
+        "epoch {epoch} is after the epoch following the tip of the stored blockchain ({tip_epoch})"
+    )]
+    EpochTooFar { epoch: u32, tip_epoch: u32 },
+    #[error("the time of the slot {date} is unknown, cannot compute its KES period")]
+    UnknownSlotTime { date: BlockDate },
+    #[error("cannot evaluate the leadership schedule")]
+    Schedule(#[source] EnclaveError),
+    #[error("cannot sign the candidate block")]
+    Signing(#[source] EnclaveError),
+    #[error("the candidate block is not valid")]
+    InvalidBlock(#[source] Error),
+}
+
+/// the outcome of a dry run
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub epoch: u32,
+    /// the block of the tip of the stored blockchain
+    pub tip: Hash,
+    pub tip_date: BlockDate,
+    pub schedule: Vec<LeadershipScheduleEntry>,
+    /// the candidate block is not signed when the leaders sign remotely
+    pub remote_signer: bool,
+    /// `None` if none of the leaders is elected for a slot following the tip
+    /// or if the leaders sign remotely
+    pub candidate_block: Option<CandidateBlock>,
+}
+
+/// a block built and signed, but not broadcast, during a dry run
+#[derive(Debug, Serialize)]
+pub struct CandidateBlock {
+    pub block: Hash,
+    pub parent: Hash,
+    pub chain_length: u32,
+    pub date: BlockDate,
+    pub enclave_leader_id: EnclaveLeaderId,
+    /// the period the KES key is evolved to, for the genesis praos leaders
+    pub kes_period: Option<u32>,
+}
+
+/// run the leadership of the given epoch, or of the epoch of the tip by
+/// default, on top of the tip of the stored blockchain. The candidate block
+/// is not signed if `remote_signer` is set.
+pub async fn dry_run(
+    blockchain: &Blockchain,
+    tip: Arc<Ref>,
+    enclave: &Enclave,
+    epoch: Option<u32>,
+    remote_signer: bool,
+) -> Result<DryRunReport, DryRunError> {
+    let tip_date = tip.block_date();
+    let epoch = epoch.unwrap_or(tip_date.epoch);
+    if epoch < tip_date.epoch {
+        return Err(DryRunError::EpochInThePast {
+            epoch,
+            tip_epoch: tip_date.epoch,
+        });
+    }
+    // the stake distribution of the later epochs is not known yet
+    if epoch > tip_date.epoch + 1 {
+        return Err(DryRunError::EpochTooFar {
+            epoch,
+            tip_epoch: tip_date.epoch,
+        });
+    }
+
+    // the same state the leadership task would use if the tip was still
+    // the tip of the blockchain at the given epoch, the rewards of the
+    // epoch transition are not reported
+    let (ledger, leadership, _, _, time_frame, _) =
+        new_epoch_leadership_from(Epoch(epoch), Arc::clone(&tip), false);
+
+    let era = leadership.era().clone();
+    let slot_time = |event: &LeaderEvent| -> Option<SystemTime> {
+        let slot = era.from_era_to_slot(EpochPosition {
+            epoch: Epoch(event.date.epoch),
+            slot: EpochSlotOffset(event.date.slot_id),
+        });
+        time_frame.slot_to_systemtime(slot).map(Into::into)
+    };
+
+    let events = enclave
+        .query_schedules(Arc::clone(&leadership), 0, era.slots_per_epoch())
+        .await
+        .map_err(DryRunError::Schedule)?;
+
+    let schedule = events
+        .iter()
+        .filter_map(|event| {
+            Some(LeadershipScheduleEntry {
+                enclave_leader_id: event.id,
+                scheduled_at_date: event.date.into(),
+                scheduled_at_time: slot_time(event)?,
+            })
+        })
+        .collect();
+
+    let next_event = events.iter().find(|event| event.date > tip_date);
+    let candidate_block = match next_event.filter(|_| !remote_signer) {
+        Some(event) => {
+            let kes_period = match event.output {
+                LeaderOutput::GenesisPraos(..) => {
+                    let slot_time =
+                        slot_time(event).ok_or_else(|| DryRunError::UnknownSlotTime {
+                            date: event.date.into(),
+                        })?;
+                    Some(slot_kes_period(&ledger, slot_time))
+                }
+                _ => None,
+            };
+            let block = sign_block(
+                enclave,
+                event,
+                tip.hash(),
+                tip.chain_length().increase(),
+                ContentsBuilder::new().into(),
+                // not used to sign the blocks of the BFT leaders
+                kes_period.unwrap_or(0),
+            )
+            .await
+            .map_err(DryRunError::Signing)?;
+
+            // check the block the same way the blockchain task checks the
+            // blocks of the network, without storing it
+            let post_checked = blockchain
+                .post_check_header(
+                    block.header.clone(),
+                    Arc::clone(&tip),
+                    CheckHeaderProof::Enabled,
+                )
+                .await
+                .map_err(DryRunError::InvalidBlock)?;
+            blockchain
+                .apply_block_dry_run(&post_checked, &block)
+                .map_err(DryRunError::InvalidBlock)?;
+
+            Some(CandidateBlock {
+                block: block.header.hash().into(),
+                parent: block.header.block_parent_hash().into(),
+                chain_length: block.header.chain_length().into(),
+                date: event.date.into(),
+                enclave_leader_id: event.id,
+                kes_period,
+            })
+        }
+        None => None,
+    };
+
+    Ok(DryRunReport {
+        epoch,
+        tip: tip.hash().into(),
+        tip_date: tip_date.into(),
+        schedule,
+        remote_signer,
+        candidate_block,
+    })
+}
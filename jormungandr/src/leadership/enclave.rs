@@ -4,16 +4,27 @@ use crate::{
         HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
         HeaderSetConsensusSignature, Leadership,
     },
-    secure::enclave::Enclave as SecureEnclave,
+    secure::enclave::{EnclaveBackend, SigningError},
 };
-use jormungandr_lib::interfaces::EnclaveLeaderId as LeaderId;
+use jormungandr_lib::{crypto::kes::KesPeriods, interfaces::EnclaveLeaderId as LeaderId};
 use std::sync::Arc;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Error)]
+#[derive(Debug, Error)]
 pub enum EnclaveError {
     #[error("This leader {id} is not in the enclave")]
     NotInEnclave { id: LeaderId },
+    #[error("The leadership cannot be evaluated: {source}")]
+    Evaluation {
+        #[source]
+        source: SigningError,
+    },
+    #[error("The leader {id} cannot sign: {source}")]
+    Signing {
+        id: LeaderId,
+        #[source]
+        source: SigningError,
+    },
 }
 
 /// represent the client side of an enclave. From there we will query the
@@ -21,19 +32,16 @@ pub enum EnclaveError {
 ///
 #[derive(Clone)]
 pub struct Enclave {
-    /// TODO: we will need to remove this to instead query data to an
-    /// external service (running in the secure enclave). For now we
-    /// hold on the `SecureEnclave` . But it will need to be separated
-    /// when we have the necessary crypto done.
-    inner: Arc<SecureEnclave>,
+    /// the enclave holding the leaders' keys, either in the node process or
+    /// in a remote signer
+    inner: Arc<dyn EnclaveBackend>,
 }
 
 impl Enclave {
-    /// create a new enclave structure. This will need to change in the future
-    /// with a parameter to contact the secure enclave instead of the dummy type.
-    pub fn new(secure_enclave: SecureEnclave) -> Self {
+    /// create a new enclave structure querying the given backend
+    pub fn new<E: EnclaveBackend + 'static>(backend: E) -> Self {
         Enclave {
-            inner: Arc::new(secure_enclave),
+            inner: Arc::new(backend),
         }
     }
 
@@ -48,10 +56,10 @@ impl Enclave {
         slot_start: u32,
         nb_slots: u32,
     ) -> Result<Vec<LeaderEvent>, EnclaveError> {
-        Ok(self
-            .inner
+        self.inner
             .leadership_evaluate(&leadership, slot_start, nb_slots)
-            .await)
+            .await
+            .map_err(|source| EnclaveError::Evaluation { source })
     }
 
     /// ask the leader associated to the `LeaderEvent` to finalize the given
@@ -72,7 +80,8 @@ impl Enclave {
     }
 
     /// ask the leader associated to the `LeaderEvent` to finalize the given
-    /// block by providing the proof.
+    /// block by providing the proof. The KES key of the leader is evolved
+    /// to the given period before signing.
     ///
     /// TODO: for now we are querying the whole with the block builder but on the long
     ///       run we will only need the block signing data.
@@ -80,15 +89,25 @@ impl Enclave {
         &self,
         block_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
+        kes_period: u32,
     ) -> Result<HeaderGenesisPraos, EnclaveError> {
-        if let Some(block) = self
+        match self
             .inner
-            .create_header_genesis_praos(block_builder, id)
+            .create_header_genesis_praos(block_builder, id, kes_period)
             .await
         {
-            Ok(block)
-        } else {
-            Err(EnclaveError::NotInEnclave { id })
+            Ok(Some(block)) => Ok(block),
+            Ok(None) => Err(EnclaveError::NotInEnclave { id }),
+            Err(source) => Err(EnclaveError::Signing { id, source }),
+        }
+    }
+
+    /// ask the enclave for the periods of the KES key of the given leader
+    pub async fn query_kes_periods(&self, id: LeaderId) -> Result<KesPeriods, EnclaveError> {
+        match self.inner.kes_periods(id).await {
+            Ok(Some(periods)) => Ok(periods),
+            Ok(None) => Err(EnclaveError::NotInEnclave { id }),
+            Err(source) => Err(EnclaveError::Signing { id, source }),
         }
     }
 }
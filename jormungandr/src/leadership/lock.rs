@@ -0,0 +1,239 @@
+//! Leader lock shared by the nodes running the same leaders.
+//!
+//! Running the same leader on several nodes for redundancy would make each
+//! of them create a block for the slots the leader is elected for. When a
+//! leader lock is configured, the nodes race to acquire the lock of a slot
+//! before signing its block and only the node holding the lock signs.
+//!
+//! Two backends are available: lock files created in a directory of a
+//! shared filesystem, and the TCP lock service `jormungandr-lock`. The
+//! locks are never released: a slot is only signed once.
+
+use crate::blockcfg::LeaderOutput;
+use crate::leadership::LeaderEvent;
+use async_trait::async_trait;
+use jormungandr_lib::interfaces;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    task::{spawn_blocking, JoinError},
+    time::timeout,
+};
+
+/// the lock files older than this are removed
+const LOCK_FILE_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+/// maximum time to get an answer from the lock service
+const LOCK_SERVICE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum LeaderLockConfig {
+    /// lock files created in the given directory, on a filesystem shared
+    /// by the nodes
+    File(PathBuf),
+    /// the `jormungandr-lock` service listening on the given address, the
+    /// requests are authenticated with the secret of the given file
+    Tcp {
+        address: SocketAddr,
+        secret_file: PathBuf,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("cannot read the secret of the lock service")]
+    Secret(#[source] io::Error),
+    #[error("the secret file of the lock service is empty")]
+    EmptySecret,
+    #[error("cannot create the lock file")]
+    File(#[source] io::Error),
+    #[error("the lock file task failed")]
+    Task(#[from] JoinError),
+    #[error("cannot communicate with the lock service")]
+    Service(#[source] io::Error),
+    #[error("the lock service did not answer in time")]
+    Timeout,
+    #[error("unexpected answer from the lock service: {0}")]
+    UnexpectedAnswer(String),
+}
+
+#[async_trait]
+pub trait LeaderLock: Send + Sync {
+    /// Try to acquire the lock of a slot. Returns `false` if the lock is
+    /// already held by another node.
+    async fn try_acquire(&self, key: &str) -> Result<bool, LockError>;
+}
+
+pub fn new_leader_lock(config: &LeaderLockConfig) -> Result<Arc<dyn LeaderLock>, LockError> {
+    match config {
+        LeaderLockConfig::File(directory) => Ok(Arc::new(FileLock {
+            directory: directory.clone(),
+        })),
+        LeaderLockConfig::Tcp {
+            address,
+            secret_file,
+        } => {
+            let secret = fs::read_to_string(secret_file).map_err(LockError::Secret)?;
+            let secret = secret.trim_end_matches(&['\r', '\n'][..]);
+            if secret.is_empty() {
+                return Err(LockError::EmptySecret);
+            }
+            Ok(Arc::new(TcpLock {
+                address: *address,
+                secret: secret.as_bytes().to_vec(),
+            }))
+        }
+    }
+}
+
+/// The key of the lock of the slot of the given event, identifying the
+/// leader the same way on all the nodes. `None` if the event does not need
+/// to be signed.
+pub fn slot_lock_key(event: &LeaderEvent) -> Option<String> {
+    let leader = match &event.output {
+        LeaderOutput::None => return None,
+        LeaderOutput::Bft(leader_id) => leader_id.as_public_key().to_string(),
+        LeaderOutput::GenesisPraos(node_id, _) => node_id.to_string(),
+    };
+    Some(interfaces::slot_lock_key(
+        event.date.epoch,
+        event.date.slot_id,
+        &leader,
+    ))
+}
+
+struct FileLock {
+    directory: PathBuf,
+}
+
+#[async_trait]
+impl LeaderLock for FileLock {
+    async fn try_acquire(&self, key: &str) -> Result<bool, LockError> {
+        let directory = self.directory.clone();
+        let path = directory.join(key);
+        spawn_blocking(move || {
+            // creating a new file is atomic, only one node can succeed
+            let acquired = match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => true,
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => false,
+                Err(error) => return Err(LockError::File(error)),
+            };
+            if acquired {
+                remove_old_lock_files(&directory);
+            }
+            Ok(acquired)
+        })
+        .await?
+    }
+}
+
+/// best effort removal of the lock files of the past slots
+fn remove_old_lock_files(directory: &Path) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let now = SystemTime::now();
+    for entry in entries.filter_map(Result::ok) {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map_or(false, |age| age > LOCK_FILE_RETENTION);
+        if expired {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+struct TcpLock {
+    address: SocketAddr,
+    secret: Vec<u8>,
+}
+
+impl TcpLock {
+    async fn request(&self, key: &str) -> Result<String, io::Error> {
+        let mut stream = TcpStream::connect(self.address).await?;
+        stream
+            .write_all(
+                format!(
+                    "acquire {} {}\n",
+                    key,
+                    interfaces::lock_request_mac(&self.secret, key)
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).await?;
+        Ok(answer)
+    }
+}
+
+#[async_trait]
+impl LeaderLock for TcpLock {
+    async fn try_acquire(&self, key: &str) -> Result<bool, LockError> {
+        let answer = timeout(LOCK_SERVICE_TIMEOUT, self.request(key))
+            .await
+            .map_err(|_| LockError::Timeout)?
+            .map_err(LockError::Service)?;
+        match answer.trim() {
+            "granted" => Ok(true),
+            "denied" => Ok(false),
+            answer => Err(LockError::UnexpectedAnswer(answer.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::{OsRng, RngCore};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("leader-lock-{:016x}", OsRng.next_u64()));
+            fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn file_lock_is_acquired_once() {
+        let dir = TempDir::new();
+        let lock = new_leader_lock(&LeaderLockConfig::File(dir.0.clone())).unwrap();
+        assert!(lock.try_acquire("slot-1").await.unwrap());
+        assert!(!lock.try_acquire("slot-1").await.unwrap());
+        assert!(lock.try_acquire("slot-2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn file_locks_are_shared_through_the_directory() {
+        let dir = TempDir::new();
+        let config = LeaderLockConfig::File(dir.0.clone());
+        let first = new_leader_lock(&config).unwrap();
+        let second = new_leader_lock(&config).unwrap();
+        assert!(first.try_acquire("slot-1").await.unwrap());
+        assert!(!second.try_acquire("slot-1").await.unwrap());
+    }
+}
@@ -57,10 +57,14 @@
 //! we don't duplicate the work everywhere.
 //!
 
+mod dry_run;
 mod enclave;
+mod lock;
 mod logs;
 mod process;
 
+pub use self::dry_run::{dry_run, CandidateBlock, DryRunError, DryRunReport};
 pub use self::enclave::{Enclave, EnclaveError, LeaderEvent};
+pub use self::lock::{new_leader_lock, LeaderLock, LeaderLockConfig, LockError};
 pub use self::logs::{LeadershipLogHandle, Logs};
 pub use self::process::Module;
@@ -1,12 +1,14 @@
 use crate::{
     blockcfg::{
-        Block, BlockDate, BlockVersion, Contents, HeaderBuilderNew, LeaderOutput, Leadership,
-        Ledger, LedgerParameters,
+        Block, BlockDate, BlockVersion, ChainLength, Contents, HeaderBuilderNew, HeaderHash,
+        LeaderOutput, Leadership, Ledger, LedgerParameters,
     },
     blockchain::{new_epoch_leadership_from, Ref, Tip},
+    fragment::selection::FragmentSelectionAlgorithmParams,
     intercom::{unary_reply, BlockMsg, Error as IntercomError, TransactionMsg},
     leadership::{
         enclave::{Enclave, EnclaveError, LeaderEvent},
+        lock::{slot_lock_key, LeaderLock},
         LeadershipLogHandle, Logs,
     },
     utils::{async_msg::MessageBox, task::TokioServiceInfo},
@@ -21,7 +23,11 @@ use jormungandr_lib::{
     time::SystemTime,
 };
 use slog::Logger;
-use std::{collections::VecDeque, sync::Arc, time::Instant};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use tokio::time::{delay_until, timeout_at, Instant as TokioInstant};
 
@@ -72,6 +78,8 @@ pub struct Module {
     pool: MessageBox<TransactionMsg>,
     enclave: Enclave,
     block_message: MessageBox<BlockMsg>,
+    selection_alg: FragmentSelectionAlgorithmParams,
+    leader_lock: Option<Arc<dyn LeaderLock>>,
 }
 
 impl Module {
@@ -82,6 +90,8 @@ impl Module {
         pool: MessageBox<TransactionMsg>,
         enclave: Enclave,
         block_message: MessageBox<BlockMsg>,
+        selection_alg: FragmentSelectionAlgorithmParams,
+        leader_lock: Option<Arc<dyn LeaderLock>>,
     ) -> Result<Self, LeadershipError> {
         let tip_ref = tip.get_ref().await;
 
@@ -94,6 +104,8 @@ impl Module {
             pool,
             enclave,
             block_message,
+            selection_alg,
+            leader_lock,
         })
     }
 
@@ -212,6 +224,10 @@ impl Module {
         }
     }
 
+    fn kes_period(&self, event: &LeaderEvent) -> u32 {
+        slot_kes_period(&self.tip_ref.ledger(), self.event_slot_time(event))
+    }
+
     fn slot_instant(&self, epoch: Epoch, slot: EpochSlotOffset) -> Option<Instant> {
         let slot_time = self.slot_time(epoch, slot);
 
@@ -377,6 +393,39 @@ impl Module {
         }.map(|()| self)
     }
 
+    /// Acquire the lock of the slot of the event if a leader lock is
+    /// configured. Returns `false` if the block must not be signed, because
+    /// another node holds the lock or the lock cannot be acquired.
+    async fn acquire_slot_lock(
+        &self,
+        event: &LeaderEvent,
+        event_logs: &LeadershipLogHandle,
+        logger: &Logger,
+    ) -> bool {
+        let (lock, key) = match (&self.leader_lock, slot_lock_key(event)) {
+            (Some(lock), Some(key)) => (lock, key),
+            _ => return true,
+        };
+        let reason = match lock.try_acquire(&key).await {
+            Ok(true) => return true,
+            Ok(false) => {
+                info!(logger, "the slot is locked by another node, not signing the block"; "lock" => &key);
+                "The slot is locked by another node".to_owned()
+            }
+            Err(e) => {
+                error!(logger, "cannot acquire the lock of the slot, not signing the block";
+                    "lock" => &key,
+                    "reason" => %e,
+                );
+                format!("Cannot acquire the lock of the slot: {}", e)
+            }
+        };
+        event_logs
+            .set_status(LeadershipLogStatus::Rejected { reason })
+            .await;
+        false
+    }
+
     async fn action_run_entry_build_block(
         &self,
         entry: Entry,
@@ -422,99 +471,75 @@ impl Module {
             return Ok(());
         };
 
-        let contents =
-            prepare_block(pool, event.date, ledger, ledger_parameters, logger.clone()).await?;
-
-        let event_logs_error = event_logs.clone();
-        let signing = {
-            let ver = match event.output {
-                LeaderOutput::None => BlockVersion::Genesis,
-                LeaderOutput::Bft(_) => BlockVersion::Ed25519Signed,
-                LeaderOutput::GenesisPraos(..) => BlockVersion::KesVrfproof,
-            };
-
-            let hdr_builder = HeaderBuilderNew::new(ver, &contents)
-                .set_parent(&parent_id, chain_length)
-                .set_date(event.date);
-
-            match event.output {
-                LeaderOutput::None => {
-                    let header = hdr_builder
-                        .into_unsigned_header()
-                        .expect("Valid Header Builder")
-                        .generalize();
-                    Ok(Some(Block { header, contents }))
-                }
-                LeaderOutput::Bft(leader_id) => {
-                    let final_builder = hdr_builder
-                        .into_bft_builder()
-                        .expect("Valid Header Builder")
-                        .set_consensus_data(&leader_id);
-                    enclave
-                        .query_header_bft_finalize(final_builder, event.id)
-                        .map_ok(|h| {
-                            Some(Block {
-                                header: h.generalize(),
-                                contents,
-                            })
-                        })
-                        .or_else(|e| async move {
-                            event_logs_error
-                                .set_status(LeadershipLogStatus::Rejected {
-                                    reason: format!("Cannot sign the block: {}", e),
-                                })
-                                .await;
-                            Ok(None)
-                        })
-                        .await
-                }
-                LeaderOutput::GenesisPraos(node_id, vrfproof) => {
-                    let final_builder = hdr_builder
-                        .into_genesis_praos_builder()
-                        .expect("Valid Header Builder")
-                        .set_consensus_data(&node_id, &vrfproof.into());
-                    enclave
-                        .query_header_genesis_praos_finalize(final_builder, event.id)
-                        .map_ok(|h| {
-                            Some(Block {
-                                header: h.generalize(),
-                                contents,
-                            })
-                        })
-                        .or_else(|e| async move {
-                            event_logs_error
-                                .set_status(LeadershipLogStatus::Rejected {
-                                    reason: format!("Cannot sign the block: {}", e),
-                                })
-                                .await;
-                            Ok(None)
-                        })
-                        .await
+        // the selection removes the selected fragments from the mempool, the
+        // slot lock is acquired first so a standby node keeps its fragments
+        if !self.acquire_slot_lock(&event, &event_logs, &logger).await {
+            return Ok(());
+        }
+
+        let contents = prepare_block(
+            pool,
+            event.date,
+            ledger,
+            ledger_parameters,
+            self.selection_alg,
+            logger.clone(),
+        )
+        .await?;
+
+        let kes_period = self.kes_period(&event);
+        let signing = sign_block(
+            &enclave,
+            &event,
+            parent_id,
+            chain_length,
+            contents,
+            kes_period,
+        )
+        .await;
+
+        if let LeaderOutput::GenesisPraos(..) = event.output {
+            if let Ok(kes_periods) = enclave.query_kes_periods(event.id).await {
+                debug!(logger, "KES key evolved";
+                    "current_period" => kes_periods.current,
+                    "remaining_periods" => kes_periods.remaining,
+                );
+                if kes_periods.remaining == 0 {
+                    warn!(logger, "the KES key is at its last period, a new KES key needs to be registered for the stake pool");
                 }
             }
-        };
+        }
 
         match signing {
-            Ok(maybe_block) => {
-                if let Some(block) = maybe_block {
-                    let id = block.header.hash();
-                    let parent = block.header.block_parent_hash();
-                    let chain_length: u32 = block.header.chain_length().into();
-                    sender
-                        .send(BlockMsg::LeadershipBlock(block))
-                        .map_err(|_send_error| LeadershipError::CannotSendLeadershipBlock)
-                        .await?;
-                    event_logs
-                        .set_status(LeadershipLogStatus::Block {
-                            block: id.into(),
-                            parent: parent.into(),
-                            chain_length,
-                        })
-                        .await;
-                };
+            Ok(block) => {
+                let id = block.header.hash();
+                let parent = block.header.block_parent_hash();
+                let chain_length: u32 = block.header.chain_length().into();
+                sender
+                    .send(BlockMsg::LeadershipBlock(block))
+                    .map_err(|_send_error| LeadershipError::CannotSendLeadershipBlock)
+                    .await?;
+                event_logs
+                    .set_status(LeadershipLogStatus::Block {
+                        block: id.into(),
+                        parent: parent.into(),
+                        chain_length,
+                    })
+                    .await;
+                Ok(())
+            }
+            Err(e) => {
+                error!(logger, "cannot sign the block";
+                    "kes_period" => kes_period,
+                    "reason" => %e,
+                );
+                event_logs
+                    .set_status(LeadershipLogStatus::Rejected {
+                        reason: format!("Cannot sign the block: {}", e),
+                    })
+                    .await;
                 Ok(())
             }
-            Err(e) => Err(e),
         }
     }
 
@@ -631,22 +656,85 @@ impl Schedule {
     }
 }
 
+/// the KES period of a slot: the number of KES update periods elapsed
+/// between the start of the blockchain and the slot's time
+pub(super) fn slot_kes_period(ledger: &Ledger, slot_time: SystemTime) -> u32 {
+    let static_params = ledger.get_static_parameters();
+    let block0_time =
+        std::time::UNIX_EPOCH + Duration::from_secs(static_params.block0_start_time.0);
+    let elapsed = slot_time
+        .as_ref()
+        .duration_since(block0_time)
+        .unwrap_or_default();
+    let kes_update_speed = u64::from(static_params.kes_update_speed.max(1));
+    (elapsed.as_secs() / kes_update_speed) as u32
+}
+
+/// build the header of the block of the given event on top of the given
+/// parent and have it signed by the enclave
+pub(super) async fn sign_block(
+    enclave: &Enclave,
+    event: &LeaderEvent,
+    parent_id: HeaderHash,
+    chain_length: ChainLength,
+    contents: Contents,
+    kes_period: u32,
+) -> Result<Block, EnclaveError> {
+    let ver = match event.output {
+        LeaderOutput::None => BlockVersion::Genesis,
+        LeaderOutput::Bft(_) => BlockVersion::Ed25519Signed,
+        LeaderOutput::GenesisPraos(..) => BlockVersion::KesVrfproof,
+    };
+
+    let hdr_builder = HeaderBuilderNew::new(ver, &contents)
+        .set_parent(&parent_id, chain_length)
+        .set_date(event.date);
+
+    let header = match &event.output {
+        LeaderOutput::None => hdr_builder
+            .into_unsigned_header()
+            .expect("Valid Header Builder")
+            .generalize(),
+        LeaderOutput::Bft(leader_id) => {
+            let final_builder = hdr_builder
+                .into_bft_builder()
+                .expect("Valid Header Builder")
+                .set_consensus_data(leader_id);
+            enclave
+                .query_header_bft_finalize(final_builder, event.id)
+                .await?
+                .generalize()
+        }
+        LeaderOutput::GenesisPraos(node_id, vrfproof) => {
+            let final_builder = hdr_builder
+                .into_genesis_praos_builder()
+                .expect("Valid Header Builder")
+                .set_consensus_data(node_id, &vrfproof.clone().into());
+            enclave
+                .query_header_genesis_praos_finalize(final_builder, event.id, kes_period)
+                .await?
+                .generalize()
+        }
+    };
+
+    Ok(Block { header, contents })
+}
+
 async fn prepare_block(
     mut fragment_pool: MessageBox<TransactionMsg>,
     block_date: BlockDate,
     ledger: Arc<Ledger>,
     epoch_parameters: Arc<LedgerParameters>,
+    selection_alg: FragmentSelectionAlgorithmParams,
     logger: Logger,
 ) -> Result<Contents, LeadershipError> {
-    use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-
     let (reply_handle, reply_future) = unary_reply(logger.clone());
 
     let msg = TransactionMsg::SelectTransactions {
         ledger: ledger.as_ref().clone(),
         block_date,
         ledger_params: epoch_parameters.as_ref().clone(),
-        selection_alg: FragmentSelectionAlgorithmParams::OldestFirst,
+        selection_alg,
         reply_handle,
     };
 
@@ -27,12 +27,13 @@ use crate::{
     settings::start::Settings,
     utils::{async_msg, task::Services},
 };
-use futures::{executor::block_on, future::TryFutureExt};
+use futures::{channel::oneshot, executor::block_on, future::TryFutureExt};
 use jormungandr_lib::interfaces::NodeState;
-use settings::{start::RawSettings, CommandLine};
+use settings::{start::RawSettings, ArchiveCommand, Command, CommandLine, StorageCommand};
 use slog::Logger;
 use tokio::signal::ctrl_c;
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -58,7 +59,22 @@ pub mod utils;
 use stats_counter::StatsCounter;
 
 fn start() -> Result<(), start_up::Error> {
-    let initialized_node = initialize_node()?;
+    let mut initialized_node = initialize_node()?;
+
+    if let Some(command) = initialized_node.settings.command.take() {
+        return match command {
+            Command::DryRun { epoch } => dry_run(initialized_node, epoch),
+            Command::Export(arguments) => {
+                run_archive_command(initialized_node, ArchiveCommand::Export(arguments))
+            }
+            Command::Import(arguments) => {
+                run_archive_command(initialized_node, ArchiveCommand::Import(arguments))
+            }
+            Command::Storage(StorageCommand::Check { repair }) => {
+                check_storage(initialized_node, repair)
+            }
+        };
+    }
 
     let bootstrapped_node = bootstrap(initialized_node)?;
 
@@ -82,6 +98,7 @@ const NETWORK_TASK_QUEUE_LEN: usize = 32;
 const EXPLORER_TASK_QUEUE_LEN: usize = 32;
 const CLIENT_TASK_QUEUE_LEN: usize = 32;
 const BOOTSTRAP_RETRY_WAIT: Duration = Duration::from_secs(5);
+const BLOCKCHAIN_CACHE_CAPACITY: usize = 102_400;
 
 fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::Error> {
     if let Some(context) = bootstrapped_node.rest_context.as_ref() {
@@ -114,16 +131,46 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
 
     let stats_counter = StatsCounter::default();
 
+    // the fragment task saves the mempool before the node shuts down
+    let (fragment_shutdown, fragment_shutdown_receiver) = oneshot::channel();
+
     {
         let stats_counter = stats_counter.clone();
+        let persistence = if bootstrapped_node.settings.mempool.persistent {
+            match &bootstrapped_node.settings.storage {
+                Some(storage) => Some(fragment::Persistence::new(storage)),
+                None => {
+                    warn!(
+                        bootstrapped_node.logger,
+                        "mempool persistence requires a storage directory, the mempool will not be saved"
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
         let process = fragment::Process::new(
             bootstrapped_node.settings.mempool.pool_max_entries.into(),
             bootstrapped_node.settings.mempool.log_max_entries.into(),
+            bootstrapped_node
+                .settings
+                .mempool
+                .fee_replacement_margin
+                .into(),
+            bootstrapped_node.settings.mempool.fragment_ttl.into(),
             network_msgbox.clone(),
+            blockchain_tip.clone(),
+            persistence,
         );
 
         services.spawn_try_future("fragment", move |info| {
-            process.start(info, stats_counter, fragment_queue)
+            process.start(
+                info,
+                stats_counter,
+                fragment_queue,
+                fragment_shutdown_receiver,
+            )
         });
     };
 
@@ -215,27 +262,25 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         });
     }
 
-    let leader_secrets: Result<Vec<Leader>, start_up::Error> = bootstrapped_node
-        .settings
-        .secrets
-        .iter()
-        .map(|secret_path| {
-            let secret = secure::NodeSecret::load_from_file(secret_path.as_path())?;
-            Ok(Leader {
-                bft_leader: secret.bft(),
-                genesis_leader: secret.genesis(),
-            })
-        })
-        .collect();
-    let leader_secrets = leader_secrets?;
+    let remote_signer = bootstrapped_node.settings.leadership.remote_signer.clone();
+    let leader_secrets = load_leader_secrets(&bootstrapped_node.settings)?;
     let enclave = block_on(Enclave::from_vec(leader_secrets));
+    let leadership_enclave = leadership_enclave(enclave.clone(), remote_signer);
 
     {
         let leadership_logs = leadership_logs.clone();
         let block_msgbox = block_msgbox.clone();
         let blockchain_tip = blockchain_tip.clone();
-        let enclave = leadership::Enclave::new(enclave.clone());
+        let enclave = leadership_enclave.clone();
         let fragment_msgbox = fragment_msgbox.clone();
+        let selection_alg = bootstrapped_node.settings.leadership.fragment_selection;
+        let leader_lock = bootstrapped_node
+            .settings
+            .leadership
+            .lock
+            .as_ref()
+            .map(leadership::new_leader_lock)
+            .transpose()?;
 
         services.spawn_try_future("leadership", move |info| {
             let fut = leadership::Module::new(
@@ -245,6 +290,8 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 fragment_msgbox,
                 enclave,
                 block_msgbox,
+                selection_alg,
+                leader_lock,
             )
             .and_then(|module| module.run())
             .map_err(|e| {
@@ -262,6 +309,8 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
             transaction_task: fragment_msgbox,
             leadership_logs,
             enclave,
+            leadership_enclave,
+            leadership_schedules: rest::LeadershipSchedules::new(),
             network_state,
             explorer: explorer.as_ref().map(|(_msg_box, context)| context.clone()),
         };
@@ -288,7 +337,23 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         });
     }
 
-    services.spawn_try_future("sigint_listener", move |_info| ctrl_c().map_err(|_| ()));
+    if let Some(storage) = &bootstrapped_node.settings.storage {
+        let blockchain_tip = blockchain_tip.clone();
+        let directory = storage.clone();
+        services.spawn_future("ledger_checkpoints", move |info| {
+            blockchain::take_ledger_checkpoints(info, blockchain_tip, directory)
+        });
+    }
+
+    services.spawn_try_future("signal_listener", move |info| async move {
+        shutdown_signal().await?;
+        info!(info.logger(), "shutdown requested");
+        let (saved, saved_receiver) = oneshot::channel();
+        if fragment_shutdown.send(saved).is_ok() {
+            let _ = saved_receiver.await;
+        }
+        Ok(())
+    });
 
     match services.wait_any_finished() {
         Err(err) => {
@@ -313,6 +378,25 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
     }
 }
 
+/// resolves on SIGINT, or on SIGTERM on Unix
+async fn shutdown_signal() -> Result<(), ()> {
+    #[cfg(unix)]
+    {
+        use futures::future::{select, Either};
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).map_err(|_| ())?;
+        match select(Box::pin(ctrl_c()), Box::pin(terminate.recv())).await {
+            Either::Left((result, _)) => result.map_err(|_| ()),
+            Either::Right(_) => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        ctrl_c().await.map_err(|_| ())
+    }
+}
+
 /// # Bootstrap phase
 ///
 /// done at every startup: we need to bootstrap from whatever local state (including nothing)
@@ -397,12 +481,10 @@ async fn bootstrap_internal(
 
     let block0_explorer = block0.clone();
 
-    let cache_capacity = 102_400;
-
     let (blockchain, blockchain_tip) = start_up::load_blockchain(
         block0,
         storage,
-        cache_capacity,
+        BLOCKCHAIN_CACHE_CAPACITY,
         settings.rewards_report_all,
         &logger,
     )
@@ -594,6 +676,174 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
     })
 }
 
+/// # Dry run
+///
+/// evaluate the leadership of the node's secrets against the blockchain in
+/// the storage and build a candidate block, without bootstrapping from the
+/// network nor starting the services of the node. The report is printed on
+/// the standard output.
+fn dry_run(initialized_node: InitializedNode, epoch: Option<u32>) -> Result<(), start_up::Error> {
+    let InitializedNode {
+        settings,
+        block0,
+        storage,
+        mut services,
+        ..
+    } = initialized_node;
+
+    let leader_secrets = load_leader_secrets(&settings)?;
+    let remote_signer = settings.leadership.remote_signer.clone();
+    let rewards_report_all = settings.rewards_report_all;
+    // the candidate block is not signed by the remote signer, which would
+    // then refuse to sign the actual block of the slot
+    let signs_remotely = cfg!(unix) && remote_signer.is_some();
+
+    let report = services.block_on_task("dry_run", |info| async move {
+        let (blockchain, blockchain_tip) = start_up::load_blockchain(
+            block0,
+            storage,
+            BLOCKCHAIN_CACHE_CAPACITY,
+            rewards_report_all,
+            info.logger(),
+        )
+        .await?;
+        let enclave = leadership_enclave(Enclave::from_vec(leader_secrets).await, remote_signer);
+        let tip = blockchain_tip.get_ref().await;
+        leadership::dry_run(&blockchain, tip, &enclave, epoch, signs_remotely)
+            .await
+            .map_err(start_up::Error::from)
+    })?;
+
+    let report = serde_yaml::to_string(&report).map_err(start_up::Error::DryRunReport)?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// # Archives
+///
+/// export the blocks of the blockchain in the storage to an archive file, or
+/// import the blocks of an archive file, without bootstrapping from the
+/// network nor starting the services of the node.
+fn run_archive_command(
+    initialized_node: InitializedNode,
+    command: ArchiveCommand,
+) -> Result<(), start_up::Error> {
+    let InitializedNode {
+        settings,
+        block0,
+        storage,
+        mut services,
+        ..
+    } = initialized_node;
+
+    let rewards_report_all = settings.rewards_report_all;
+
+    services.block_on_task("archive", |info| async move {
+        let logger = info.logger();
+        let (mut blockchain, blockchain_tip) = start_up::load_blockchain(
+            block0,
+            storage,
+            BLOCKCHAIN_CACHE_CAPACITY,
+            rewards_report_all,
+            logger,
+        )
+        .await?;
+
+        match command {
+            ArchiveCommand::Export(export) => {
+                let tip = blockchain_tip.get_ref().await;
+                let exported =
+                    blockchain::export(&blockchain, tip, export.from, export.to, &export.file)
+                        .await?;
+                info!(
+                    logger,
+                    "exported {} blocks to {}",
+                    exported,
+                    export.file.display()
+                );
+            }
+            ArchiveCommand::Import(import) => {
+                let imported =
+                    blockchain::import(&mut blockchain, blockchain_tip, &import.file, logger)
+                        .await?;
+                info!(
+                    logger,
+                    "imported {} blocks from {}",
+                    imported,
+                    import.file.display()
+                );
+            }
+        }
+        Ok(())
+    })
+}
+
+/// # Storage check
+///
+/// check the integrity of the blocks of the storage, and repair it if asked
+/// to, without loading the blockchain. The report is printed on the standard
+/// output.
+fn check_storage(initialized_node: InitializedNode, repair: bool) -> Result<(), start_up::Error> {
+    let InitializedNode {
+        block0,
+        storage,
+        mut services,
+        ..
+    } = initialized_node;
+
+    let report = services.block_on_task("storage_check", |_info| async move {
+        blockchain::check_storage(&storage, block0.header.hash(), repair).await
+    })?;
+
+    let issues = report.issues.len();
+    let repaired = report.repair.is_some();
+    let report = serde_yaml::to_string(&report).map_err(start_up::Error::StorageCheckReport)?;
+    println!("{}", report);
+
+    if issues > 0 && !repaired {
+        return Err(start_up::Error::StorageCorrupted { issues });
+    }
+    Ok(())
+}
+
+fn load_leader_secrets(settings: &Settings) -> Result<Vec<Leader>, start_up::Error> {
+    let remote_signer = &settings.leadership.remote_signer;
+    let mut passphrase = secure::Passphrase::new(settings.secret_passphrase_file.clone());
+    settings
+        .secrets
+        .iter()
+        .map(|secret_path| {
+            let secret =
+                secure::NodeSecret::load_from_file(secret_path.as_path(), &mut passphrase)?;
+            // the remote signer is only supported on Unix
+            if !secret.has_kes_key() && !(cfg!(unix) && remote_signer.is_some()) {
+                return Err(start_up::Error::MissingKesKey {
+                    path: secret_path.clone(),
+                });
+            }
+            Ok(Leader {
+                bft_leader: secret.bft(),
+                genesis_leader: secret.genesis(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn leadership_enclave(enclave: Enclave, remote_signer: Option<PathBuf>) -> leadership::Enclave {
+    match remote_signer {
+        Some(socket_path) => {
+            leadership::Enclave::new(secure::remote::RemoteEnclave::new(enclave, socket_path))
+        }
+        None => leadership::Enclave::new(enclave),
+    }
+}
+
+#[cfg(not(unix))]
+fn leadership_enclave(enclave: Enclave, _remote_signer: Option<PathBuf>) -> leadership::Enclave {
+    leadership::Enclave::new(enclave)
+}
+
 fn main() {
     use std::error::Error;
 
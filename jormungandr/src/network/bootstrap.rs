@@ -1,22 +1,29 @@
-use super::grpc;
-use crate::blockcfg::{Block, HeaderDesc, HeaderHash};
-use crate::blockchain::{self, Blockchain, Error as BlockchainError, PreCheckedHeader, Ref, Tip};
+use super::{convert::Decode, grpc, tls::TlsContext};
+use crate::blockcfg::{Block, Header, HeaderDesc, HeaderHash};
+use crate::blockchain::{
+    self, Blockchain, Error as BlockchainError, HeaderChainVerifyError, PreCheckedHeader, Ref, Tip,
+};
 use crate::settings::start::network::Peer;
 use chain_core::property::{Deserialize, HasHeader};
 use chain_network::data as net_data;
 use chain_network::error::Error as NetworkError;
 use futures::{
     channel::oneshot::Receiver,
-    future::{Either, Shared},
+    future::{self, Either, Shared},
     prelude::*,
-    stream,
-    task::Poll,
+    stream::FuturesOrdered,
 };
 use slog::Logger;
+use tokio::time::timeout;
 
+use std::convert::TryFrom;
 use std::fmt::Debug;
-use std::pin::Pin;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -30,8 +37,26 @@ pub enum Error {
     PullRequestFailed(#[source] NetworkError),
     #[error("bootstrap pull stream failed")]
     PullStreamFailed(#[source] NetworkError),
+    #[error("tip request failed")]
+    TipRequestFailed(#[source] NetworkError),
+    #[error("block request failed")]
+    GetBlocksFailed(#[source] NetworkError),
     #[error("decoding of a block failed")]
     BlockDecodingFailed(#[source] <Block as Deserialize>::Error),
+    #[error("decoding of a block header failed")]
+    HeaderDecodingFailed(#[source] NetworkError),
+    #[error("the parent hash field of received block header {0} does not match the hash of the preceding header")]
+    BrokenHeaderChain(HeaderHash),
+    #[error("block headers do not form a valid chain")]
+    HeaderChainVerificationFailed(#[source] HeaderChainVerifyError),
+    #[error("block {0} was not sent by the peer")]
+    MissingBlock(HeaderHash),
+    #[error("received block {0} was not requested")]
+    UnexpectedBlock(HeaderHash),
+    #[error("none of the peers sent the blocks starting from {0}")]
+    BlocksNotAvailable(HeaderHash),
+    #[error("the block download task failed")]
+    DownloadTaskFailed(#[source] tokio::task::JoinError),
     #[error("block header check failed")]
     HeaderCheckFailed(#[source] BlockchainError),
     #[error(
@@ -52,13 +77,30 @@ pub enum Error {
 
 const MAX_BOOTSTRAP_PEERS: u32 = 32;
 
-pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<Peer>, Error> {
+/// the maximum number of peers the blocks are downloaded from
+pub const MAX_BLOCK_PEERS: usize = 8;
+
+/// the number of blocks requested at once from a peer, so that the block IDs
+/// of a request fit within a reasonable network path MTU
+const BLOCK_RANGE_SIZE: usize = 32;
+
+/// the number of ranges of blocks requested at the same time from each peer
+const RANGES_IN_FLIGHT_PER_PEER: usize = 4;
+
+/// the time a peer has to send a range of blocks before it is failed over
+const RANGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub async fn peers_from_trusted_peer(
+    peer: &Peer,
+    tls: Option<&TlsContext>,
+    logger: Logger,
+) -> Result<Vec<Peer>, Error> {
     info!(
         logger,
         "getting peers from bootstrap peer {}", peer.connection
     );
 
-    let mut client = grpc::connect(&peer).await.map_err(Error::Connect)?;
+    let mut client = grpc::connect(&peer, tls).await.map_err(Error::Connect)?;
     let peers = client
         .peers(MAX_BOOTSTRAP_PEERS)
         .await
@@ -74,43 +116,307 @@ pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<
     Ok(peers)
 }
 
-pub async fn bootstrap_from_peer(
-    peer: &Peer,
-    blockchain: Blockchain,
+/// Bootstraps the blockchain header first: the headers of the chain are
+/// pulled from `header_peer` and checked to form a chain starting from the
+/// storage, then the blocks are downloaded in ranges from all the
+/// `block_peers` in parallel and applied in order. A peer failing to send a
+/// range, or not sending it in time, is not asked for blocks anymore and
+/// the range is requested from the next peer.
+pub async fn bootstrap_from_peers(
+    header_peer: &Peer,
+    block_peers: &[&Peer],
+    tls: Option<&TlsContext>,
+    mut blockchain: Blockchain,
     tip: Tip,
-    bootstrap_stopper: Shared<Receiver<()>>,
+    mut bootstrap_stopper: Shared<Receiver<()>>,
     logger: Logger,
 ) -> Result<(), Error> {
-    use futures::future::select;
+    debug!(
+        logger,
+        "connecting to bootstrap peer {}", header_peer.connection
+    );
 
-    debug!(logger, "connecting to bootstrap peer {}", peer.connection);
+    let mut client = interruptible(grpc::connect(header_peer, tls), &mut bootstrap_stopper)
+        .await?
+        .map_err(Error::Connect)?;
+    let block_peers = interruptible(
+        connect_block_peers(header_peer, &client, block_peers, tls, &logger),
+        &mut bootstrap_stopper,
+    )
+    .await?;
+
+    let peer_tip = interruptible(client.tip(), &mut bootstrap_stopper)
+        .await?
+        .map_err(Error::TipRequestFailed)?
+        .decode()
+        .map_err(Error::HeaderDecodingFailed)?;
+    let checkpoints = blockchain.get_checkpoints(tip.branch()).await;
+    let checkpoints = net_data::block::try_ids_from_iter(checkpoints).unwrap();
+    let to = net_data::BlockId::try_from(peer_tip.hash().as_bytes()).unwrap();
 
-    let blockchain1 = blockchain.clone();
-    let tip1 = tip.clone();
-    let logger1 = logger.clone();
+    info!(
+        logger,
+        "pulling headers up to {} from {} peer(s) starting from checkpoints: {:?}",
+        peer_tip.description(),
+        block_peers.len(),
+        checkpoints
+    );
 
-    let stream_future = async move {
-        let mut client = grpc::connect(&peer).await.map_err(|e| Error::Connect(e))?;
+    let headers = interruptible(client.pull_headers(checkpoints, to), &mut bootstrap_stopper)
+        .await?
+        .map_err(Error::PullRequestFailed)?;
+    let mut header_chain = HeaderChain {
+        stream: headers,
+        blockchain: blockchain.clone(),
+        last_header: None,
+    };
 
-        let checkpoints = blockchain1.get_checkpoints(tip1.branch()).await;
-        let checkpoints = net_data::block::try_ids_from_iter(checkpoints).unwrap();
+    let mut maybe_parent_tip = None;
+    let result = download_blocks(
+        &mut header_chain,
+        block_peers,
+        &blockchain,
+        &mut maybe_parent_tip,
+        &mut bootstrap_stopper,
+        &logger,
+    )
+    .await;
+
+    match (result, maybe_parent_tip) {
+        (Ok(()), Some(parent_tip)) => {
+            blockchain::process_new_ref(&logger, &mut blockchain, tip, parent_tip)
+                .await
+                .map_err(Error::ChainSelectionFailed)
+        }
+        (Ok(()), None) => {
+            info!(logger, "no new blocks to bootstrap from");
+            Ok(())
+        }
+        (Err(err), Some(parent_tip)) => {
+            if let Err(err) =
+                blockchain::process_new_ref(&logger, &mut blockchain, tip, parent_tip).await
+            {
+                warn!(logger, "couldn't gracefully exit from failed netboot"; "error" => ?err);
+            }
+            Err(err)
+        }
+        (Err(err), None) => Err(err),
+    }
+}
 
-        info!(
-            logger1,
-            "pulling blocks starting from checkpoints: {:?}", checkpoints
-        );
+/// a peer the blocks are downloaded from
+struct BlockPeer {
+    address: SocketAddr,
+    client: grpc::Client,
+    /// set once the peer failed to send a range of blocks
+    failed: AtomicBool,
+}
 
-        client
-            .pull_blocks_to_tip(checkpoints)
-            .await
-            .map_err(|e| Error::PullRequestFailed(e))
-    };
+async fn connect_block_peers(
+    header_peer: &Peer,
+    header_client: &grpc::Client,
+    block_peers: &[&Peer],
+    tls: Option<&TlsContext>,
+    logger: &Logger,
+) -> Arc<Vec<BlockPeer>> {
+    let others = block_peers
+        .iter()
+        .filter(|peer| peer.connection != header_peer.connection)
+        .map(|peer| async move { (peer.connection, grpc::connect(peer, tls).await) });
+    let mut peers = vec![BlockPeer {
+        address: header_peer.connection,
+        client: header_client.clone(),
+        failed: AtomicBool::new(false),
+    }];
+    for (address, result) in future::join_all(others).await {
+        match result {
+            Ok(client) => peers.push(BlockPeer {
+                address,
+                client,
+                failed: AtomicBool::new(false),
+            }),
+            Err(e) => {
+                warn!(logger, "unable to reach peer to download blocks from"; "peer_addr" => %address, "reason" => %e);
+            }
+        }
+    }
+    Arc::new(peers)
+}
 
-    // process a signal from the stopper if it arrives before the stream is ready
-    match select(stream_future.boxed(), bootstrap_stopper).await {
-        Either::Left((stream_result, bootstrap_stopper)) => {
-            bootstrap_from_stream(blockchain, tip, stream_result?, bootstrap_stopper, logger).await
+/// checks the headers as they are received and splits the blocks to
+/// download in ranges
+struct HeaderChain<S> {
+    stream: S,
+    blockchain: Blockchain,
+    last_header: Option<Header>,
+}
+
+impl<S> HeaderChain<S>
+where
+    S: Stream<Item = Result<net_data::Header, NetworkError>> + Unpin,
+{
+    /// the hashes of the next range of blocks to download, empty once all
+    /// the headers are received
+    async fn next_range(&mut self) -> Result<Vec<HeaderHash>, Error> {
+        let mut range = Vec::with_capacity(BLOCK_RANGE_SIZE);
+        while range.len() < BLOCK_RANGE_SIZE {
+            let header = match self.stream.next().await {
+                Some(header) => header
+                    .map_err(Error::PullStreamFailed)?
+                    .decode()
+                    .map_err(Error::HeaderDecodingFailed)?,
+                None => break,
+            };
+            let header = match &self.last_header {
+                Some(parent) => {
+                    if header.block_parent_hash() != parent.hash() {
+                        return Err(Error::BrokenHeaderChain(header.hash()));
+                    }
+                    blockchain::pre_verify_link(&header, parent)
+                        .map_err(Error::HeaderChainVerificationFailed)?;
+                    header
+                }
+                // the chain must continue a block of the storage, the
+                // headers of the blocks already present are skipped
+                None => match self
+                    .blockchain
+                    .pre_check_header(header, false)
+                    .await
+                    .map_err(Error::HeaderCheckFailed)?
+                {
+                    PreCheckedHeader::AlreadyPresent { .. } => continue,
+                    PreCheckedHeader::MissingParent { header } => {
+                        return Err(Error::BlockMissingParent(header.hash()))
+                    }
+                    PreCheckedHeader::HeaderWithCache { header, .. } => header,
+                },
+            };
+            range.push(header.hash());
+            self.last_header = Some(header);
         }
+        Ok(range)
+    }
+}
+
+async fn download_blocks<S>(
+    header_chain: &mut HeaderChain<S>,
+    block_peers: Arc<Vec<BlockPeer>>,
+    blockchain: &Blockchain,
+    maybe_parent_tip: &mut Option<Arc<Ref>>,
+    bootstrap_stopper: &mut Shared<Receiver<()>>,
+    logger: &Logger,
+) -> Result<(), Error>
+where
+    S: Stream<Item = Result<net_data::Header, NetworkError>> + Unpin,
+{
+    const PROCESS_LOGGING_DISTANCE: u64 = 2500;
+
+    let max_ranges = block_peers.len() * RANGES_IN_FLIGHT_PER_PEER;
+    let mut bootstrap_info = BootstrapInfo::new();
+    let mut ranges = FuturesOrdered::new();
+    let mut headers_complete = false;
+    let mut next_peer = 0;
+
+    loop {
+        // keep every peer busy while the blocks are applied
+        while !headers_complete && ranges.len() < max_ranges {
+            let range = interruptible(header_chain.next_range(), bootstrap_stopper).await??;
+            if range.is_empty() {
+                headers_complete = true;
+            } else {
+                ranges.push(tokio::spawn(fetch_range(
+                    block_peers.clone(),
+                    next_peer,
+                    range,
+                    logger.clone(),
+                )));
+                next_peer = (next_peer + 1) % block_peers.len();
+            }
+        }
+
+        let blocks = match interruptible(ranges.next(), bootstrap_stopper).await? {
+            Some(result) => result.map_err(Error::DownloadTaskFailed)??,
+            None => return Ok(()),
+        };
+
+        for block in blocks {
+            bootstrap_info.append_block(&block);
+            if bootstrap_info.block_received % PROCESS_LOGGING_DISTANCE == 0 {
+                bootstrap_info.report(logger);
+            }
+            *maybe_parent_tip = Some(handle_block(blockchain, block, logger).await?);
+        }
+    }
+}
+
+/// download a range of blocks, starting with the peer `first_peer` and
+/// failing over to the next peers
+async fn fetch_range(
+    block_peers: Arc<Vec<BlockPeer>>,
+    first_peer: usize,
+    range: Vec<HeaderHash>,
+    logger: Logger,
+) -> Result<Vec<Block>, Error> {
+    for index in (0..block_peers.len()).map(|i| (first_peer + i) % block_peers.len()) {
+        let peer = &block_peers[index];
+        if peer.failed.load(Ordering::Relaxed) {
+            continue;
+        }
+        let result = timeout(RANGE_TIMEOUT, get_blocks(peer.client.clone(), &range)).await;
+        let reason = match result {
+            Ok(Ok(blocks)) => return Ok(blocks),
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "the peer did not send the blocks in time".to_owned(),
+        };
+        warn!(
+            logger,
+            "failed to download blocks, not downloading from this peer anymore";
+            "peer_addr" => %peer.address,
+            "from" => %range[0],
+            "reason" => reason,
+        );
+        peer.failed.store(true, Ordering::Relaxed);
+    }
+    Err(Error::BlocksNotAvailable(range[0]))
+}
+
+async fn get_blocks(mut client: grpc::Client, range: &[HeaderHash]) -> Result<Vec<Block>, Error> {
+    let ids: Vec<_> = range
+        .iter()
+        .map(|hash| net_data::BlockId::try_from(hash.as_bytes()).unwrap())
+        .collect();
+    let blocks: Vec<_> = client
+        .get_blocks(ids.into())
+        .await
+        .map_err(Error::GetBlocksFailed)?
+        .map_err(Error::PullStreamFailed)
+        .and_then(|block| {
+            future::ready(Block::deserialize(block.as_bytes()).map_err(Error::BlockDecodingFailed))
+        })
+        .try_collect()
+        .await?;
+    for (block, hash) in blocks.iter().zip(range) {
+        if block.header.hash() != *hash {
+            return Err(Error::UnexpectedBlock(block.header.hash()));
+        }
+    }
+    if let Some(block) = blocks.get(range.len()) {
+        return Err(Error::UnexpectedBlock(block.header.hash()));
+    }
+    if let Some(hash) = range.get(blocks.len()) {
+        return Err(Error::MissingBlock(*hash));
+    }
+    Ok(blocks)
+}
+
+/// run `future` unless the bootstrap is interrupted first
+async fn interruptible<F: Future>(
+    future: F,
+    bootstrap_stopper: &mut Shared<Receiver<()>>,
+) -> Result<F::Output, Error> {
+    futures::pin_mut!(future);
+    match future::select(future, bootstrap_stopper).await {
+        Either::Left((output, _)) => Ok(output),
         Either::Right((bootstrap_stopper_result, _)) => match bootstrap_stopper_result {
             Ok(()) => Err(Error::Interrupted),
             Err(_) => panic!("failed to wait for SIGINT"),
@@ -183,96 +489,6 @@ impl BootstrapInfo {
     }
 }
 
-async fn bootstrap_from_stream<S>(
-    mut blockchain: Blockchain,
-    branch: Tip,
-    stream: S,
-    bootstrap_stopper: Shared<Receiver<()>>,
-    logger: Logger,
-) -> Result<(), Error>
-where
-    S: Stream<Item = Result<net_data::Block, NetworkError>> + Unpin,
-{
-    const PROCESS_LOGGING_DISTANCE: u64 = 2500;
-    let block0 = blockchain.block0().clone();
-
-    let mut bootstrap_info = BootstrapInfo::new();
-    let mut maybe_parent_tip = None;
-
-    let mut stream = stream.map_err(Error::PullStreamFailed);
-    let mut bootstrap_stopper = bootstrap_stopper.map(|res| match res {
-        Ok(()) => Err(Error::Interrupted),
-        Err(_) => panic!("failed to wait for SIGINT"),
-    });
-
-    // This stream will either end when the block stream is exhausted or when
-    // the cancellation signal arrives. Building such stream allows us to
-    // correctly write all blocks and update the block tip upon the arrival of
-    // the cancellation signal.
-    let mut stream = stream::poll_fn(move |cx| {
-        let bootstrap_stopper = Pin::new(&mut bootstrap_stopper);
-        match bootstrap_stopper.poll(cx) {
-            Poll::Pending => {
-                let stream = Pin::new(&mut stream);
-                stream.poll_next(cx)
-            }
-            Poll::Ready(value) => Poll::Ready(Some(value)),
-        }
-    });
-
-    while let Some(block_result) = stream.next().await {
-        let result = match block_result {
-            Ok(block) => {
-                let block =
-                    Block::deserialize(block.as_bytes()).map_err(Error::BlockDecodingFailed)?;
-
-                if block.header.hash() == block0 {
-                    continue;
-                }
-
-                bootstrap_info.append_block(&block);
-
-                if bootstrap_info.block_received % PROCESS_LOGGING_DISTANCE == 0 {
-                    bootstrap_info.report(&logger);
-                }
-
-                handle_block(&blockchain, block, &logger).await
-            }
-            Err(err) => Err(err),
-        };
-
-        match result {
-            Ok(parent_tip) => {
-                maybe_parent_tip = Some(parent_tip);
-            }
-            Err(err) => {
-                if let Some(parent_tip) = maybe_parent_tip {
-                    if let Err(err) = blockchain::process_new_ref(
-                        &logger,
-                        &mut blockchain,
-                        branch.clone(),
-                        parent_tip.clone(),
-                    )
-                    .await
-                    {
-                        warn!(logger, "couldn't gracefully exit from failed netboot"; "error" => ?err);
-                    }
-                }
-                return Err(err);
-            }
-        }
-    }
-
-    if let Some(parent_tip) = maybe_parent_tip {
-        blockchain::process_new_ref(&logger, &mut blockchain, branch, parent_tip)
-            .await
-            .map_err(|e| Error::ChainSelectionFailed(e))
-    } else {
-        info!(logger, "no new blocks in bootstrap stream");
-        Ok(())
-    }
-}
-
 async fn handle_block(
     blockchain: &Blockchain,
     block: Block,
@@ -315,3 +531,145 @@ async fn handle_block(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockDate, BlockVersion, ChainLength, ContentsBuilder, Fragment};
+    use crate::blockchain::Storage;
+    use crate::network::convert::Encode;
+    use chain_addr::Discrimination;
+    use chain_impl_mockchain::testing::ledger::ConfigBuilder;
+    use std::convert::Infallible;
+
+    type HeaderStream =
+        futures::stream::Iter<std::vec::IntoIter<Result<net_data::Header, NetworkError>>>;
+
+    fn block0() -> Block {
+        let config = ConfigBuilder::new(0)
+            .with_discrimination(Discrimination::Test)
+            .build();
+        let mut contents = ContentsBuilder::new();
+        contents.push(Fragment::Initial(config));
+        chain_impl_mockchain::block::builder(BlockVersion::Genesis, contents.into(), |hdr| {
+            let header: Result<Header, Infallible> = Ok(hdr
+                .set_genesis()
+                .set_date(BlockDate::first())
+                .into_unsigned_header()
+                .unwrap()
+                .generalize());
+            header
+        })
+        .unwrap()
+    }
+
+    /// the header of an empty block with the given parent and chain length
+    fn header(parent: &Header, chain_length: ChainLength, slot_id: u32) -> Header {
+        chain_impl_mockchain::block::builder(
+            BlockVersion::Genesis,
+            ContentsBuilder::new().into(),
+            |hdr| {
+                let header: Result<Header, Infallible> = Ok(hdr
+                    .set_parent(&parent.hash(), chain_length)
+                    .set_date(BlockDate { epoch: 0, slot_id })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize());
+                header
+            },
+        )
+        .unwrap()
+        .header
+    }
+
+    /// `count` headers following `parent`
+    fn headers(parent: &Header, count: usize) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for _ in 0..count {
+            let parent = headers.last().unwrap_or(parent);
+            let child = header(
+                parent,
+                parent.chain_length().increase(),
+                parent.block_date().slot_id + 1,
+            );
+            headers.push(child);
+        }
+        headers
+    }
+
+    /// the header chain of a blockchain holding only `block0`, receiving the
+    /// header of `block0` followed by `headers`
+    async fn header_chain(block0: &Block, headers: &[Header]) -> HeaderChain<HeaderStream> {
+        let store = chain_storage::BlockStoreBuilder::memory()
+            .busy_timeout(1000)
+            .build();
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let storage = Storage::new(store, None, logger);
+        let blockchain = Blockchain::new(block0.header.hash(), storage, 10, false);
+        blockchain.load_from_block0(block0.clone()).await.unwrap();
+
+        let stream: Vec<_> = std::iter::once(&block0.header)
+            .chain(headers)
+            .map(|header| Ok(header.encode()))
+            .collect();
+        HeaderChain {
+            stream: futures::stream::iter(stream),
+            blockchain,
+            last_header: None,
+        }
+    }
+
+    fn hashes(headers: &[Header]) -> Vec<HeaderHash> {
+        headers.iter().map(Header::hash).collect()
+    }
+
+    #[tokio::test]
+    async fn headers_are_split_in_ranges() {
+        let block0 = block0();
+        let headers = headers(&block0.header, BLOCK_RANGE_SIZE + 1);
+        let mut header_chain = header_chain(&block0, &headers).await;
+
+        // the header of block0, already present, is skipped
+        let range = header_chain.next_range().await.unwrap();
+        assert_eq!(range, hashes(&headers[..BLOCK_RANGE_SIZE]));
+        let range = header_chain.next_range().await.unwrap();
+        assert_eq!(range, hashes(&headers[BLOCK_RANGE_SIZE..]));
+        assert!(header_chain.next_range().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn broken_parent_link_is_refused() {
+        let block0 = block0();
+        let mut headers = headers(&block0.header, 2);
+        // a sibling of the first header instead of its child
+        let sibling = header(&block0.header, block0.header.chain_length().increase(), 5);
+        headers.push(sibling.clone());
+        let mut header_chain = header_chain(&block0, &headers).await;
+
+        match header_chain.next_range().await {
+            Err(Error::BrokenHeaderChain(hash)) => assert_eq!(hash, sibling.hash()),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_link_verification_is_refused() {
+        let block0 = block0();
+        let mut headers = headers(&block0.header, 1);
+        // the parent hash matches, but not the chain length
+        let skipping = header(
+            &headers[0],
+            headers[0].chain_length().increase().increase(),
+            2,
+        );
+        headers.push(skipping);
+        let mut header_chain = header_chain(&block0, &headers).await;
+
+        match header_chain.next_range().await {
+            Err(Error::HeaderChainVerificationFailed(
+                HeaderChainVerifyError::ChainLengthNotIncremental { .. },
+            )) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -31,7 +31,7 @@ pub fn connect(state: ConnectionState, channels: Channels) -> (ConnectHandle, Co
         logger: state.logger.clone(),
     };
     let cf = async move {
-        let mut grpc_client = grpc::connect(&peer)
+        let mut grpc_client = grpc::connect(&peer, state.global.config.tls.as_deref())
             .await
             .map_err(ConnectError::Transport)?;
         let block0 = grpc_client
@@ -15,11 +15,12 @@ use super::{
     Channels, GlobalStateR,
 };
 use crate::{
-    intercom::{self, BlockMsg, ClientMsg},
+    intercom::{self, BlockMsg, ClientMsg, TransactionMsg},
     utils::async_msg::MessageBox,
 };
 use chain_network::data as net_data;
 use chain_network::data::block::{BlockEvent, BlockIds, ChainPullRequest};
+use chain_network::data::FragmentIds;
 
 use futures::prelude::*;
 use futures::ready;
@@ -38,10 +39,12 @@ pub struct Client {
     inbound: InboundSubscriptions,
     block_solicitations: OutboundSubscription<BlockIds>,
     chain_pulls: OutboundSubscription<ChainPullRequest>,
+    fragment_solicitations: OutboundSubscription<FragmentIds>,
     block_sink: BlockAnnouncementProcessor,
     fragment_sink: FragmentProcessor,
     gossip_sink: GossipProcessor,
     client_box: MessageBox<ClientMsg>,
+    transaction_box: MessageBox<TransactionMsg>,
     incoming_block_announcement: Option<net_data::Header>,
     incoming_solicitation: Option<ClientMsg>,
     shutting_down: bool,
@@ -77,7 +80,7 @@ impl Client {
             logger.new(o!("stream" => "block_events", "direction" => "in")),
         );
         let fragment_sink = FragmentProcessor::new(
-            builder.channels.transaction_box,
+            builder.channels.transaction_box.clone(),
             inbound.node_id.clone(),
             global_state.clone(),
             logger.new(o!("stream" => "fragments", "direction" => "in")),
@@ -95,10 +98,12 @@ impl Client {
             inbound,
             block_solicitations: comms.subscribe_to_block_solicitations(),
             chain_pulls: comms.subscribe_to_chain_pulls(),
+            fragment_solicitations: comms.subscribe_to_fragment_solicitations(),
             block_sink,
             fragment_sink,
             gossip_sink,
             client_box: builder.channels.client_box,
+            transaction_box: builder.channels.transaction_box,
             incoming_block_announcement: None,
             incoming_solicitation: None,
             shutting_down: false,
@@ -334,6 +339,7 @@ impl Client {
 
     fn pull_headers(&mut self, req: ChainPullRequest) {
         let mut block_box = self.block_sink.message_box();
+        let node_id = self.inbound.node_id.clone();
         let logger = self.logger.new(o!("request" => "PullHeaders"));
         let logger1 = logger.clone();
         let (handle, sink, _) =
@@ -342,7 +348,9 @@ impl Client {
         // in flight prevents unlimited spawning of these tasks.
         // https://github.com/input-output-hk/jormungandr/issues/1034
         self.global_state.spawn(async move {
-            let res = block_box.send(BlockMsg::ChainHeaders(handle)).await;
+            let res = block_box
+                .send(BlockMsg::ChainHeaders(handle, Some(node_id)))
+                .await;
             if let Err(e) = res {
                 error!(
                     logger,
@@ -378,6 +386,7 @@ impl Client {
 
     fn solicit_blocks(&mut self, block_ids: BlockIds) {
         let mut block_box = self.block_sink.message_box();
+        let node_id = self.inbound.node_id.clone();
         let logger = self.logger.new(o!("request" => "GetBlocks"));
         let req_err_logger = logger.clone();
         let res_logger = logger.clone();
@@ -387,7 +396,9 @@ impl Client {
         // in flight prevents unlimited spawning of these tasks.
         // https://github.com/input-output-hk/jormungandr/issues/1034
         self.global_state.spawn(async move {
-            let res = block_box.send(BlockMsg::NetworkBlocks(handle)).await;
+            let res = block_box
+                .send(BlockMsg::NetworkBlocks(handle, Some(node_id)))
+                .await;
             if let Err(e) = res {
                 error!(
                     logger,
@@ -421,6 +432,39 @@ impl Client {
         });
     }
 
+    fn solicit_fragments(&mut self, fragment_ids: FragmentIds) {
+        let logger = self.logger.new(o!("request" => "GetFragments"));
+        // the fragments are processed as if the peer had pushed them
+        let sink = FragmentProcessor::new(
+            self.transaction_box.clone(),
+            self.inbound.node_id.clone(),
+            self.global_state.clone(),
+            logger.clone(),
+        );
+        let mut client = self.inner.clone();
+        self.global_state.spawn(async move {
+            match client.get_fragments(fragment_ids).await {
+                Err(e) => {
+                    info!(
+                        logger,
+                        "request failed";
+                        "reason" => %e,
+                    );
+                }
+                Ok(stream) => {
+                    let res = stream.forward(sink).await;
+                    if let Err(e) = res {
+                        info!(
+                            logger,
+                            "response stream failed";
+                            "reason" => %e,
+                        );
+                    }
+                }
+            }
+        });
+    }
+
     fn process_fragments(&mut self, cx: &mut Context<'_>) -> Poll<Result<ProcessingOutcome, ()>> {
         use self::ProcessingOutcome::*;
 
@@ -550,6 +594,20 @@ impl Future for Client {
                         }
                     })
             });
+            progress.and_proceed_with(|| {
+                Pin::new(&mut self.fragment_solicitations)
+                    .poll_next(cx)
+                    .map(|maybe_item| match maybe_item {
+                        Some(fragment_ids) => {
+                            self.solicit_fragments(fragment_ids);
+                            Ok(Continue)
+                        }
+                        None => {
+                            debug!(self.logger, "outbound fragment solicitation stream closed");
+                            Ok(Disconnect)
+                        }
+                    })
+            });
 
             match progress {
                 Progress(Poll::Pending) => return Poll::Pending,
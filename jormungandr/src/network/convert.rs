@@ -1,5 +1,5 @@
 use super::p2p::Gossip;
-use crate::blockcfg::{Block, Fragment, Header, HeaderId};
+use crate::blockcfg::{Block, Fragment, FragmentId, Header, HeaderId};
 use crate::intercom;
 use chain_core::mempack::{ReadBuf, Readable};
 use chain_core::property::{Deserialize, Serialize};
@@ -87,6 +87,14 @@ impl Decode for net_data::Fragment {
     }
 }
 
+impl Decode for net_data::FragmentId {
+    type Object = FragmentId;
+
+    fn decode(self) -> Result<Self::Object, Error> {
+        read(&self)
+    }
+}
+
 impl Decode for net_data::gossip::Node {
     type Object = Gossip;
     fn decode(self) -> Result<Self::Object, Error> {
@@ -113,6 +121,15 @@ impl Encode for HeaderId {
     }
 }
 
+/// `FragmentId` and `HeaderId` are the same type, which can only have
+/// one `Encode` implementation
+pub fn encode_fragment_ids(ids: &[FragmentId]) -> net_data::FragmentIds {
+    ids.iter()
+        .map(|id| net_data::FragmentId::try_from(id.as_bytes()).unwrap())
+        .collect::<Vec<_>>()
+        .into()
+}
+
 impl Encode for Block {
     type NetworkData = net_data::Block;
 
@@ -2,6 +2,7 @@ use crate::{
     blockcfg::{Block, HeaderHash},
     network::concurrency_limits,
     network::convert::Decode,
+    network::tls::{TlsContext, TLS_DOMAIN_NAME},
     settings::start::network::{Peer, Protocol},
 };
 use chain_network::data as net_data;
@@ -36,19 +37,26 @@ pub type ConnectError = transport::Error;
 
 pub type Client = chain_network::grpc::Client<tonic::transport::Channel>;
 
-pub async fn connect(peer: &Peer) -> Result<Client, ConnectError> {
+pub async fn connect(peer: &Peer, tls: Option<&TlsContext>) -> Result<Client, ConnectError> {
     assert!(peer.protocol == Protocol::Grpc);
-    let endpoint = destination_endpoint(peer.connection)
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let mut endpoint = destination_endpoint(scheme, peer.connection)
         .concurrency_limit(concurrency_limits::CLIENT_REQUESTS)
         .timeout(peer.timeout);
+    if let Some(tls) = tls {
+        let tls_config = transport::ClientTlsConfig::new()
+            .domain_name(TLS_DOMAIN_NAME)
+            .rustls_client_config(tls.client_config(peer.connection));
+        endpoint = endpoint.tls_config(tls_config);
+    }
     Client::connect(endpoint).await
 }
 
-fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
+fn destination_endpoint(scheme: &str, addr: SocketAddr) -> transport::Endpoint {
     let ip = addr.ip();
     let uri = match ip {
-        IpAddr::V4(ip) => format!("http://{}:{}", ip, addr.port()),
-        IpAddr::V6(ip) => format!("http://[{}]:{}", ip, addr.port()),
+        IpAddr::V4(ip) => format!("{}://{}:{}", scheme, ip, addr.port()),
+        IpAddr::V6(ip) => format!("{}://[{}]:{}", scheme, ip, addr.port()),
     };
     transport::Endpoint::try_from(uri).unwrap()
 }
@@ -58,10 +66,11 @@ fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
 pub async fn fetch_block(
     peer: &Peer,
     hash: HeaderHash,
+    tls: Option<&TlsContext>,
     logger: &Logger,
 ) -> Result<Block, FetchBlockError> {
     info!(logger, "fetching block {}", hash);
-    let mut client = connect(peer)
+    let mut client = connect(peer, tls)
         .await
         .map_err(|err| FetchBlockError::Connect { source: err })?;
     let block_id = net_data::BlockId::try_from(hash.as_bytes()).unwrap();
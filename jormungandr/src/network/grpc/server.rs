@@ -1,8 +1,25 @@
 use super::super::{service::NodeService, Channels, GlobalStateR, ListenError};
+use crate::network::p2p::ConnectionPermit;
 use crate::settings::start::network::Listen;
 use chain_network::grpc;
 
-use tonic::transport::Server;
+use futures::prelude::*;
+use futures::stream;
+use slog::Logger;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time;
+use tonic::transport::{server::Connected, Server, ServerTlsConfig};
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// the delay before accepting connections again after an error, which is
+/// typically running out of file descriptors
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
 
 pub async fn run_listen_socket(
     listen: &Listen,
@@ -12,13 +29,114 @@ pub async fn run_listen_socket(
     let sockaddr = listen.address();
 
     let logger = state.logger().new(o!("local_addr" => sockaddr.to_string()));
+
+    let listener = TcpListener::bind(sockaddr).await.map_err(|e| ListenError {
+        cause: e.into(),
+        sockaddr,
+    })?;
     info!(logger, "listening and accepting gRPC connections");
 
+    let mut builder = Server::builder();
+    if let Some(tls) = &state.config.tls {
+        let mut tls_config = ServerTlsConfig::new();
+        tls_config.rustls_server_config(tls.server_config());
+        builder = builder.tls_config(tls_config);
+    }
+
+    let incoming = limited_incoming(listener, state.clone(), logger);
     let service = grpc::Server::new(grpc::NodeService::new(NodeService::new(channels, state)));
 
-    Server::builder()
+    builder
         .add_service(service)
-        .serve(sockaddr)
+        .serve_with_incoming(incoming)
         .await
-        .map_err(|cause| ListenError { cause, sockaddr })
+        .map_err(|e| ListenError {
+            cause: e.into(),
+            sockaddr,
+        })
+}
+
+/// the connections accepted on the listener, without the ones refused by
+/// the connection limits
+fn limited_incoming(
+    listener: TcpListener,
+    state: GlobalStateR,
+    logger: Logger,
+) -> impl Stream<Item = Result<LimitedStream, io::Error>> {
+    stream::unfold(listener, |mut listener| async move {
+        let res = listener.accept().await;
+        Some((res, listener))
+    })
+    .filter_map(move |res| {
+        let state = state.clone();
+        let logger = logger.clone();
+        async move {
+            match res {
+                Ok((stream, addr)) => match state.connection_limits.admit_inbound(addr) {
+                    Ok(permit) => Some(Ok(LimitedStream {
+                        inner: stream,
+                        addr,
+                        _permit: permit,
+                    })),
+                    Err(refusal) => {
+                        debug!(
+                            logger,
+                            "refusing connection";
+                            "peer_addr" => %addr,
+                            "reason" => %refusal,
+                        );
+                        None
+                    }
+                },
+                Err(e) => {
+                    warn!(logger, "failed to accept connection"; "reason" => %e);
+                    time::delay_for(ACCEPT_ERROR_DELAY).await;
+                    None
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// an accepted connection, accounted in the connection limits until it is
+/// closed
+struct LimitedStream {
+    inner: TcpStream,
+    addr: SocketAddr,
+    _permit: ConnectionPermit,
+}
+
+impl Connected for LimitedStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.addr)
+    }
+}
+
+impl AsyncRead for LimitedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for LimitedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
 }
@@ -12,6 +12,7 @@ mod grpc;
 pub mod p2p;
 mod service;
 mod subscription;
+pub mod tls;
 
 use self::convert::Encode;
 use futures::{future, prelude::*};
@@ -45,6 +46,9 @@ mod buffer_sizes {
         // (GetBlocks response or an UploadBlocks request)
         // before the client request task producing them gets preempted.
         pub const BLOCKS: usize = 8;
+
+        // The maximum number of fragments to buffer for a GetFragments response.
+        pub const FRAGMENTS: usize = 32;
     }
 }
 
@@ -54,7 +58,11 @@ mod concurrency_limits {
 }
 
 use self::client::ConnectError;
-use self::p2p::{comm::Peers, P2pTopology};
+use self::p2p::{
+    address_book::{self, AddressBook},
+    comm::Peers,
+    ConnectionLimits, FragmentLimits, P2pTopology, PeerScores,
+};
 use crate::blockcfg::{Block, HeaderHash};
 use crate::blockchain::{Blockchain as NewBlockchain, Tip};
 use crate::intercom::{BlockMsg, ClientMsg, NetworkMsg, PropagateMsg, TransactionMsg};
@@ -69,12 +77,12 @@ use futures::{channel::oneshot::Receiver, future::Shared};
 use poldercast::StrikeReason;
 use rand::seq::SliceRandom;
 use slog::Logger;
-use tonic::transport;
 
 use std::collections::BTreeMap;
 use std::error;
 use std::fmt;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -83,7 +91,7 @@ use crate::stats_counter::StatsCounter;
 
 #[derive(Debug)]
 pub struct ListenError {
-    cause: transport::Error,
+    cause: Box<dyn error::Error + Send + Sync>,
     sockaddr: SocketAddr,
 }
 
@@ -99,7 +107,7 @@ impl fmt::Display for ListenError {
 
 impl error::Error for ListenError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        Some(&self.cause)
+        Some(&*self.cause)
     }
 }
 
@@ -129,6 +137,9 @@ pub struct GlobalState {
     stats_counter: StatsCounter,
     topology: P2pTopology,
     peers: Peers,
+    fragment_limits: FragmentLimits,
+    peer_scores: PeerScores,
+    connection_limits: ConnectionLimits,
     logger: Logger,
 }
 
@@ -144,6 +155,9 @@ impl GlobalState {
         logger: Logger,
     ) -> Self {
         let peers = Peers::new(config.max_connections, logger.clone());
+        let fragment_limits = FragmentLimits::new(&config.fragment_limits);
+        let peer_scores = PeerScores::new(&config.peer_scoring);
+        let connection_limits = ConnectionLimits::new(&config.connection_limits);
 
         GlobalState {
             block0_hash,
@@ -151,6 +165,9 @@ impl GlobalState {
             stats_counter,
             topology,
             peers,
+            fragment_limits,
+            peer_scores,
+            connection_limits,
             logger,
         }
     }
@@ -167,6 +184,10 @@ impl GlobalState {
         &self.topology
     }
 
+    pub fn peer_scores(&self) -> &PeerScores {
+        &self.peer_scores
+    }
+
     pub fn spawn<F>(&self, f: F)
     where
         F: Future<Output = ()> + Send + 'static,
@@ -272,6 +293,15 @@ pub async fn start(service_info: TokioServiceInfo, params: TaskParams) {
         start_gossiping(global_state.clone(), channels.clone()),
     );
 
+    if let Some(path) = global_state.config.address_book.clone() {
+        let state = global_state.clone();
+        service_info.run_periodic(
+            "save address book",
+            address_book::SAVE_INTERVAL,
+            move || save_address_book(state.clone(), path.clone()),
+        );
+    }
+
     let handle_cmds = handle_network_input(input, global_state.clone(), channels.clone());
 
     let reset_state = global_state.clone();
@@ -299,7 +329,12 @@ async fn handle_network_input(
             NetworkMsg::Propagate(msg) => {
                 handle_propagation_msg(msg, state.clone(), channels.clone()).await;
             }
-            NetworkMsg::GetBlocks(block_ids) => state.peers.fetch_blocks(block_ids.encode()).await,
+            NetworkMsg::GetBlocks(block_ids) => {
+                state
+                    .peers
+                    .fetch_blocks(block_ids.encode(), &state.peer_scores)
+                    .await
+            }
             NetworkMsg::GetNextBlock(node_id, block_id) => {
                 state
                     .peers
@@ -320,10 +355,37 @@ async fn handle_network_input(
             NetworkMsg::PeerInfo(reply) => {
                 state.peers.infos().map(|infos| reply.reply_ok(infos)).await;
             }
+            NetworkMsg::ScorePeer(node_id, outcome) => {
+                if state.peer_scores.on_block_outcome(&node_id, outcome) {
+                    report_poorly_scored_peer(state.clone(), node_id).await;
+                }
+            }
         }
     }
 }
 
+/// Report a peer that sent invalid blocks or headers while its score is
+/// below the threshold to the p2p policy and drop the connections with it.
+async fn report_poorly_scored_peer(state: GlobalStateR, node_id: p2p::Address) {
+    if let Some(score) = state.peer_scores.forget(&node_id) {
+        info!(
+            state.logger(),
+            "peer sent invalid data with a poor score, reporting it";
+            "node_id" => %node_id,
+            "score" => score.score,
+            "useful" => score.counters.useful,
+            "invalid" => score.counters.invalid,
+        );
+    }
+    future::join(
+        state
+            .topology
+            .report_node(node_id.clone(), StrikeReason::InvalidData),
+        state.peers.remove_peer(node_id),
+    )
+    .await;
+}
+
 async fn handle_propagation_msg(msg: PropagateMsg, state: GlobalStateR, channels: Channels) {
     let prop_state = state.clone();
     let propagate_res = match &msg {
@@ -356,7 +418,9 @@ async fn handle_propagation_msg(msg: PropagateMsg, state: GlobalStateR, channels
     // If any nodes selected for propagation are not in the
     // active subscriptions map, connect to them and deliver
     // the item.
-    if let Err(unreached_nodes) = propagate_res {
+    if let Err(mut unreached_nodes) = propagate_res {
+        // connect to the best scored peers first
+        state.peer_scores.rank(&mut unreached_nodes);
         debug!(
             state.logger(),
             "will try to connect to {} of the peers not immediately reachable for propagation",
@@ -382,23 +446,35 @@ async fn start_gossiping(state: GlobalStateR, channels: Channels) {
     let topology = &state.topology;
     let logger = state.logger().new(o!(log::KEY_SUB_TASK => "start_gossip"));
     let address = config.profile.address().unwrap();
-    // inject the trusted peers as initial gossips, this will make the node
-    // gossip with them at least at the beginning
+    // inject the peers of the address book, or the trusted peers if none
+    // are known, as initial gossips, this will make the node gossip with
+    // them at least at the beginning
+    let known_peers = load_address_book(config, &logger);
+    let initial_gossips: Vec<p2p::Gossip> = if known_peers.is_empty() {
+        config
+            .trusted_peers
+            .iter()
+            .map(|tp| {
+                let mut builder = poldercast::NodeProfileBuilder::new();
+                builder.address(tp.address.clone().into());
+                builder.build()
+            })
+            .map(p2p::Gossip::from)
+            .collect()
+    } else {
+        info!(
+            logger,
+            "starting from {} peers of the address book",
+            known_peers.entries().len()
+        );
+        known_peers
+            .entries()
+            .iter()
+            .map(|entry| p2p::Gossip::from(entry.profile.clone()))
+            .collect()
+    };
     topology
-        .accept_gossips(
-            address.clone().into(),
-            config
-                .trusted_peers
-                .iter()
-                .map(|tp| {
-                    let mut builder = poldercast::NodeProfileBuilder::new();
-                    builder.address(tp.address.clone().into());
-                    builder.build()
-                })
-                .map(p2p::Gossip::from)
-                .collect::<Vec<p2p::Gossip>>()
-                .into(),
-        )
+        .accept_gossips(address.clone().into(), initial_gossips.into())
         .await;
     let view = topology.view(poldercast::Selection::Any).await;
     let peers: Vec<p2p::Address> = view.peers;
@@ -461,6 +537,18 @@ fn connect_and_propagate(
             return;
         }
     };
+    let permit = match state.connection_limits.acquire_outbound(addr) {
+        Ok(permit) => permit,
+        Err(refusal) => {
+            debug!(
+                state.logger(),
+                "not connecting to P2P node" ;
+                "address" => %node,
+                "reason" => %refusal
+            );
+            return;
+        }
+    };
     options.evict_clients = state.num_clients_to_bump();
     assert_ne!(
         Some(&node),
@@ -474,9 +562,11 @@ fn connect_and_propagate(
     let (handle, connecting) = client::connect(conn_state, channels);
     let spawn_state = state.clone();
     let cf = async move {
+        // the outbound connection is accounted until the client is done
+        let _permit = permit;
         state
             .peers
-            .add_connecting(node.clone(), handle, options)
+            .add_connecting(node.clone(), handle, options, &state.peer_scores)
             .await;
         match connecting.await {
             Err(e) => {
@@ -523,6 +613,37 @@ fn connect_and_propagate(
     spawn_state.spawn(cf);
 }
 
+/// the peers of the address book, empty if the node has no storage or no
+/// peer was seen recently
+fn load_address_book(config: &Configuration, logger: &Logger) -> AddressBook {
+    let path = match &config.address_book {
+        Some(path) => path,
+        None => return AddressBook::default(),
+    };
+    AddressBook::load(path).unwrap_or_else(|e| {
+        warn!(logger, "cannot read the address book"; "path" => %path.display(), "reason" => %e);
+        AddressBook::default()
+    })
+}
+
+async fn save_address_book(state: GlobalStateR, path: PathBuf) {
+    let logger = state.logger().new(o!(log::KEY_SUB_TASK => "address_book"));
+    let mut book = load_address_book(&state.config, &logger);
+    let (available, quarantined, connected) = future::join3(
+        state.topology.list_available(),
+        state.topology.list_quarantined(),
+        state.peers.infos(),
+    )
+    .await;
+    book.update(&available, &quarantined, &connected);
+    match book.save(&path) {
+        Ok(()) => debug!(logger, "address book saved"; "peers" => book.entries().len()),
+        Err(e) => {
+            warn!(logger, "cannot save the address book"; "path" => %path.display(), "reason" => %e)
+        }
+    }
+}
+
 fn trusted_peers_shuffled(config: &Configuration) -> Vec<SocketAddr> {
     let mut peers = config
         .trusted_peers
@@ -590,16 +711,20 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
         for tpeer in trusted_peers {
             // let peer = Peer::new(peer, Protocol::Grpc);
             let tp_logger = logger.new(o!("peer_addr" => tpeer.address().to_string()));
-            let received_peers = bootstrap::peers_from_trusted_peer(&tpeer, tp_logger.clone())
-                .await
-                .unwrap_or_else(|e| {
-                    warn!(
-                        tp_logger,
-                        "failed to retrieve the list of bootstrap peers from trusted peer";
-                        "reason" => %e,
-                    );
-                    vec![tpeer]
-                });
+            let received_peers = bootstrap::peers_from_trusted_peer(
+                &tpeer,
+                config.tls.as_deref(),
+                tp_logger.clone(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    tp_logger,
+                    "failed to retrieve the list of bootstrap peers from trusted peer";
+                    "reason" => %e,
+                );
+                vec![tpeer]
+            });
             let added = peers.add_peers(&received_peers);
             info!(logger, "adding {} peers from peer", added);
 
@@ -611,6 +736,9 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
     peers
 }
 
+/// the number of peers of the address book tried for the bootstrap
+const MAX_ADDRESS_BOOK_BOOTSTRAP_PEERS: usize = 32;
+
 pub async fn bootstrap(
     config: &Configuration,
     blockchain: NewBlockchain,
@@ -636,7 +764,45 @@ pub async fn bootstrap(
         //panic!("use non empty trusted-peers or set skip_bootstrap");
     }
 
-    let mut bootstrapped = false;
+    // the peers of the address book are tried first, the trusted peers are
+    // only used if none of them can be bootstrapped from
+    let known_peers: Vec<Peer> = if config.bootstrap_from_trusted_peers {
+        Vec::new()
+    } else {
+        load_address_book(config, logger)
+            .entries()
+            .iter()
+            .filter_map(|entry| entry.socket_addr().map(Peer::new))
+            .collect()
+    };
+    if !known_peers.is_empty() {
+        info!(
+            logger,
+            "bootstrapping from {} peers of the address book",
+            known_peers.len()
+        );
+        // the best peers come first
+        let peers: Vec<&Peer> = known_peers
+            .iter()
+            .take(MAX_ADDRESS_BOOK_BOOTSTRAP_PEERS)
+            .collect();
+        if bootstrap_from(
+            config,
+            &peers,
+            blockchain.clone(),
+            branch.clone(),
+            bootstrap_stopper.clone(),
+            logger,
+        )
+        .await?
+        {
+            return Ok(true);
+        }
+        warn!(
+            logger,
+            "could not bootstrap from the peers of the address book, trying the trusted peers"
+        );
+    }
 
     let (netboot_peers, bootstrap_stopper) =
         match select(netboot_peers(config, logger).boxed(), bootstrap_stopper).await {
@@ -647,10 +813,40 @@ pub async fn bootstrap(
             },
         };
 
-    for peer in netboot_peers.randomly() {
+    bootstrap_from(
+        config,
+        &netboot_peers.randomly(),
+        blockchain,
+        branch,
+        bootstrap_stopper,
+        logger,
+    )
+    .await
+}
+
+async fn bootstrap_from(
+    config: &Configuration,
+    peers: &[&Peer],
+    blockchain: NewBlockchain,
+    branch: Tip,
+    bootstrap_stopper: Shared<Receiver<()>>,
+    logger: &Logger,
+) -> Result<bool, bootstrap::Error> {
+    for (index, peer) in peers.iter().enumerate() {
         let logger = logger.new(o!("peer_addr" => peer.address().to_string()));
-        let res = bootstrap::bootstrap_from_peer(
+        // the headers are pulled from this peer, the blocks are also
+        // downloaded from the next peers
+        let block_peers: Vec<&Peer> = peers
+            .iter()
+            .cycle()
+            .skip(index)
+            .take(bootstrap::MAX_BLOCK_PEERS.min(peers.len()))
+            .cloned()
+            .collect();
+        let res = bootstrap::bootstrap_from_peers(
             peer,
+            &block_peers,
+            config.tls.as_deref(),
             blockchain.clone(),
             branch.clone(),
             bootstrap_stopper.clone(),
@@ -671,13 +867,12 @@ pub async fn bootstrap(
             }
             Ok(()) => {
                 info!(logger, "initial bootstrap completed");
-                bootstrapped = true;
-                break;
+                return Ok(true);
             }
         }
     }
 
-    Ok(bootstrapped)
+    Ok(false)
 }
 
 /// Queries the trusted peers for a block identified with the hash.
@@ -704,7 +899,7 @@ pub async fn fetch_block(
     for address in trusted_peers_shuffled(&config) {
         let logger = logger.new(o!("peer_address" => address.to_string()));
         let peer = Peer::new(address);
-        match grpc::fetch_block(&peer, hash, &logger).await {
+        match grpc::fetch_block(&peer, hash, config.tls.as_deref(), &logger).await {
             Err(grpc::FetchBlockError::Connect { source: e }) => {
                 warn!(logger, "unable to reach peer for block download"; "reason" => %e);
             }
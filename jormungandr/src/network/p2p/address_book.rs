@@ -0,0 +1,359 @@
+//! Address book of the peers known to the node.
+//!
+//! The topology only lives in memory, so after a restart the node would
+//! only know about its trusted peers. The peers of the topology are saved
+//! periodically in the storage directory, with the last time they were seen
+//! and some statistics about their quality, and the node reconnects to the
+//! network from them on startup. The peers not seen for a while are
+//! forgotten.
+
+use super::{comm::PeerInfo, Address};
+use jormungandr_lib::{multiaddr::multiaddr_to_socket_addr, time::SystemTime};
+use poldercast::{Node, NodeProfile};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    time::Duration,
+};
+
+pub const ADDRESS_BOOK_FILE_NAME: &str = "p2p-address-book.json";
+
+/// the interval at which the address book is saved
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// the peers not seen for longer are forgotten
+const STALE_AFTER: Duration = Duration::from_secs(3 * 24 * 3600);
+
+/// the maximum number of peers kept in the address book
+const MAX_ENTRIES: usize = 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub profile: NodeProfile,
+    /// the last time the node was connected to the peer or, for a peer it
+    /// never connected to, heard about it
+    pub last_seen: SystemTime,
+    #[serde(default)]
+    pub quality: Quality,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Quality {
+    /// the number of times the peer was connected when the address book
+    /// was saved
+    pub connected: u32,
+    /// the number of times the peer was in quarantine when the address book
+    /// was saved
+    pub quarantined: u32,
+    /// the last time a block was received from the peer
+    pub last_block_received: Option<SystemTime>,
+}
+
+impl Entry {
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        self.profile
+            .address()
+            .and_then(|address| multiaddr_to_socket_addr(address.multi_address()))
+    }
+
+    fn is_stale(&self, now: SystemTime) -> bool {
+        now.duration_since(self.last_seen)
+            .map_or(false, |elapsed| elapsed > STALE_AFTER)
+    }
+
+    /// the share of the saves the peer was connected at rather than in
+    /// quarantine, smoothed for the peers with few records
+    fn score(&self) -> f64 {
+        f64::from(self.quality.connected + 1)
+            / f64::from(self.quality.connected + self.quality.quarantined + 2)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct AddressBook {
+    entries: Vec<Entry>,
+}
+
+impl AddressBook {
+    /// read the address book, without the stale entries. The address book
+    /// is empty if the file does not exist yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(AddressBook::default())
+            }
+            Err(error) => return Err(error),
+        };
+        let mut book: AddressBook = serde_json::from_reader(BufReader::new(file))
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        book.tidy();
+        Ok(book)
+    }
+
+    /// write the address book, the file is only replaced once the new one
+    /// is completely written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// the peers of the address book, the best ones first
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// record the nodes of the topology and the activity of the connected
+    /// peers. The nodes already known are only considered seen again when
+    /// connected, the topology also lists the nodes the node was told
+    /// about, which may be gone.
+    pub fn update(&mut self, available: &[Node], quarantined: &[Node], connected: &[PeerInfo]) {
+        self.merge(
+            available
+                .iter()
+                .map(|node| (node.profile(), node.logs().last_update().into())),
+            quarantined.iter().map(Node::address),
+            connected,
+        )
+    }
+
+    /// the nodes of the topology are given by their profile and the last
+    /// time they were heard about
+    fn merge<'a>(
+        &mut self,
+        available: impl IntoIterator<Item = (&'a NodeProfile, SystemTime)>,
+        quarantined: impl IntoIterator<Item = &'a Address>,
+        connected: &[PeerInfo],
+    ) {
+        let connected: HashMap<SocketAddr, &PeerInfo> = connected
+            .iter()
+            .filter_map(|info| Some((info.addr?, info)))
+            .collect();
+        let mut entries: HashMap<Address, Entry> = self
+            .entries
+            .drain(..)
+            .filter_map(|entry| Some((entry.profile.address()?.clone(), entry)))
+            .collect();
+
+        for (profile, last_update) in available {
+            let address = match profile.address() {
+                Some(address) => address.clone(),
+                None => continue,
+            };
+            let entry = entries.entry(address).or_insert_with(|| Entry {
+                profile: profile.clone(),
+                last_seen: last_update,
+                quality: Quality::default(),
+            });
+            entry.profile = profile.clone();
+            let info = entry
+                .socket_addr()
+                .and_then(|addr| connected.get(&addr).cloned());
+            if let Some(info) = info {
+                entry.last_seen = entry.last_seen.max(info.stats.last_activity().into());
+                entry.quality.connected += 1;
+                if let Some(last_block) = info.stats.last_block_received() {
+                    entry.quality.last_block_received = Some(last_block.into());
+                }
+            }
+        }
+        for address in quarantined {
+            if let Some(entry) = entries.get_mut(address) {
+                entry.quality.quarantined += 1;
+            }
+        }
+
+        self.entries = entries.into_iter().map(|(_, entry)| entry).collect();
+        self.tidy();
+    }
+
+    /// remove the stale entries and sort the others, the best first
+    fn tidy(&mut self) {
+        let now = SystemTime::now();
+        self.entries
+            .retain(|entry| entry.socket_addr().is_some() && !entry.is_stale(now));
+        self.entries.sort_by(|a, b| {
+            b.score()
+                .partial_cmp(&a.score())
+                .unwrap_or(Ordering::Equal)
+                .then(b.last_seen.cmp(&a.last_seen))
+        });
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::p2p::comm::PeerStats;
+    use poldercast::NodeProfileBuilder;
+    use rand_core::{OsRng, RngCore};
+    use std::path::PathBuf;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("address-book-{:016x}", OsRng.next_u64()));
+            fs::create_dir(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn address(port: u16) -> Address {
+        format!("/ip4/10.0.0.1/tcp/{}", port).parse().unwrap()
+    }
+
+    fn profile(port: u16) -> NodeProfile {
+        let mut builder = NodeProfileBuilder::new();
+        builder.address(address(port));
+        builder.build()
+    }
+
+    fn ago(secs: u64) -> SystemTime {
+        (std::time::SystemTime::now() - Duration::from_secs(secs)).into()
+    }
+
+    fn entry(port: u16, last_seen: SystemTime, connected: u32, quarantined: u32) -> Entry {
+        Entry {
+            profile: profile(port),
+            last_seen,
+            quality: Quality {
+                connected,
+                quarantined,
+                last_block_received: None,
+            },
+        }
+    }
+
+    fn ports(book: &AddressBook) -> Vec<u16> {
+        book.entries()
+            .iter()
+            .map(|entry| entry.socket_addr().unwrap().port())
+            .collect()
+    }
+
+    #[test]
+    fn nothing_to_load_without_a_file() {
+        let dir = TempDir::new();
+        let book = AddressBook::load(&dir.0.join(ADDRESS_BOOK_FILE_NAME)).unwrap();
+        assert!(book.is_empty());
+    }
+
+    #[test]
+    fn address_book_roundtrip() {
+        let dir = TempDir::new();
+        let path = dir.0.join(ADDRESS_BOOK_FILE_NAME);
+        let last_seen = ago(60);
+        let mut book = AddressBook {
+            entries: vec![entry(3000, last_seen, 4, 1), entry(3001, ago(120), 0, 0)],
+        };
+        book.entries[0].quality.last_block_received = Some(last_seen);
+        book.save(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let loaded = AddressBook::load(&path).unwrap();
+        assert_eq!(ports(&loaded), vec![3000, 3001]);
+        let first = &loaded.entries()[0];
+        assert_eq!(first.last_seen, last_seen);
+        assert_eq!(first.quality.connected, 4);
+        assert_eq!(first.quality.quarantined, 1);
+        assert_eq!(first.quality.last_block_received, Some(last_seen));
+    }
+
+    #[test]
+    fn stale_entries_are_forgotten() {
+        let mut book = AddressBook {
+            entries: vec![
+                entry(3000, ago(STALE_AFTER.as_secs() + 60), 10, 0),
+                entry(3001, ago(STALE_AFTER.as_secs() - 60), 0, 0),
+                Entry {
+                    profile: NodeProfileBuilder::new().build(),
+                    last_seen: ago(0),
+                    quality: Quality::default(),
+                },
+            ],
+        };
+        book.tidy();
+        assert_eq!(ports(&book), vec![3001]);
+    }
+
+    #[test]
+    fn entries_are_sorted_by_score_then_last_seen() {
+        let mut book = AddressBook {
+            entries: vec![
+                entry(3000, ago(60), 0, 3),
+                entry(3001, ago(120), 0, 0),
+                entry(3002, ago(60), 0, 0),
+                entry(3003, ago(600), 5, 0),
+            ],
+        };
+        book.tidy();
+        assert_eq!(ports(&book), vec![3003, 3002, 3001, 3000]);
+    }
+
+    #[test]
+    fn merge_records_the_topology_and_the_connected_peers() {
+        let old_block = ago(3600);
+        let mut book = AddressBook {
+            entries: vec![
+                entry(3000, ago(3600), 1, 0),
+                entry(3001, ago(3600), 1, 0),
+                entry(3002, ago(60), 0, 0),
+            ],
+        };
+        book.entries[0].quality.last_block_received = Some(old_block);
+
+        let new_profile = profile(3003);
+        let known_profile = profile(3000);
+        let quarantined_profile = profile(3001);
+        let connected = vec![PeerInfo {
+            addr: Some("10.0.0.1:3000".parse().unwrap()),
+            stats: PeerStats::default(),
+        }];
+        book.merge(
+            vec![
+                (&known_profile, ago(7200)),
+                (&quarantined_profile, ago(0)),
+                (&new_profile, ago(30)),
+            ],
+            vec![&address(3001), &address(3004)],
+            &connected,
+        );
+
+        // the known entries are kept and the new node is added, the
+        // quarantined node unknown to the address book is not
+        assert_eq!(ports(&book), vec![3000, 3003, 3002, 3001]);
+        let connected_entry = &book.entries()[0];
+        assert_eq!(connected_entry.quality.connected, 2);
+        assert!(connected_entry.last_seen > ago(60));
+        assert_eq!(connected_entry.quality.last_block_received, Some(old_block));
+        let quarantined_entry = &book.entries()[3];
+        assert_eq!(quarantined_entry.quality.connected, 1);
+        assert_eq!(quarantined_entry.quality.quarantined, 1);
+        // the nodes only heard about are not seen again
+        assert!(quarantined_entry.last_seen < ago(3000));
+        assert!(book.entries()[1].last_seen < ago(20));
+    }
+}
@@ -2,9 +2,12 @@ mod peer_map;
 
 use peer_map::{CommStatus, PeerMap};
 
-use crate::network::{client::ConnectHandle, p2p::Address};
+use crate::network::{
+    client::ConnectHandle,
+    p2p::{Address, PeerScores},
+};
 use chain_network::data::block::{BlockEvent, ChainPullRequest};
-use chain_network::data::{BlockId, BlockIds, Fragment, Gossip, Header};
+use chain_network::data::{BlockId, BlockIds, Fragment, FragmentIds, Gossip, Header};
 use futures::channel::mpsc;
 use futures::lock::{Mutex, MutexLockFuture};
 use futures::prelude::*;
@@ -183,6 +186,11 @@ impl<T> CommHandle<T> {
         }
     }
 
+    /// Returns true if the peer has subscribed to this node.
+    pub fn is_server_subscribed(&self) -> bool {
+        !self.is_client() && self.is_subscribed()
+    }
+
     // Try sending an item to the subscriber.
     // Sending is done as best effort: if the stream buffer is full due to a
     // blockage downstream, a `StreamOverflow` error is returned and
@@ -239,6 +247,7 @@ pub struct PeerComms {
     block_solicitations: CommHandle<BlockIds>,
     chain_pulls: CommHandle<ChainPullRequest>,
     fragments: CommHandle<Fragment>,
+    fragment_solicitations: CommHandle<FragmentIds>,
     gossip: CommHandle<Gossip>,
 }
 
@@ -253,6 +262,12 @@ impl PeerComms {
             || self.gossip.is_client()
     }
 
+    pub fn has_server_subscriptions(&self) -> bool {
+        self.block_announcements.is_server_subscribed()
+            || self.fragments.is_server_subscribed()
+            || self.gossip.is_server_subscribed()
+    }
+
     pub fn update(&mut self, newer: PeerComms) {
         // If there would be a need to tell the old connection that
         // it is replaced in any better way than just dropping all its
@@ -262,6 +277,8 @@ impl PeerComms {
         self.gossip.update(newer.gossip);
         self.block_solicitations.update(newer.block_solicitations);
         self.chain_pulls.update(newer.chain_pulls);
+        self.fragment_solicitations
+            .update(newer.fragment_solicitations);
     }
 
     pub fn clear_pending(&mut self) {
@@ -270,6 +287,7 @@ impl PeerComms {
         self.gossip.clear_pending();
         self.block_solicitations.clear_pending();
         self.chain_pulls.clear_pending();
+        self.fragment_solicitations.clear_pending();
     }
 
     pub fn set_pending_block_announcement(&mut self, header: Header) {
@@ -314,6 +332,10 @@ impl PeerComms {
         self.chain_pulls.subscribe()
     }
 
+    pub fn subscribe_to_fragment_solicitations(&mut self) -> OutboundSubscription<FragmentIds> {
+        self.fragment_solicitations.subscribe()
+    }
+
     pub fn subscribe_to_block_events(&mut self) -> BlockEventSubscription {
         let announce_events: BlockEventAnnounceStream = self
             .block_announcements
@@ -362,7 +384,7 @@ pub struct ConnectOptions {
     /// Gossip to send once the subscription is established
     pub pending_gossip: Option<Gossip>,
     /// The to number of client connections that need to be removed
-    /// prior to connecting.
+    /// prior to connecting, the worst scored ones are removed first.
     pub evict_clients: usize,
 }
 
@@ -482,12 +504,13 @@ impl Peers {
         id: Address,
         handle: ConnectHandle,
         options: ConnectOptions,
+        scores: &PeerScores,
     ) {
         if options.evict_clients != 0 {
             debug!(self.logger, "will evict {} clients", options.evict_clients);
         }
         let mut map = self.inner().await;
-        map.evict_clients(options.evict_clients);
+        map.evict_clients(options.evict_clients, scores);
         let comms = map.add_connecting(id, handle);
         if let Some(header) = options.pending_block_announcement {
             comms.set_pending_block_announcement(header);
@@ -657,9 +680,9 @@ impl Peers {
         }
     }
 
-    pub async fn fetch_blocks(&self, hashes: BlockIds) {
+    pub async fn fetch_blocks(&self, hashes: BlockIds, scores: &PeerScores) {
         let mut map = self.inner().await;
-        if let Some((node_id, comms)) = map.next_peer_for_block_fetch() {
+        if let Some((node_id, comms)) = map.next_peer_for_block_fetch(scores) {
             debug!(self.logger, "fetching blocks from {}", node_id);
             comms
                 .block_solicitations
@@ -702,6 +725,33 @@ impl Peers {
         }
     }
 
+    /// Request the given fragments from the peer, which only works on a
+    /// connection established by this node.
+    pub async fn solicit_fragments(&self, node_id: Address, ids: FragmentIds) {
+        let mut map = self.inner().await;
+        match map.peer_comms(&node_id) {
+            Some(comms) => {
+                debug!(self.logger, "sending fragment solicitation to {}", node_id;
+                    "count" => ids.len());
+                comms
+                    .fragment_solicitations
+                    .try_send(ids)
+                    .unwrap_or_else(|e| {
+                        debug!(
+                            self.logger,
+                            "fragment solicitation from {} failed: {:?}", node_id, e
+                        );
+                    });
+            }
+            None => {
+                debug!(
+                    self.logger,
+                    "peer {} not available to solicit fragments from", node_id
+                );
+            }
+        }
+    }
+
     pub async fn pull_headers(&self, node_id: Address, from: BlockIds, to: BlockId) {
         let mut map = self.inner().await;
         match map.peer_comms(&node_id) {
@@ -736,4 +786,10 @@ impl Peers {
         let map = self.inner().await;
         map.infos()
     }
+
+    /// the addresses of the peers subscribed to this node
+    pub async fn subscriber_addrs(&self) -> Vec<SocketAddr> {
+        let map = self.inner().await;
+        map.subscriber_addrs()
+    }
 }
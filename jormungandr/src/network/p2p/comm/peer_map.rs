@@ -2,10 +2,11 @@ use crate::network::{
     client::ConnectHandle,
     p2p::{
         comm::{PeerComms, PeerInfo, PeerStats},
-        Address,
+        Address, PeerScores,
     },
 };
 use linked_hash_map::LinkedHashMap;
+use std::{cmp::Ordering, net::SocketAddr};
 
 pub struct PeerMap {
     map: LinkedHashMap<Address, PeerData>,
@@ -132,15 +133,31 @@ impl PeerMap {
         })
     }
 
-    pub fn next_peer_for_block_fetch(&mut self) -> Option<(Address, &mut PeerComms)> {
+    /// the established peer with the best score, the most recently
+    /// refreshed one among the peers with the same score
+    pub fn next_peer_for_block_fetch(
+        &mut self,
+        scores: &PeerScores,
+    ) -> Option<(Address, &mut PeerComms)> {
+        let mut best: Option<(f64, Address)> = None;
         let mut iter = self.map.iter_mut();
         while let Some((id, data)) = iter.next_back() {
             match data.update_comm_status() {
-                CommStatus::Established(comms) => return Some((id.clone(), comms)),
+                CommStatus::Established(_) => {
+                    let score = scores.score(id);
+                    if best
+                        .as_ref()
+                        .map_or(true, |(best_score, _)| score > *best_score)
+                    {
+                        best = Some((score, id.clone()));
+                    }
+                }
                 CommStatus::Connecting(_) => {}
             }
         }
-        None
+        let (_, id) = best?;
+        let comms = &mut self.map.get_mut(&id)?.comms;
+        Some((id, comms))
     }
 
     pub fn infos(&self) -> Vec<PeerInfo> {
@@ -155,14 +172,31 @@ impl PeerMap {
             .collect()
     }
 
-    pub fn evict_clients(&mut self, num: usize) {
-        for entry in self
+    pub fn subscriber_addrs(&self) -> Vec<SocketAddr> {
+        use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
+
+        self.map
+            .iter()
+            .filter(|(_, data)| data.comms.has_server_subscriptions())
+            .filter_map(|(id, _)| multiaddr_to_socket_addr(id.multi_address()))
+            .collect()
+    }
+
+    /// evict the given number of peers with client subscriptions, the
+    /// worst scored first
+    pub fn evict_clients(&mut self, num: usize, scores: &PeerScores) {
+        if num == 0 {
+            return;
+        }
+        let mut clients: Vec<(f64, Address)> = self
             .map
-            .entries()
-            .filter(|entry| entry.get().comms.has_client_subscriptions())
-            .take(num)
-        {
-            entry.remove();
+            .iter()
+            .filter(|(_, data)| data.comms.has_client_subscriptions())
+            .map(|(id, _)| (scores.score(id), id.clone()))
+            .collect();
+        clients.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        for (_, id) in clients.into_iter().take(num) {
+            self.map.remove(&id);
         }
     }
 
@@ -0,0 +1,432 @@
+//! Per address limits on the connections of the node.
+//!
+//! The global connection limit does not prevent a single host, or a few
+//! hosts of the same subnet, from taking all the connection slots of the
+//! node. The inbound connections are limited per IP address and per subnet
+//! (/24 for IPv4, /64 for IPv6), both at the TCP level and for the peers
+//! subscribed to the node, and the outbound connections have their own
+//! budget. The addresses of the deny list can neither connect to the node
+//! nor be connected to, unless they are also in the allow list. The
+//! addresses of the allow list are not subject to the per address limits.
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    num::ParseIntError,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// the prefix length of the IPv4 subnets the inbound connections are
+/// counted by
+const IPV4_SUBNET_PREFIX_LEN: u8 = 24;
+
+/// the prefix length of the IPv6 subnets the inbound connections are
+/// counted by
+const IPV6_SUBNET_PREFIX_LEN: u8 = 64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct ConnectionLimitsConfig {
+    /// the number of inbound connections accepted from an IP address
+    #[serde(default)]
+    max_inbound_per_ip: Option<usize>,
+    /// the number of inbound connections accepted from a subnet
+    #[serde(default)]
+    max_inbound_per_subnet: Option<usize>,
+    /// the number of outbound connections open at once
+    #[serde(default)]
+    max_outbound: Option<usize>,
+    /// the addresses not subject to the limits, even if they are denied
+    #[serde(default)]
+    allow: Vec<Cidr>,
+    /// the addresses the node does not connect to nor accept connections from
+    #[serde(default)]
+    deny: Vec<Cidr>,
+}
+
+/// an IP address range in CIDR notation, e.g. `192.168.0.0/16`. A bare
+/// address is the range of this address only.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+#[derive(Debug, Error)]
+pub enum CidrParseError {
+    #[error("invalid IP address")]
+    Address(#[from] std::net::AddrParseError),
+    #[error("invalid prefix length")]
+    PrefixLength(#[from] ParseIntError),
+    #[error("the prefix length {0} is too long for the address")]
+    PrefixTooLong(u8),
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = canonical_ip(ip);
+        match (self.network, ip) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                mask(ip, self.prefix_len) == self.network
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = CidrParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.find('/') {
+            Some(index) => (&s[..index], Some(s[index + 1..].parse::<u8>()?)),
+            None => (s, None),
+        };
+        let address = canonical_ip(address.parse()?);
+        let max_prefix_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = prefix_len.unwrap_or(max_prefix_len);
+        if prefix_len > max_prefix_len {
+            return Err(CidrParseError::PrefixTooLong(prefix_len));
+        }
+        Ok(Cidr {
+            network: mask(address, prefix_len),
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+impl fmt::Debug for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Cidr").field(&self.to_string()).finish()
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+/// the IPv4 address of an IPv4-mapped IPv6 address, as the peers connecting
+/// to a dual stack socket over IPv4 have
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, high, low] => IpAddr::V4(Ipv4Addr::new(
+                (high >> 8) as u8,
+                high as u8,
+                (low >> 8) as u8,
+                low as u8,
+            )),
+            _ => ip,
+        },
+        IpAddr::V4(_) => ip,
+    }
+}
+
+fn mask(ip: IpAddr, prefix_len: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix_len))
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(v4) & mask))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix_len))
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+        }
+    }
+}
+
+fn subnet(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => mask(ip, IPV4_SUBNET_PREFIX_LEN),
+        IpAddr::V6(_) => mask(ip, IPV6_SUBNET_PREFIX_LEN),
+    }
+}
+
+/// the reason a connection is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Refusal {
+    #[error("the address is in the deny list")]
+    Denied,
+    #[error("too many connections from the address")]
+    TooManyFromAddress,
+    #[error("too many connections from the subnet of the address")]
+    TooManyFromSubnet,
+    #[error("the outbound connection budget is exhausted")]
+    OutboundBudgetExhausted,
+}
+
+pub struct ConnectionLimits {
+    max_inbound_per_ip: Option<usize>,
+    max_inbound_per_subnet: Option<usize>,
+    max_outbound: Option<usize>,
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+    counters: Arc<Mutex<Counters>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    inbound_per_ip: HashMap<IpAddr, usize>,
+    inbound_per_subnet: HashMap<IpAddr, usize>,
+    outbound: usize,
+}
+
+/// A connection accounted in the limits, the connection is released when
+/// the permit is dropped.
+pub struct ConnectionPermit {
+    counters: Arc<Mutex<Counters>>,
+    kind: PermitKind,
+}
+
+enum PermitKind {
+    /// the connection is not accounted, the address is in the allow list
+    Exempt,
+    Inbound(IpAddr),
+    Outbound,
+}
+
+impl ConnectionLimits {
+    pub fn new(config: &ConnectionLimitsConfig) -> Self {
+        ConnectionLimits {
+            max_inbound_per_ip: config.max_inbound_per_ip,
+            max_inbound_per_subnet: config.max_inbound_per_subnet,
+            max_outbound: config.max_outbound,
+            allow: config.allow.clone(),
+            deny: config.deny.clone(),
+            counters: Arc::new(Mutex::new(Counters::default())),
+        }
+    }
+
+    fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    fn is_denied(&self, ip: IpAddr) -> bool {
+        self.deny.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    fn permit(&self, kind: PermitKind) -> ConnectionPermit {
+        ConnectionPermit {
+            counters: self.counters.clone(),
+            kind,
+        }
+    }
+
+    /// Account for a connection accepted from the given address, the
+    /// connection must be closed if it is refused.
+    pub fn admit_inbound(&self, addr: SocketAddr) -> Result<ConnectionPermit, Refusal> {
+        let ip = canonical_ip(addr.ip());
+        if self.is_allowed(ip) {
+            return Ok(self.permit(PermitKind::Exempt));
+        }
+        if self.is_denied(ip) {
+            return Err(Refusal::Denied);
+        }
+        let mut counters = self.counters.lock().unwrap();
+        let from_ip = counters.inbound_per_ip.get(&ip).cloned().unwrap_or(0);
+        if self.max_inbound_per_ip.map_or(false, |max| from_ip >= max) {
+            return Err(Refusal::TooManyFromAddress);
+        }
+        let from_subnet = counters
+            .inbound_per_subnet
+            .get(&subnet(ip))
+            .cloned()
+            .unwrap_or(0);
+        if self
+            .max_inbound_per_subnet
+            .map_or(false, |max| from_subnet >= max)
+        {
+            return Err(Refusal::TooManyFromSubnet);
+        }
+        *counters.inbound_per_ip.entry(ip).or_insert(0) += 1;
+        *counters.inbound_per_subnet.entry(subnet(ip)).or_insert(0) += 1;
+        Ok(self.permit(PermitKind::Inbound(ip)))
+    }
+
+    /// Check whether the given peer may subscribe to the node, given the
+    /// addresses of the other peers subscribed to the node. A single
+    /// connection is not enough to tell, as the peers subscribed over the
+    /// connections already closed are kept until they are evicted.
+    pub fn check_subscriber(
+        &self,
+        subscriber: SocketAddr,
+        inbound_peers: &[SocketAddr],
+    ) -> Result<(), Refusal> {
+        let ip = canonical_ip(subscriber.ip());
+        if self.is_allowed(ip) {
+            return Ok(());
+        }
+        if self.is_denied(ip) {
+            return Err(Refusal::Denied);
+        }
+        let others = inbound_peers
+            .iter()
+            .filter(|peer| **peer != subscriber)
+            .map(|peer| canonical_ip(peer.ip()));
+        let (from_ip, from_subnet) = others.fold((0, 0), |(from_ip, from_subnet), other| {
+            (
+                from_ip + (other == ip) as usize,
+                from_subnet + (subnet(other) == subnet(ip)) as usize,
+            )
+        });
+        if self.max_inbound_per_ip.map_or(false, |max| from_ip >= max) {
+            return Err(Refusal::TooManyFromAddress);
+        }
+        if self
+            .max_inbound_per_subnet
+            .map_or(false, |max| from_subnet >= max)
+        {
+            return Err(Refusal::TooManyFromSubnet);
+        }
+        Ok(())
+    }
+
+    /// Account for a connection about to be opened to the given address,
+    /// the connection must not be opened if it is refused.
+    pub fn acquire_outbound(&self, addr: SocketAddr) -> Result<ConnectionPermit, Refusal> {
+        let ip = canonical_ip(addr.ip());
+        if self.is_allowed(ip) {
+            return Ok(self.permit(PermitKind::Exempt));
+        }
+        if self.is_denied(ip) {
+            return Err(Refusal::Denied);
+        }
+        let mut counters = self.counters.lock().unwrap();
+        if self
+            .max_outbound
+            .map_or(false, |max| counters.outbound >= max)
+        {
+            return Err(Refusal::OutboundBudgetExhausted);
+        }
+        counters.outbound += 1;
+        Ok(self.permit(PermitKind::Outbound))
+    }
+}
+
+fn release(counter: &mut HashMap<IpAddr, usize>, key: IpAddr) {
+    if let Some(count) = counter.get_mut(&key) {
+        *count -= 1;
+        if *count == 0 {
+            counter.remove(&key);
+        }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut counters = self.counters.lock().unwrap();
+        match self.kind {
+            PermitKind::Exempt => {}
+            PermitKind::Inbound(ip) => {
+                release(&mut counters.inbound_per_ip, ip);
+                release(&mut counters.inbound_per_subnet, subnet(ip));
+            }
+            PermitKind::Outbound => counters.outbound -= 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cidr_contains() {
+        let cidr: Cidr = "192.168.1.17/24".parse().unwrap();
+        assert_eq!(cidr.to_string(), "192.168.1.0/24");
+        assert!(cidr.contains("192.168.1.200".parse().unwrap()));
+        assert!(cidr.contains("::ffff:192.168.1.1".parse().unwrap()));
+        assert!(!cidr.contains("192.168.2.1".parse().unwrap()));
+        assert!(!cidr.contains("::1".parse().unwrap()));
+
+        let host: Cidr = "2001:db8::1".parse().unwrap();
+        assert!(host.contains("2001:db8::1".parse().unwrap()));
+        assert!(!host.contains("2001:db8::2".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("8.8.8.8".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn inbound_limits() {
+        let config = ConnectionLimitsConfig {
+            max_inbound_per_ip: Some(2),
+            max_inbound_per_subnet: Some(3),
+            allow: vec!["10.0.0.9".parse().unwrap()],
+            deny: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+        let limits = ConnectionLimits::new(&config);
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+
+        let first = limits.admit_inbound(addr("1.2.3.4:1000")).unwrap();
+        let _second = limits.admit_inbound(addr("1.2.3.4:1001")).unwrap();
+        assert_eq!(
+            limits.admit_inbound(addr("1.2.3.4:1002")).err(),
+            Some(Refusal::TooManyFromAddress)
+        );
+        let _third = limits.admit_inbound(addr("1.2.3.5:1000")).unwrap();
+        assert_eq!(
+            limits.admit_inbound(addr("1.2.3.6:1000")).err(),
+            Some(Refusal::TooManyFromSubnet)
+        );
+        drop(first);
+        assert!(limits.admit_inbound(addr("1.2.3.6:1000")).is_ok());
+
+        assert_eq!(
+            limits.admit_inbound(addr("10.1.2.3:1000")).err(),
+            Some(Refusal::Denied)
+        );
+        for port in 0..10 {
+            assert!(limits
+                .admit_inbound(addr(&format!("10.0.0.9:{}", port)))
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn outbound_budget() {
+        let config = ConnectionLimitsConfig {
+            max_outbound: Some(1),
+            ..Default::default()
+        };
+        let limits = ConnectionLimits::new(&config);
+        let addr = "1.2.3.4:3000".parse().unwrap();
+
+        let permit = limits.acquire_outbound(addr).unwrap();
+        assert_eq!(
+            limits.acquire_outbound(addr).err(),
+            Some(Refusal::OutboundBudgetExhausted)
+        );
+        drop(permit);
+        assert!(limits.acquire_outbound(addr).is_ok());
+    }
+}
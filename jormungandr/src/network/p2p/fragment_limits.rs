@@ -0,0 +1,348 @@
+//! Per-peer accounting of the fragments received from the network.
+//!
+//! Every peer gets a token bucket limiting the rate at which it can push
+//! fragments to us, the extra fragments are dropped. The fragments are
+//! counted by outcome (accepted, duplicate, rejected, invalid or dropped)
+//! over a sliding window, a peer sending too many invalid fragments or
+//! going over its rate too often in a window is reported as misbehaving so
+//! the p2p policy can quarantine it.
+//!
+//! Duplicates and fragments rejected against the ledger are not held
+//! against a peer: they are expected from honest peers relaying the same
+//! fragments as the other peers, or relaying fragments that became invalid
+//! with the latest blocks.
+
+use jormungandr_lib::time::Duration;
+use lru::LruCache;
+use poldercast::Address;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration as StdDuration, Instant};
+
+/// default sustained rate is 100 fragments per second
+const DEFAULT_RATE: u32 = 100;
+
+/// default burst is 1_000 fragments
+const DEFAULT_BURST: u32 = 1_000;
+
+/// default number of invalid fragments tolerated in a window is 100
+const DEFAULT_MAX_INVALID: u32 = 100;
+
+/// default number of rate limited fragments tolerated in a window is 10_000
+const DEFAULT_MAX_RATE_LIMITED: u32 = 10_000;
+
+/// default accounting window is 10min
+const DEFAULT_WINDOW: StdDuration = StdDuration::from_secs(10 * 60);
+
+/// default number of peer records is 1_024
+const DEFAULT_MAX_NUM_RECORDS: usize = 1_024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct FragmentLimitsConfig {
+    /// the number of fragments per second a peer may send on average
+    #[serde(default)]
+    rate: Option<u32>,
+    /// the number of fragments a peer may send at once
+    #[serde(default)]
+    burst: Option<u32>,
+    /// the number of invalid fragments tolerated from a peer in a window
+    #[serde(default)]
+    max_invalid: Option<u32>,
+    /// the number of fragments over the rate tolerated from a peer in a window
+    #[serde(default)]
+    max_rate_limited: Option<u32>,
+    /// the duration over which the fragments of a peer are counted
+    #[serde(default)]
+    window: Option<Duration>,
+    #[serde(default)]
+    max_num_records: Option<usize>,
+}
+
+/// the result of the processing of a fragment received from a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentOutcome {
+    Accepted,
+    /// the fragment is already known
+    Duplicate,
+    /// the fragment cannot be applied to the ledger of the tip
+    Rejected,
+    /// the fragment cannot be decoded or its witnesses do not match
+    Invalid,
+}
+
+/// what to do with a fragment received from a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// process the fragment
+    Accept,
+    /// the peer is over its rate, drop the fragment
+    Drop,
+    /// the peer went over one of the thresholds, drop the fragment and
+    /// report the peer
+    Report,
+}
+
+/// fragment counters of a peer over the current window
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentCounters {
+    pub accepted: u32,
+    pub duplicates: u32,
+    pub rejected: u32,
+    pub invalid: u32,
+    pub rate_limited: u32,
+}
+
+pub struct FragmentLimits {
+    rate: f64,
+    burst: f64,
+    max_invalid: u32,
+    max_rate_limited: u32,
+    window: StdDuration,
+    records: Mutex<LruCache<Address, Record>>,
+}
+
+struct Record {
+    tokens: f64,
+    last_refill: Instant,
+    window_start: Instant,
+    counters: FragmentCounters,
+}
+
+impl FragmentLimits {
+    pub fn new(config: &FragmentLimitsConfig) -> Self {
+        FragmentLimits {
+            rate: config.rate.unwrap_or(DEFAULT_RATE) as f64,
+            burst: config.burst.unwrap_or(DEFAULT_BURST) as f64,
+            max_invalid: config.max_invalid.unwrap_or(DEFAULT_MAX_INVALID),
+            max_rate_limited: config.max_rate_limited.unwrap_or(DEFAULT_MAX_RATE_LIMITED),
+            window: config.window.map(Into::into).unwrap_or(DEFAULT_WINDOW),
+            records: Mutex::new(LruCache::new(
+                config.max_num_records.unwrap_or(DEFAULT_MAX_NUM_RECORDS),
+            )),
+        }
+    }
+
+    /// Account for a fragment received from the given peer, before it is
+    /// processed.
+    pub fn on_received(&self, peer: &Address) -> Verdict {
+        self.received_at(peer, Instant::now())
+    }
+
+    fn received_at(&self, peer: &Address, now: Instant) -> Verdict {
+        self.with_record(peer, now, |limits, record| {
+            let elapsed = now
+                .saturating_duration_since(record.last_refill)
+                .as_secs_f64();
+            record.tokens = (record.tokens + elapsed * limits.rate).min(limits.burst);
+            record.last_refill = now;
+
+            if record.tokens >= 1.0 {
+                record.tokens -= 1.0;
+                Verdict::Accept
+            } else {
+                record.counters.rate_limited = record.counters.rate_limited.saturating_add(1);
+                if limits.exceeded(&record.counters) {
+                    Verdict::Report
+                } else {
+                    Verdict::Drop
+                }
+            }
+        })
+    }
+
+    /// Account for the outcome of the processing of fragments received
+    /// from the given peer. Returns `true` if the peer went over one of the
+    /// thresholds and should be reported.
+    pub fn on_processed(
+        &self,
+        peer: &Address,
+        outcomes: impl IntoIterator<Item = FragmentOutcome>,
+    ) -> bool {
+        self.processed_at(peer, outcomes, Instant::now())
+    }
+
+    fn processed_at(
+        &self,
+        peer: &Address,
+        outcomes: impl IntoIterator<Item = FragmentOutcome>,
+        now: Instant,
+    ) -> bool {
+        self.with_record(peer, now, |limits, record| {
+            for outcome in outcomes {
+                let counter = match outcome {
+                    FragmentOutcome::Accepted => &mut record.counters.accepted,
+                    FragmentOutcome::Duplicate => &mut record.counters.duplicates,
+                    FragmentOutcome::Rejected => &mut record.counters.rejected,
+                    FragmentOutcome::Invalid => &mut record.counters.invalid,
+                };
+                *counter = counter.saturating_add(1);
+            }
+            limits.exceeded(&record.counters)
+        })
+    }
+
+    /// Start a new window for the given peer, to be called once the peer
+    /// has been reported so it is not reported again for the same
+    /// fragments. The peer keeps its remaining tokens, it does not get a
+    /// full burst back.
+    /// Returns the counters of the peer in the window that ended.
+    pub fn on_reported(&self, peer: &Address) -> Option<FragmentCounters> {
+        let now = Instant::now();
+        self.records.lock().unwrap().get_mut(peer).map(|record| {
+            record.window_start = now;
+            std::mem::take(&mut record.counters)
+        })
+    }
+
+    fn exceeded(&self, counters: &FragmentCounters) -> bool {
+        counters.invalid > self.max_invalid || counters.rate_limited > self.max_rate_limited
+    }
+
+    fn with_record<F, R>(&self, peer: &Address, now: Instant, f: F) -> R
+    where
+        F: FnOnce(&Self, &mut Record) -> R,
+    {
+        let mut records = self.records.lock().unwrap();
+        if !records.contains(peer) {
+            records.put(
+                peer.clone(),
+                Record {
+                    tokens: self.burst,
+                    last_refill: now,
+                    window_start: now,
+                    counters: FragmentCounters::default(),
+                },
+            );
+        }
+        let record = records.get_mut(peer).unwrap();
+        if now.saturating_duration_since(record.window_start) >= self.window {
+            record.window_start = now;
+            record.counters = FragmentCounters::default();
+        }
+        f(self, record)
+    }
+}
+
+impl Default for FragmentLimitsConfig {
+    fn default() -> Self {
+        Self {
+            rate: Some(DEFAULT_RATE),
+            burst: Some(DEFAULT_BURST),
+            max_invalid: Some(DEFAULT_MAX_INVALID),
+            max_rate_limited: Some(DEFAULT_MAX_RATE_LIMITED),
+            window: Some(Duration::from(DEFAULT_WINDOW)),
+            max_num_records: Some(DEFAULT_MAX_NUM_RECORDS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn peer(n: u8) -> Address {
+        Address::new(Ipv4Addr::new(10, 0, 0, n)).ok().unwrap()
+    }
+
+    fn limits(rate: u32, burst: u32, max_invalid: u32, max_rate_limited: u32) -> FragmentLimits {
+        FragmentLimits::new(&FragmentLimitsConfig {
+            rate: Some(rate),
+            burst: Some(burst),
+            max_invalid: Some(max_invalid),
+            max_rate_limited: Some(max_rate_limited),
+            window: Some(Duration::from(StdDuration::from_secs(60))),
+            max_num_records: Some(16),
+        })
+    }
+
+    #[test]
+    fn burst_then_rate() {
+        let limits = limits(10, 5, 100, 100);
+        let start = Instant::now();
+        for _ in 0..5 {
+            assert_eq!(limits.received_at(&peer(1), start), Verdict::Accept);
+        }
+        assert_eq!(limits.received_at(&peer(1), start), Verdict::Drop);
+        // the other peers have their own bucket
+        assert_eq!(limits.received_at(&peer(2), start), Verdict::Accept);
+
+        // 10 fragments per second, one token every 100ms
+        let later = start + StdDuration::from_millis(250);
+        assert_eq!(limits.received_at(&peer(1), later), Verdict::Accept);
+        assert_eq!(limits.received_at(&peer(1), later), Verdict::Accept);
+        assert_eq!(limits.received_at(&peer(1), later), Verdict::Drop);
+
+        // the bucket does not fill over the burst
+        let much_later = start + StdDuration::from_secs(10);
+        for _ in 0..5 {
+            assert_eq!(limits.received_at(&peer(1), much_later), Verdict::Accept);
+        }
+        assert_eq!(limits.received_at(&peer(1), much_later), Verdict::Drop);
+    }
+
+    #[test]
+    fn going_over_the_rate_too_often_is_reported() {
+        let limits = limits(1, 1, 100, 2);
+        let now = Instant::now();
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Accept);
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Drop);
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Drop);
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Report);
+    }
+
+    #[test]
+    fn only_invalid_fragments_are_held_against_a_peer() {
+        let limits = limits(100, 100, 2, 100);
+        let now = Instant::now();
+        let harmless = vec![
+            FragmentOutcome::Accepted,
+            FragmentOutcome::Duplicate,
+            FragmentOutcome::Rejected,
+        ];
+        for _ in 0..100 {
+            assert!(!limits.processed_at(&peer(1), harmless.clone(), now));
+        }
+        let invalid = vec![FragmentOutcome::Invalid, FragmentOutcome::Invalid];
+        assert!(!limits.processed_at(&peer(1), invalid, now));
+        assert!(limits.processed_at(&peer(1), Some(FragmentOutcome::Invalid), now));
+
+        let counters = limits.on_reported(&peer(1)).unwrap();
+        assert_eq!(
+            counters,
+            FragmentCounters {
+                accepted: 100,
+                duplicates: 100,
+                rejected: 100,
+                invalid: 3,
+                rate_limited: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn counters_are_reset_with_the_window() {
+        let limits = limits(100, 100, 1, 100);
+        let start = Instant::now();
+        assert!(!limits.processed_at(&peer(1), Some(FragmentOutcome::Invalid), start));
+        let next_window = start + StdDuration::from_secs(60);
+        assert!(!limits.processed_at(&peer(1), Some(FragmentOutcome::Invalid), next_window));
+        assert!(limits.processed_at(&peer(1), Some(FragmentOutcome::Invalid), next_window));
+    }
+
+    #[test]
+    fn reported_peer_keeps_its_tokens() {
+        let limits = limits(1, 2, 100, 100);
+        let now = Instant::now();
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Accept);
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Accept);
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Drop);
+
+        let counters = limits.on_reported(&peer(1)).unwrap();
+        assert_eq!(counters.rate_limited, 1);
+        // the bucket is still empty after the report
+        assert_eq!(limits.received_at(&peer(1), now), Verdict::Drop);
+        assert!(limits.on_reported(&peer(2)).is_none());
+    }
+}
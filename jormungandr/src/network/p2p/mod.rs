@@ -1,11 +1,24 @@
+pub mod address_book;
 pub mod comm;
+mod connection_limits;
+mod fragment_limits;
 mod gossip;
 pub mod layers;
 mod policy;
+mod scoring;
 mod topology;
 
+pub use self::connection_limits::{
+    Cidr, ConnectionLimits, ConnectionLimitsConfig, ConnectionPermit, Refusal as ConnectionRefusal,
+};
+pub use self::fragment_limits::{
+    FragmentLimits, FragmentLimitsConfig, FragmentOutcome, Verdict as FragmentVerdict,
+};
 pub use self::gossip::{Gossip, Gossips, Peer, Peers};
 pub use self::policy::{Policy, PolicyConfig};
+pub use self::scoring::{
+    BlockOutcome, PeerScore, PeerScores, PeerScoringConfig, PeerScoringConfigError, ScoreCounters,
+};
 pub use self::topology::P2pTopology;
 
 pub use poldercast::Address;
@@ -0,0 +1,434 @@
+//! Scoring of the peers by the blocks they deliver.
+//!
+//! The policy only reacts to strikes, it cannot tell a peer delivering the
+//! new blocks first from a peer lagging behind. Every peer gets a score
+//! between 0 and 1 from:
+//!
+//! * the delay of its block announcements after the first announcement of
+//!   the same block by any peer;
+//! * the share of the blocks and headers it sent that passed validation,
+//!   out of the ones that were new to the node or invalid.
+//!
+//! The counters decay over time, so the score reflects the recent behaviour
+//! of the peer. The peers with the best score are preferred to fetch blocks
+//! from and kept connected to, and a peer sending invalid data while its
+//! score is below a threshold is reported to the policy and quarantined.
+
+use crate::blockcfg::HeaderHash;
+use jormungandr_lib::time::Duration;
+use lru::LruCache;
+use poldercast::Address;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration as StdDuration, Instant};
+use thiserror::Error;
+
+/// default score under which a peer sending invalid data is reported
+const DEFAULT_MIN_SCORE: f64 = 0.25;
+
+/// default half life of the counters is 1h
+const DEFAULT_HALF_LIFE: StdDuration = StdDuration::from_secs(3600);
+
+/// default number of peer records is 1_024
+const DEFAULT_MAX_NUM_RECORDS: usize = 1_024;
+
+/// the number of recently announced blocks to remember the first
+/// announcement of
+const MAX_NUM_ANNOUNCED_BLOCKS: usize = 256;
+
+/// the announcements coming later than this after the first one are
+/// accounted with this delay
+const MAX_ANNOUNCEMENT_DELAY: StdDuration = StdDuration::from_secs(60);
+
+/// the average announcement delay at which the speed of a peer is halved
+const ANNOUNCEMENT_DELAY_SCALE: f64 = 2.0;
+
+/// the weight of the last announcement in the average delay
+const ANNOUNCEMENT_DELAY_WEIGHT: f64 = 0.2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct PeerScoringConfig {
+    /// the score under which a peer sending invalid data is reported
+    #[serde(default)]
+    min_score: Option<f64>,
+    /// the duration after which the counters of a peer count for half
+    #[serde(default)]
+    half_life: Option<Duration>,
+    #[serde(default)]
+    max_num_records: Option<usize>,
+}
+
+#[derive(Debug, Error)]
+pub enum PeerScoringConfigError {
+    #[error("`half_life` must not be zero")]
+    ZeroHalfLife,
+    #[error("`max_num_records` must not be zero")]
+    ZeroMaxNumRecords,
+}
+
+impl PeerScoringConfig {
+    /// check the values the scores cannot be computed with
+    pub fn validate(&self) -> Result<(), PeerScoringConfigError> {
+        if let Some(half_life) = self.half_life {
+            if StdDuration::from(half_life) == StdDuration::from_secs(0) {
+                return Err(PeerScoringConfigError::ZeroHalfLife);
+            }
+        }
+        if self.max_num_records == Some(0) {
+            return Err(PeerScoringConfigError::ZeroMaxNumRecords);
+        }
+        Ok(())
+    }
+}
+
+/// the result of the validation of blocks or headers received from a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOutcome {
+    /// the given number of blocks or headers were new and valid
+    Useful(u32),
+    /// a block or a header failed validation
+    Invalid,
+}
+
+/// decayed counters of a peer
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScoreCounters {
+    /// blocks and headers new to the node that passed validation
+    pub useful: f64,
+    /// blocks and headers that failed validation
+    pub invalid: f64,
+    /// blocks announced
+    pub announcements: f64,
+    /// blocks announced before any other peer
+    pub first_announcements: f64,
+}
+
+/// the score of a peer and what it is computed from
+#[derive(Debug, Clone)]
+pub struct PeerScore {
+    pub address: Address,
+    pub score: f64,
+    pub counters: ScoreCounters,
+    /// the average delay of the announcements of the peer after the first
+    /// announcement of the same block, if it announced any block
+    pub announcement_delay: Option<StdDuration>,
+}
+
+pub struct PeerScores {
+    min_score: f64,
+    half_life: f64,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    records: LruCache<Address, Record>,
+    first_announcements: LruCache<HeaderHash, Instant>,
+}
+
+struct Record {
+    last_decay: Instant,
+    counters: ScoreCounters,
+    /// the moving average of the announcement delay, in seconds
+    announcement_delay: Option<f64>,
+}
+
+impl Record {
+    fn new(now: Instant) -> Self {
+        Record {
+            last_decay: now,
+            counters: ScoreCounters::default(),
+            announcement_delay: None,
+        }
+    }
+
+    fn decay(&mut self, now: Instant, half_life: f64) {
+        let elapsed = now.saturating_duration_since(self.last_decay).as_secs_f64();
+        let factor = 0.5f64.powf(elapsed / half_life);
+        self.counters.useful *= factor;
+        self.counters.invalid *= factor;
+        self.counters.announcements *= factor;
+        self.counters.first_announcements *= factor;
+        self.last_decay = now;
+    }
+
+    /// the share of valid deliveries, smoothed for the peers with few
+    /// records, weighed by the speed of the announcements. The speed only
+    /// weighs half of the score, so a slow but honest peer is not
+    /// quarantined.
+    fn score(&self) -> f64 {
+        let reliability =
+            (self.counters.useful + 1.0) / (self.counters.useful + self.counters.invalid + 2.0);
+        let speed = self
+            .announcement_delay
+            .map_or(1.0, |delay| 1.0 / (1.0 + delay / ANNOUNCEMENT_DELAY_SCALE));
+        reliability * (0.5 + 0.5 * speed)
+    }
+}
+
+impl PeerScores {
+    pub fn new(config: &PeerScoringConfig) -> Self {
+        PeerScores {
+            min_score: config.min_score.unwrap_or(DEFAULT_MIN_SCORE),
+            half_life: config
+                .half_life
+                .map(Into::into)
+                .unwrap_or(DEFAULT_HALF_LIFE)
+                .as_secs_f64(),
+            inner: Mutex::new(Inner {
+                records: LruCache::new(config.max_num_records.unwrap_or(DEFAULT_MAX_NUM_RECORDS)),
+                first_announcements: LruCache::new(MAX_NUM_ANNOUNCED_BLOCKS),
+            }),
+        }
+    }
+
+    /// Account for a block announced by the given peer.
+    pub fn on_announcement(&self, peer: &Address, block: HeaderHash) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        let (delay, first) = match inner.first_announcements.get(&block) {
+            Some(first_seen) => (
+                now.saturating_duration_since(*first_seen)
+                    .min(MAX_ANNOUNCEMENT_DELAY),
+                false,
+            ),
+            None => {
+                inner.first_announcements.put(block, now);
+                (StdDuration::from_secs(0), true)
+            }
+        };
+        let delay = delay.as_secs_f64();
+        let record = inner.record(peer, now, self.half_life);
+        record.counters.announcements += 1.0;
+        if first {
+            record.counters.first_announcements += 1.0;
+        }
+        record.announcement_delay = Some(match record.announcement_delay {
+            Some(average) => average + ANNOUNCEMENT_DELAY_WEIGHT * (delay - average),
+            None => delay,
+        });
+    }
+
+    /// Account for the validation of blocks or headers received from the
+    /// given peer. Returns `true` if the peer sent invalid data while its
+    /// score is below the threshold and should be reported.
+    pub fn on_block_outcome(&self, peer: &Address, outcome: BlockOutcome) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let record = inner.record(peer, Instant::now(), self.half_life);
+        match outcome {
+            BlockOutcome::Useful(count) => {
+                record.counters.useful += f64::from(count);
+                false
+            }
+            BlockOutcome::Invalid => {
+                record.counters.invalid += 1.0;
+                record.score() < self.min_score
+            }
+        }
+    }
+
+    /// the score of the given peer, a peer with no record gets the score
+    /// of a peer that did not deliver anything yet
+    pub fn score(&self, peer: &Address) -> f64 {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        match inner.records.get_mut(peer) {
+            Some(record) => {
+                record.decay(now, self.half_life);
+                record.score()
+            }
+            None => Record::new(now).score(),
+        }
+    }
+
+    /// sort the given peers by score, the best first
+    pub fn rank(&self, peers: &mut [Address]) {
+        let mut scored: Vec<(f64, Address)> = peers
+            .iter()
+            .map(|peer| (self.score(peer), peer.clone()))
+            .collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        for (peer, (_, scored)) in peers.iter_mut().zip(scored) {
+            *peer = scored;
+        }
+    }
+
+    /// Forget about the given peer, to be called once the peer has been
+    /// reported so it starts afresh after the quarantine.
+    pub fn forget(&self, peer: &Address) -> Option<PeerScore> {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .records
+            .pop(peer)
+            .map(|record| peer_score(peer.clone(), &record))
+    }
+
+    /// the scores of all the peers with a record, the best first
+    pub fn scores(&self) -> Vec<PeerScore> {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        let mut scores: Vec<PeerScore> = inner
+            .records
+            .iter_mut()
+            .map(|(peer, record)| {
+                record.decay(now, self.half_life);
+                peer_score(peer.clone(), record)
+            })
+            .collect();
+        scores.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+        scores
+    }
+}
+
+impl Inner {
+    fn record(&mut self, peer: &Address, now: Instant, half_life: f64) -> &mut Record {
+        if !self.records.contains(peer) {
+            self.records.put(peer.clone(), Record::new(now));
+        }
+        let record = self.records.get_mut(peer).unwrap();
+        record.decay(now, half_life);
+        record
+    }
+}
+
+fn peer_score(address: Address, record: &Record) -> PeerScore {
+    PeerScore {
+        address,
+        score: record.score(),
+        counters: record.counters,
+        announcement_delay: record.announcement_delay.map(StdDuration::from_secs_f64),
+    }
+}
+
+impl Default for PeerScoringConfig {
+    fn default() -> Self {
+        Self {
+            min_score: Some(DEFAULT_MIN_SCORE),
+            half_life: Some(Duration::from(DEFAULT_HALF_LIFE)),
+            max_num_records: Some(DEFAULT_MAX_NUM_RECORDS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_LIFE: f64 = 3600.0;
+
+    fn address(port: u16) -> Address {
+        format!("/ip4/10.0.0.1/tcp/{}", port).parse().unwrap()
+    }
+
+    fn scores(min_score: f64) -> PeerScores {
+        PeerScores::new(&PeerScoringConfig {
+            min_score: Some(min_score),
+            ..PeerScoringConfig::default()
+        })
+    }
+
+    #[test]
+    fn peer_without_record_has_an_even_score() {
+        let scores = scores(DEFAULT_MIN_SCORE);
+        assert_eq!(scores.score(&address(3000)), 0.5);
+        assert!(scores.scores().is_empty());
+    }
+
+    #[test]
+    fn score_weighs_reliability_by_speed() {
+        let mut record = Record::new(Instant::now());
+        record.counters.useful = 8.0;
+        assert_eq!(record.score(), 0.9);
+        record.counters.invalid = 2.0;
+        assert_eq!(record.score(), 0.75);
+        // announcing at the scale delay halves the speed, which only
+        // weighs half of the score
+        record.announcement_delay = Some(ANNOUNCEMENT_DELAY_SCALE);
+        assert_eq!(record.score(), 0.75 * 0.75);
+    }
+
+    #[test]
+    fn counters_are_halved_every_half_life() {
+        let start = Instant::now();
+        let mut record = Record::new(start);
+        record.counters = ScoreCounters {
+            useful: 8.0,
+            invalid: 4.0,
+            announcements: 2.0,
+            first_announcements: 1.0,
+        };
+        record.decay(start + StdDuration::from_secs(3600), HALF_LIFE);
+        assert_eq!(
+            record.counters,
+            ScoreCounters {
+                useful: 4.0,
+                invalid: 2.0,
+                announcements: 1.0,
+                first_announcements: 0.5,
+            }
+        );
+        record.decay(start + StdDuration::from_secs(3 * 3600), HALF_LIFE);
+        assert_eq!(record.counters.useful, 1.0);
+        // the time elapsed is not accounted twice
+        record.decay(start + StdDuration::from_secs(3 * 3600), HALF_LIFE);
+        assert_eq!(record.counters.useful, 1.0);
+    }
+
+    #[test]
+    fn invalid_data_is_reported_below_the_min_score() {
+        let scores = scores(0.3);
+        let peer = address(3000);
+        // 1/3 of reliability is still above the threshold
+        assert!(!scores.on_block_outcome(&peer, BlockOutcome::Invalid));
+        // 1/4 is below
+        assert!(scores.on_block_outcome(&peer, BlockOutcome::Invalid));
+    }
+
+    #[test]
+    fn useful_data_is_never_reported() {
+        let scores = scores(0.99);
+        let peer = address(3000);
+        assert!(!scores.on_block_outcome(&peer, BlockOutcome::Useful(1)));
+        assert!(scores.score(&peer) < 0.99);
+        assert!(!scores.on_block_outcome(&peer, BlockOutcome::Useful(1)));
+    }
+
+    #[test]
+    fn peers_delivering_invalid_data_are_ranked_last() {
+        let scores = scores(0.0);
+        let honest = address(3000);
+        let dishonest = address(3001);
+        let unknown = address(3002);
+        scores.on_block_outcome(&honest, BlockOutcome::Useful(10));
+        scores.on_block_outcome(&dishonest, BlockOutcome::Invalid);
+        let mut peers = vec![dishonest.clone(), unknown.clone(), honest.clone()];
+        scores.rank(&mut peers);
+        assert_eq!(peers, vec![honest, unknown, dishonest.clone()]);
+
+        let forgotten = scores.forget(&dishonest).unwrap();
+        assert_eq!(forgotten.counters.invalid.round(), 1.0);
+        assert_eq!(scores.score(&dishonest), 0.5);
+    }
+
+    #[test]
+    fn zero_values_are_refused() {
+        assert!(PeerScoringConfig::default().validate().is_ok());
+        let config = PeerScoringConfig {
+            half_life: Some(Duration::from(StdDuration::from_secs(0))),
+            ..PeerScoringConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(PeerScoringConfigError::ZeroHalfLife)
+        ));
+        let config = PeerScoringConfig {
+            max_num_records: Some(0),
+            ..PeerScoringConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(PeerScoringConfigError::ZeroMaxNumRecords)
+        ));
+    }
+}
@@ -2,11 +2,11 @@ use super::{
     buffer_sizes,
     convert::{self, Decode, Encode, ResponseStream},
     p2p::comm::{BlockEventSubscription, FragmentSubscription, GossipSubscription},
-    p2p::Address,
+    p2p::{Address, ConnectionRefusal},
     subscription, Channels, GlobalStateR,
 };
 use crate::blockcfg as app_data;
-use crate::intercom::{self, BlockMsg, ClientMsg};
+use crate::intercom::{self, BlockMsg, ClientMsg, TransactionMsg};
 use crate::utils::async_msg::MessageBox;
 use chain_network::core::server::{BlockService, FragmentService, GossipService, Node, PushStream};
 use chain_network::data::{
@@ -20,6 +20,11 @@ use futures::try_join;
 use slog::Logger;
 
 use std::convert::TryFrom;
+use std::net::SocketAddr;
+
+/// maximum number of fragments served in response to a GetFragments request,
+/// the extra identifiers are ignored
+const MAX_FRAGMENTS_PER_REQUEST: usize = 256;
 
 #[derive(Clone)]
 pub struct NodeService {
@@ -49,6 +54,23 @@ impl NodeService {
         self.logger
             .new(o!("peer" => subscriber.to_string(), "stream" => stream_name))
     }
+
+    /// refuse the subscriptions from the addresses over the connection
+    /// limits or in the deny list
+    async fn admit_subscriber(&self, addr: SocketAddr, logger: &Logger) -> Result<(), Error> {
+        let subscribers = self.global_state.peers.subscriber_addrs().await;
+        self.global_state
+            .connection_limits
+            .check_subscriber(addr, &subscribers)
+            .map_err(|refusal| {
+                info!(logger, "refusing subscription"; "reason" => %refusal);
+                let code = match refusal {
+                    ConnectionRefusal::Denied => net_error::Code::PermissionDenied,
+                    _ => net_error::Code::ResourceExhausted,
+                };
+                Error::new(code, refusal)
+            })
+    }
 }
 
 impl Node for NodeService {
@@ -166,7 +188,7 @@ impl BlockService for NodeService {
         let (handle, sink, reply) =
             intercom::stream_request(buffer_sizes::inbound::HEADERS, logger.clone());
         let block_box = self.channels.block_box.clone();
-        send_message(block_box, BlockMsg::ChainHeaders(handle), logger).await?;
+        send_message(block_box, BlockMsg::ChainHeaders(handle, None), logger).await?;
         try_join!(
             stream
                 .and_then(|header| async { header.decode() })
@@ -181,7 +203,7 @@ impl BlockService for NodeService {
         let (handle, sink, reply) =
             intercom::stream_request(buffer_sizes::inbound::BLOCKS, logger.clone());
         let block_box = self.channels.block_box.clone();
-        send_message(block_box, BlockMsg::NetworkBlocks(handle), logger).await?;
+        send_message(block_box, BlockMsg::NetworkBlocks(handle, None), logger).await?;
         try_join!(
             stream
                 .and_then(|block| async { block.decode() })
@@ -198,6 +220,7 @@ impl BlockService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "block_events");
+        self.admit_subscriber(addr, &logger).await?;
         let subscriber = Address::new(addr).unwrap();
 
         self.global_state
@@ -223,8 +246,28 @@ impl FragmentService for NodeService {
     type GetFragmentsStream = ResponseStream<app_data::Fragment>;
     type SubscriptionStream = SubscriptionStream<FragmentSubscription>;
 
-    async fn get_fragments(&self, _ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
-        Err(net_error::Error::unimplemented())
+    async fn get_fragments(&self, ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
+        let mut ids = ids.decode()?;
+        let logger = self.logger().new(o!("request" => "GetFragments"));
+        if ids.len() > MAX_FRAGMENTS_PER_REQUEST {
+            debug!(
+                logger,
+                "too many fragments requested, only serving the first {}",
+                MAX_FRAGMENTS_PER_REQUEST;
+                "requested" => ids.len(),
+            );
+            ids.truncate(MAX_FRAGMENTS_PER_REQUEST);
+        }
+        let (handle, stream) =
+            intercom::stream_reply(buffer_sizes::outbound::FRAGMENTS, logger.clone());
+        let transaction_box = self.channels.transaction_box.clone();
+        send_message(
+            transaction_box,
+            TransactionMsg::GetFragments(ids, handle),
+            logger,
+        )
+        .await?;
+        Ok(convert::response_stream(stream))
     }
 
     async fn fragment_subscription(
@@ -234,6 +277,7 @@ impl FragmentService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "fragments");
+        self.admit_subscriber(addr, &logger).await?;
         let subscriber = Address::new(addr).unwrap();
 
         self.global_state.spawn(subscription::process_fragments(
@@ -264,6 +308,7 @@ impl GossipService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "gossip");
+        self.admit_subscriber(addr, &logger).await?;
         let subscriber = Address::new(addr).unwrap();
 
         self.global_state.spawn(subscription::process_gossip(
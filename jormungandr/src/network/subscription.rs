@@ -1,18 +1,20 @@
 use super::{
     buffer_sizes,
-    convert::Decode,
-    p2p::{Address, Gossip},
+    convert::{encode_fragment_ids, Decode},
+    p2p::{Address, FragmentOutcome, FragmentVerdict, Gossip},
     GlobalStateR,
 };
 use crate::{
     blockcfg::Fragment,
-    intercom::{BlockMsg, TransactionMsg},
+    fragment::Submitted,
+    intercom::{self, BlockMsg, ReplyFuture, TransactionMsg},
     settings::start::network::Configuration,
     utils::async_msg::{self, MessageBox},
 };
 use chain_network::data as net_data;
 use chain_network::error::{Code, Error};
-use jormungandr_lib::interfaces::FragmentOrigin;
+use jormungandr_lib::interfaces::FragmentAdmission;
+use poldercast::StrikeReason;
 
 use futures::future::BoxFuture;
 use futures::prelude::*;
@@ -24,6 +26,10 @@ use std::mem;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+/// the maximum number of missing fragments requested at once from a peer
+/// for the fragments it sent
+const MAX_MISSING_FRAGMENTS: usize = 256;
+
 fn filter_gossip_node(node: &Gossip, config: &Configuration) -> bool {
     if config.allow_private_addresses {
         node.has_valid_address()
@@ -32,6 +38,35 @@ fn filter_gossip_node(node: &Gossip, config: &Configuration) -> bool {
     }
 }
 
+/// Report a peer that went over the fragment limits to the p2p policy
+/// and drop the connections with it.
+async fn report_fragment_limits_exceeded(
+    global_state: GlobalStateR,
+    node_id: Address,
+    logger: Logger,
+) {
+    let counters = global_state
+        .fragment_limits
+        .on_reported(&node_id)
+        .unwrap_or_default();
+    info!(
+        logger,
+        "peer went over the fragment limits, reporting it";
+        "accepted" => counters.accepted,
+        "duplicates" => counters.duplicates,
+        "rejected" => counters.rejected,
+        "invalid" => counters.invalid,
+        "rate_limited" => counters.rate_limited,
+    );
+    future::join(
+        global_state
+            .topology
+            .report_node(node_id.clone(), StrikeReason::InvalidData),
+        global_state.peers.remove_peer(node_id),
+    )
+    .await;
+}
+
 fn handle_mbox_error(err: async_msg::SendError, logger: &Logger) -> Error {
     error!(
         logger,
@@ -199,6 +234,60 @@ impl FragmentProcessor {
         // timestamp matters.
         self.pending_processing.start(fut);
     }
+
+    fn report_limits_exceeded(&self) {
+        self.global_state.spawn(report_fragment_limits_exceeded(
+            self.global_state.clone(),
+            self.node_id.clone(),
+            self.logger.clone(),
+        ));
+    }
+
+    /// Account the admission results of the fragments sent to the
+    /// fragment task once they are received. The fragments rejected for
+    /// spending the outputs of transactions this node has never seen are
+    /// requested again from the peer, after these transactions.
+    fn account_submissions(&self, reply_future: ReplyFuture<Vec<Submitted>>) {
+        let global_state = self.global_state.clone();
+        let node_id = self.node_id.clone();
+        let logger = self.logger.clone();
+        self.global_state.spawn(async move {
+            let submissions = match reply_future.await {
+                Ok(submissions) => submissions,
+                Err(e) => {
+                    debug!(logger, "failed to get the admission results of fragments"; "reason" => %e);
+                    return;
+                }
+            };
+            let mut missing = Vec::new();
+            for submitted in submissions.iter() {
+                if !submitted.unknown_inputs.is_empty() && missing.len() < MAX_MISSING_FRAGMENTS {
+                    missing.extend(submitted.unknown_inputs.iter().cloned());
+                    missing.push(submitted.fragment_id);
+                }
+            }
+            let outcomes = submissions
+                .iter()
+                .map(|submitted| match submitted.admission {
+                    FragmentAdmission::Accepted => FragmentOutcome::Accepted,
+                    FragmentAdmission::AlreadyKnown => FragmentOutcome::Duplicate,
+                    FragmentAdmission::Rejected { .. } if submitted.invalid_witness => {
+                        FragmentOutcome::Invalid
+                    }
+                    FragmentAdmission::Rejected { .. } => FragmentOutcome::Rejected,
+                });
+            if global_state.fragment_limits.on_processed(&node_id, outcomes) {
+                report_fragment_limits_exceeded(global_state.clone(), node_id, logger).await;
+            } else if !missing.is_empty() {
+                debug!(logger, "requesting the fragments spent by the received fragments";
+                    "count" => missing.len());
+                global_state
+                    .peers
+                    .solicit_fragments(node_id, encode_fragment_ids(&missing))
+                    .await;
+            }
+        });
+    }
 }
 
 pub struct GossipProcessor {
@@ -245,6 +334,9 @@ impl Sink<net_data::Header> for BlockAnnouncementProcessor {
             e
         })?;
         let node_id = self.node_id.clone();
+        self.global_state
+            .peer_scores
+            .on_announcement(&node_id, header.hash());
         self.mbox
             .start_send(BlockMsg::AnnouncedBlock(header, node_id))
             .map_err(|e| handle_mbox_error(e, &self.logger))?;
@@ -302,9 +394,19 @@ impl Sink<net_data::Fragment> for FragmentProcessor {
                 "failed to decode incoming fragment";
                 "reason" => %e.source().unwrap(),
             );
+            let limits = &self.global_state.fragment_limits;
+            if limits.on_processed(&self.node_id, Some(FragmentOutcome::Invalid)) {
+                self.report_limits_exceeded();
+            }
             e
         })?;
-        self.buffered_fragments.push(fragment);
+        match self.global_state.fragment_limits.on_received(&self.node_id) {
+            FragmentVerdict::Accept => self.buffered_fragments.push(fragment),
+            FragmentVerdict::Drop => {
+                trace!(self.logger, "dropping fragment, the peer is over its rate");
+            }
+            FragmentVerdict::Report => self.report_limits_exceeded(),
+        }
         Ok(())
     }
 
@@ -354,11 +456,9 @@ impl FragmentProcessor {
             &mut self.buffered_fragments,
             Vec::with_capacity(buffer_sizes::inbound::FRAGMENTS),
         );
+        let (reply_handle, reply_future) = intercom::unary_reply(self.logger.clone());
         self.mbox
-            .start_send(TransactionMsg::SendTransaction(
-                FragmentOrigin::Network,
-                fragments,
-            ))
+            .start_send(TransactionMsg::ReceiveFragments(fragments, reply_handle))
             .map_err(|e| {
                 error!(
                     self.logger,
@@ -367,6 +467,7 @@ impl FragmentProcessor {
                 );
                 Error::new(Code::Internal, e)
             })?;
+        self.account_submissions(reply_future);
         self.refresh_stat();
         Poll::Ready(Ok(()))
     }
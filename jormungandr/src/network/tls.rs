@@ -0,0 +1,431 @@
+//! TLS of the gRPC connections between the nodes.
+//!
+//! The nodes are identified by the public key of their certificate rather
+//! than by a certificate authority: the certificates are usually self-signed
+//! and the peers are reached by IP address. A connection to a peer with no
+//! public key pinned is encrypted, but the certificate of the peer is not
+//! checked and a warning is logged. A connection to a trusted peer with a pinned public key fails
+//! unless the peer presents a certificate with this public key. Likewise,
+//! the listening node can require the connecting peers to present a
+//! certificate with one of a set of public keys (mutual TLS).
+//!
+//! A public key pin is the hex encoded SHA-256 hash of the DER encoded
+//! `SubjectPublicKeyInfo` of the certificate.
+
+use cryptoxide::{digest::Digest, sha2::Sha256};
+use rustls::{
+    internal::pemfile, Certificate, ClientCertVerified, ClientCertVerifier, ClientConfig,
+    DistinguishedNames, NoClientAuth, PrivateKey, RootCertStore, ServerCertVerified,
+    ServerCertVerifier, ServerConfig, TLSError,
+};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use slog::Logger;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufReader},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// the name the nodes present in the TLS handshake, the certificates are
+/// not checked against it
+pub const TLS_DOMAIN_NAME: &str = "jormungandr";
+
+const ALPN_H2: &[u8] = b"h2";
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("cannot read the certificate file {path}")]
+    CertificateFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("no certificate found in {0}")]
+    NoCertificate(PathBuf),
+    #[error("cannot read the private key file {path}")]
+    PrivateKeyFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("no PKCS8 or RSA private key found in {0}")]
+    NoPrivateKey(PathBuf),
+    #[error("invalid certificate or private key")]
+    InvalidCertificate(#[source] TLSError),
+}
+
+/// the SHA-256 hash of the public key of a certificate
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PublicKeyPin([u8; 32]);
+
+impl PublicKeyPin {
+    /// the pin of the public key of the given DER encoded certificate
+    pub fn from_certificate(certificate: &[u8]) -> Option<Self> {
+        let public_key = subject_public_key_info(certificate)?;
+        let mut hasher = Sha256::new();
+        hasher.input(public_key);
+        let mut pin = [0; 32];
+        hasher.result(&mut pin);
+        Some(PublicKeyPin(pin))
+    }
+}
+
+impl FromStr for PublicKeyPin {
+    type Err = hex::FromHexError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pin = [0; 32];
+        hex::decode_to_slice(s, &mut pin)?;
+        Ok(PublicKeyPin(pin))
+    }
+}
+
+impl fmt::Display for PublicKeyPin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for PublicKeyPin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PublicKeyPin")
+            .field(&hex::encode(self.0))
+            .finish()
+    }
+}
+
+impl Serialize for PublicKeyPin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PublicKeyPin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+/// the TLS configurations of the connections of the node
+pub struct TlsContext {
+    server: ServerConfig,
+    client: ClientConfig,
+    pinned_clients: HashMap<SocketAddr, ClientConfig>,
+    logger: Logger,
+}
+
+impl TlsContext {
+    /// load the certificate and the private key of the node. `peer_pins`
+    /// are the public keys expected from the peers at the given addresses,
+    /// `client_pins` the public keys the connecting peers must present, if
+    /// not empty.
+    pub fn load(
+        cert_file: &Path,
+        priv_key_file: &Path,
+        peer_pins: &[(SocketAddr, PublicKeyPin)],
+        client_pins: &[PublicKeyPin],
+        logger: Logger,
+    ) -> Result<Self, TlsError> {
+        let certificates = load_certificates(cert_file)?;
+        let private_key = load_private_key(priv_key_file)?;
+
+        let client_verifier: Arc<dyn ClientCertVerifier> = if client_pins.is_empty() {
+            NoClientAuth::new()
+        } else {
+            Arc::new(PinnedClientVerifier {
+                pins: client_pins.to_vec(),
+            })
+        };
+        let mut server = ServerConfig::new(client_verifier);
+        server
+            .set_single_cert(certificates.clone(), private_key.clone())
+            .map_err(TlsError::InvalidCertificate)?;
+        server.set_protocols(&[ALPN_H2.to_vec()]);
+
+        let client_config = |verifier: Arc<dyn ServerCertVerifier>| {
+            let mut client = ClientConfig::new();
+            client.dangerous().set_certificate_verifier(verifier);
+            client.set_single_client_cert(certificates.clone(), private_key.clone());
+            client.set_protocols(&[ALPN_H2.to_vec()]);
+            client
+        };
+
+        Ok(TlsContext {
+            server,
+            // the verifier of the unpinned peers is set for each connection
+            client: client_config(Arc::new(PinnedPeerVerifier { pin: None })),
+            pinned_clients: peer_pins
+                .iter()
+                .map(|(address, pin)| {
+                    let verifier = PinnedPeerVerifier { pin: Some(*pin) };
+                    (*address, client_config(Arc::new(verifier)))
+                })
+                .collect(),
+            logger,
+        })
+    }
+
+    pub fn server_config(&self) -> ServerConfig {
+        self.server.clone()
+    }
+
+    /// the configuration of the connections to the peer at the given address
+    pub fn client_config(&self, address: SocketAddr) -> ClientConfig {
+        match self.pinned_clients.get(&address) {
+            Some(client) => client.clone(),
+            None => {
+                let mut client = self.client.clone();
+                client
+                    .dangerous()
+                    .set_certificate_verifier(Arc::new(UnpinnedPeerVerifier {
+                        address,
+                        logger: self.logger.clone(),
+                    }));
+                client
+            }
+        }
+    }
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let file = File::open(path).map_err(|source| TlsError::CertificateFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    let certificates = pemfile::certs(&mut BufReader::new(file))
+        .map_err(|()| TlsError::NoCertificate(path.to_owned()))?;
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_owned()));
+    }
+    Ok(certificates)
+}
+
+type ReadKeys = fn(&mut dyn io::BufRead) -> Result<Vec<PrivateKey>, ()>;
+
+fn load_private_key(path: &Path) -> Result<PrivateKey, TlsError> {
+    let read_keys = |read: ReadKeys| -> Result<Vec<PrivateKey>, TlsError> {
+        let file = File::open(path).map_err(|source| TlsError::PrivateKeyFile {
+            path: path.to_owned(),
+            source,
+        })?;
+        Ok(read(&mut BufReader::new(file)).unwrap_or_default())
+    };
+    read_keys(pemfile::pkcs8_private_keys)?
+        .into_iter()
+        .chain(read_keys(pemfile::rsa_private_keys)?)
+        .next()
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_owned()))
+}
+
+fn check_pins(presented_certs: &[Certificate], pins: &[PublicKeyPin]) -> Result<(), TLSError> {
+    let pin = presented_certs
+        .first()
+        .and_then(|certificate| PublicKeyPin::from_certificate(&certificate.0))
+        .ok_or(TLSError::NoCertificatesPresented)?;
+    if pins.contains(&pin) {
+        Ok(())
+    } else {
+        Err(TLSError::General(format!(
+            "the public key {} of the peer is not pinned",
+            pin
+        )))
+    }
+}
+
+struct PinnedPeerVerifier {
+    pin: Option<PublicKeyPin>,
+}
+
+impl ServerCertVerifier for PinnedPeerVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        if let Some(pin) = self.pin {
+            check_pins(presented_certs, &[pin])?;
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// accepts any certificate, logging a warning at every connection
+struct UnpinnedPeerVerifier {
+    address: SocketAddr,
+    logger: Logger,
+}
+
+impl ServerCertVerifier for UnpinnedPeerVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let public_key = presented_certs
+            .first()
+            .and_then(|certificate| PublicKeyPin::from_certificate(&certificate.0))
+            .map_or_else(|| "unknown".to_owned(), |pin| pin.to_string());
+        warn!(
+            self.logger,
+            "the public key of the peer is not pinned, its certificate is not checked";
+            "address" => %self.address,
+            "public_key" => public_key
+        );
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+struct PinnedClientVerifier {
+    pins: Vec<PublicKeyPin>,
+}
+
+impl ClientCertVerifier for PinnedClientVerifier {
+    fn client_auth_root_subjects(
+        &self,
+        _sni: Option<&webpki::DNSName>,
+    ) -> Option<DistinguishedNames> {
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        _sni: Option<&webpki::DNSName>,
+    ) -> Result<ClientCertVerified, TLSError> {
+        check_pins(presented_certs, &self.pins)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// the DER encoded `SubjectPublicKeyInfo` of a DER encoded X.509
+/// certificate
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let certificate = der_element(certificate)?.contents;
+    let mut tbs_certificate = der_element(certificate)?.contents;
+    // the version is optional and explicitly tagged
+    let version = der_element(tbs_certificate)?;
+    if version.tag == 0xa0 {
+        tbs_certificate = version.rest;
+    }
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        tbs_certificate = der_element(tbs_certificate)?.rest;
+    }
+    Some(der_element(tbs_certificate)?.element)
+}
+
+struct DerElement<'a> {
+    tag: u8,
+    element: &'a [u8],
+    contents: &'a [u8],
+    rest: &'a [u8],
+}
+
+fn der_element(input: &[u8]) -> Option<DerElement> {
+    let tag = *input.get(0)?;
+    let first_length_byte = *input.get(1)?;
+    let (length, header_length) = if first_length_byte < 0x80 {
+        (first_length_byte as usize, 2)
+    } else {
+        let length_bytes = (first_length_byte & 0x7f) as usize;
+        if length_bytes == 0 || length_bytes > 4 {
+            return None;
+        }
+        let length = input
+            .get(2..2 + length_bytes)?
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, 2 + length_bytes)
+    };
+    let end = header_length.checked_add(length)?;
+    Some(DerElement {
+        tag,
+        element: input.get(..end)?,
+        contents: input.get(header_length..end)?,
+        rest: input.get(end..)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a self-signed P-256 certificate
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBgTCCASegAwIBAgIUNEQpUF7HHXE24UfbC/OjNd/Ufj4wCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLam9ybXVuZ2FuZHIwHhcNMjYxMDE4MTM1NTMzWhcNMzYxMDE1
+MTM1NTMzWjAWMRQwEgYDVQQDDAtqb3JtdW5nYW5kcjBZMBMGByqGSM49AgEGCCqG
+SM49AwEHA0IABMUk3NYHYMPgyTxncRxOaM/Ej2p6QPFKLFEfGGbFTUddGN++9Jhy
+fNT5tiy2oPW4RYdeDSb9XqBYBwKl+Of2mICjUzBRMB0GA1UdDgQWBBSwE1TQU66Q
+BuArBEZFpXKpbgHCnzAfBgNVHSMEGDAWgBSwE1TQU66QBuArBEZFpXKpbgHCnzAP
+BgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQCd1Es0BVKbZYv4K51Q
+UFXSspmXYwcgOfMndwXCCzxhTgIgD1abyfmh7A35i3RFqU82gnSZhGh6UIrGQkrs
+cDBNLyk=
+-----END CERTIFICATE-----
+";
+
+    /// the SHA-256 hash of the `SubjectPublicKeyInfo` of the certificate,
+    /// as computed by `openssl x509 -pubkey | openssl pkey -pubin -outform der`
+    const PIN: &str = "c82e3a9334b4a7121e049c6ebb8bc3446dc28232697e8bd9efcf9e3d7be81bd1";
+
+    fn certificate() -> Vec<u8> {
+        let mut certificates = pemfile::certs(&mut CERTIFICATE.as_bytes()).unwrap();
+        certificates.remove(0).0
+    }
+
+    #[test]
+    fn pin_of_a_certificate() {
+        let pin = PublicKeyPin::from_certificate(&certificate()).unwrap();
+        assert_eq!(pin, PIN.parse().unwrap());
+        assert_eq!(pin.to_string(), PIN);
+    }
+
+    #[test]
+    fn truncated_certificate_is_refused() {
+        let certificate = certificate();
+        for length in 0..certificate.len() {
+            assert!(PublicKeyPin::from_certificate(&certificate[..length]).is_none());
+        }
+    }
+
+    #[test]
+    fn oversized_length_is_refused() {
+        // a length larger than the input
+        let mut certificate = certificate();
+        certificate[2] = 0xff;
+        certificate[3] = 0xff;
+        assert!(PublicKeyPin::from_certificate(&certificate).is_none());
+
+        // the largest length of 4 bytes, and lengths of more than 4 bytes
+        assert!(der_element(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00]).is_none());
+        assert!(der_element(&[0x30, 0x85, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00]).is_none());
+        // an indefinite length
+        assert!(der_element(&[0x30, 0x80, 0x00, 0x00]).is_none());
+    }
+
+    #[test]
+    fn der_element_is_split() {
+        let element = der_element(&[0x02, 0x01, 0x05, 0x04, 0x00]).unwrap();
+        assert_eq!(element.tag, 0x02);
+        assert_eq!(element.element, &[0x02, 0x01, 0x05]);
+        assert_eq!(element.contents, &[0x05]);
+        assert_eq!(element.rest, &[0x04, 0x00]);
+
+        let contents = [0xaa; 0x81];
+        let mut input = vec![0x04, 0x81, 0x81];
+        input.extend_from_slice(&contents);
+        let element = der_element(&input).unwrap();
+        assert_eq!(element.contents, &contents[..]);
+        assert!(element.rest.is_empty());
+    }
+}
@@ -1,18 +1,20 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::{
+    blockcfg::HeaderHash,
     blockchain::{Blockchain, Tip},
     diagnostic::Diagnostic,
     intercom::{NetworkMsg, TransactionMsg},
-    leadership::Logs as LeadershipLogs,
+    leadership::{Enclave as LeadershipEnclave, Logs as LeadershipLogs},
     network::GlobalStateR as NetworkStateR,
     rest::ServerStopper,
     secure::enclave::Enclave,
     stats_counter::StatsCounter,
     utils::async_msg::MessageBox,
 };
-use jormungandr_lib::interfaces::NodeState;
+use jormungandr_lib::interfaces::{LeadershipScheduleEntry, NodeState};
 
+use lru::LruCache;
 use slog::Logger;
 use tokio::sync::RwLock;
 
@@ -120,6 +122,44 @@ pub struct FullContext {
     pub transaction_task: MessageBox<TransactionMsg>,
     pub leadership_logs: LeadershipLogs,
     pub enclave: Enclave,
+    /// the enclave the leadership task evaluates the leaders with, which may
+    /// be a remote signer
+    pub leadership_enclave: LeadershipEnclave,
+    pub leadership_schedules: LeadershipSchedules,
     pub network_state: NetworkStateR,
     pub explorer: Option<crate::explorer::Explorer>,
 }
+
+/// number of leadership schedules kept in the cache
+const LEADERSHIP_SCHEDULES_CAPACITY: usize = 4;
+
+/// the epoch of a schedule and the last block before the epoch its
+/// leadership is derived from, if any
+type LeadershipScheduleKey = (u32, Option<HeaderHash>);
+
+/// A cache of the leadership schedules of whole epochs, so each schedule is
+/// only evaluated once. It is cleared when the leaders change.
+#[derive(Clone)]
+pub struct LeadershipSchedules(
+    Arc<Mutex<LruCache<LeadershipScheduleKey, Arc<Vec<LeadershipScheduleEntry>>>>>,
+);
+
+impl LeadershipSchedules {
+    pub fn new() -> Self {
+        LeadershipSchedules(Arc::new(Mutex::new(LruCache::new(
+            LEADERSHIP_SCHEDULES_CAPACITY,
+        ))))
+    }
+
+    pub fn get(&self, key: &LeadershipScheduleKey) -> Option<Arc<Vec<LeadershipScheduleEntry>>> {
+        self.0.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: LeadershipScheduleKey, schedule: Arc<Vec<LeadershipScheduleEntry>>) {
+        self.0.lock().unwrap().put(key, schedule);
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
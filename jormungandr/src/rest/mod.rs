@@ -4,7 +4,7 @@ pub mod context;
 pub mod explorer;
 pub mod v0;
 
-pub use self::context::{Context, ContextLock, FullContext};
+pub use self::context::{Context, ContextLock, FullContext, LeadershipSchedules};
 
 use crate::settings::start::{Rest, Tls};
 
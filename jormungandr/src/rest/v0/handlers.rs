@@ -37,6 +37,60 @@ pub async fn post_message(
         .map_err(warp::reject::custom)
 }
 
+pub async fn post_fragments(
+    fragments_hex: Vec<String>,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::post_fragments(&context, &fragments_hex)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+#[derive(Deserialize)]
+pub struct GetFragmentStatusesQuery {
+    id: String,
+}
+
+pub async fn get_fragment_statuses(
+    query: GetFragmentStatusesQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_fragment_statuses(&context, &query.id)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+/// number of mempool entries returned when no limit is given
+const MEMPOOL_DEFAULT_LIMIT: usize = 100;
+/// maximum number of mempool entries returned at once
+const MEMPOOL_MAX_LIMIT: usize = 1_000;
+
+#[derive(Deserialize)]
+pub struct GetMempoolQuery {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+pub async fn get_mempool(
+    query: GetMempoolQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    let offset = query.offset.unwrap_or(0);
+    let limit = query
+        .limit
+        .unwrap_or(MEMPOOL_DEFAULT_LIMIT)
+        .min(MEMPOOL_MAX_LIMIT);
+    logic::get_mempool(&context, offset, limit)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_tip(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_tip(&context).await.map_err(warp::reject::custom)
@@ -151,6 +205,22 @@ pub async fn get_leaders_logs(context: ContextLock) -> Result<impl Reply, Reject
         .map_err(warp::reject::custom)
 }
 
+#[derive(Deserialize)]
+pub struct GetLeadersScheduleQuery {
+    epoch: Option<u32>,
+}
+
+pub async fn get_leaders_schedule(
+    query: GetLeadersScheduleQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    logic::get_leaders_schedule(&context, query.epoch)
+        .await
+        .map_err(warp::reject::custom)?
+        .map(|r| warp::reply::json(&*r))
+        .ok_or(warp::reject::not_found())
+}
+
 pub async fn get_stake_pools(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_stake_pools(&context)
@@ -266,6 +336,14 @@ pub async fn get_network_p2p_view_topic(
         .map_err(warp::reject::custom)
 }
 
+pub async fn get_network_p2p_scores(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_network_p2p_scores(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_committees(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_committees(&context)
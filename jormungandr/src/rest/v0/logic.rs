@@ -9,7 +9,7 @@
 use crate::{
     diagnostic::Diagnostic,
     intercom::{self, NetworkMsg, TransactionMsg},
-    rest::Context,
+    rest::{Context, ContextLock},
     secure::NodeSecret,
 };
 use chain_core::property::{Block as _, Deserialize, FromStr, Serialize};
@@ -20,24 +20,31 @@ use chain_crypto::{
 use chain_impl_mockchain::{
     account::{AccountAlg, Identifier},
     block::Block as ChainBlock,
+    chaintypes::ConsensusVersion,
     fragment::{Fragment, FragmentId},
     key::Hash,
-    leadership::{Leader, LeadershipConsensus},
+    leadership::{Leader, Leadership, LeadershipConsensus},
     transaction::Transaction,
     value::{Value, ValueError},
 };
 use chain_storage::Error as StorageError;
+use chain_time::{
+    era::{EpochPosition, EpochSlotOffset},
+    Epoch,
+};
 use jormungandr_lib::{
     interfaces::{
         AccountState, EnclaveLeaderId, EpochRewardsInfo, FragmentLog, FragmentOrigin,
-        LeadershipLog, NodeStats, NodeStatsDto, PeerStats, Rewards as StakePoolRewards,
-        SettingsDto, StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde,
-        TransactionOutput, VotePlanSerializableHelper,
+        FragmentSubmission, LeadershipLog, LeadershipScheduleEntry, MempoolInfo, NodeStats,
+        NodeStatsDto, PeerScore, PeerStats, Rewards as StakePoolRewards, SettingsDto,
+        StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde, TransactionOutput,
+        VotePlanSerializableHelper,
     },
     time::SystemTime,
 };
 
 use std::sync::Arc;
+use tokio::task::spawn_blocking;
 
 use futures::{channel::mpsc::SendError, channel::mpsc::TrySendError, prelude::*};
 
@@ -69,6 +76,14 @@ pub enum Error {
     Storage(#[from] StorageError),
     #[error("Invalid topic")]
     InvalidTopic,
+    #[error(transparent)]
+    Hex(#[from] hex::FromHexError),
+    #[error(transparent)]
+    Enclave(#[from] crate::leadership::EnclaveError),
+    #[error(transparent)]
+    Blocking(#[from] tokio::task::JoinError),
+    #[error("Too many fragment ids, at most {max} can be queried at once")]
+    TooManyFragmentIds { max: usize },
 }
 
 fn parse_account_id(id_hex: &str) -> Result<Identifier, Error> {
@@ -126,6 +141,88 @@ pub async fn post_message(context: &Context, message: &[u8]) -> Result<(), Error
     Ok(())
 }
 
+pub async fn post_fragments(
+    context: &Context,
+    fragments_hex: &[String],
+) -> Result<Vec<FragmentSubmission>, Error> {
+    let fragments = fragments_hex
+        .iter()
+        .map(|fragment_hex| {
+            let bytes = hex::decode(fragment_hex)?;
+            Fragment::deserialize(bytes.as_slice()).map_err(Error::Deserialize)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let logger = context.logger()?.new(o!("request" => "post_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::SubmitTransactions(
+        FragmentOrigin::Rest,
+        fragments,
+        reply_handle,
+    ))
+    .await
+    .map_err(|e| {
+        debug!(&logger, "error submitting fragments"; "reason" => %e);
+        Error::MsgSendError(e)
+    })?;
+    reply_future.await.map_err(Into::into)
+}
+
+/// maximum number of fragments whose statuses are queried at once
+const FRAGMENT_STATUSES_MAX_IDS: usize = 1_000;
+
+/// get the logs of the fragments given as a comma separated list of
+/// hex-encoded ids
+pub async fn get_fragment_statuses(
+    context: &Context,
+    fragment_ids_hex: &str,
+) -> Result<Vec<FragmentLog>, Error> {
+    let fragment_ids_hex: Vec<_> = fragment_ids_hex
+        .split(',')
+        .filter(|id_hex| !id_hex.is_empty())
+        .collect();
+    if fragment_ids_hex.len() > FRAGMENT_STATUSES_MAX_IDS {
+        return Err(Error::TooManyFragmentIds {
+            max: FRAGMENT_STATUSES_MAX_IDS,
+        });
+    }
+    let fragment_ids = fragment_ids_hex
+        .into_iter()
+        .map(parse_fragment_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    let logger = context.logger()?.new(o!("request" => "fragment_statuses"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetStatuses(fragment_ids, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting fragment statuses"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
+pub async fn get_mempool(
+    context: &Context,
+    offset: usize,
+    limit: usize,
+) -> Result<MempoolInfo, Error> {
+    let logger = context.logger()?.new(o!("request" => "mempool"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetMempool {
+        offset,
+        limit,
+        reply_handle,
+    })
+    .await
+    .map_err(|e| {
+        debug!(&logger, "error getting mempool"; "reason" => %e);
+        Error::MsgSendError(e)
+    })?;
+    reply_future.await.map_err(Into::into)
+}
+
 pub async fn get_tip(context: &Context) -> Result<String, Error> {
     Ok(context.blockchain_tip()?.get_ref().await.hash().to_string())
 }
@@ -389,7 +486,9 @@ pub async fn post_leaders(context: &Context, secret: NodeSecret) -> Result<Encla
         bft_leader: secret.bft(),
         genesis_leader: secret.genesis(),
     };
-    let leader_id = context.try_full()?.enclave.add_leader(leader).await;
+    let full_context = context.try_full()?;
+    let leader_id = full_context.enclave.add_leader(leader).await;
+    full_context.leadership_schedules.clear();
     Ok(leader_id)
 }
 
@@ -397,7 +496,9 @@ pub async fn delete_leaders(
     context: &Context,
     leader_id: EnclaveLeaderId,
 ) -> Result<Option<()>, Error> {
-    let removed = context.try_full()?.enclave.remove_leader(leader_id).await;
+    let full_context = context.try_full()?;
+    let removed = full_context.enclave.remove_leader(leader_id).await;
+    full_context.leadership_schedules.clear();
 
     if removed {
         Ok(Some(()))
@@ -410,6 +511,81 @@ pub async fn get_leaders_logs(context: &Context) -> Result<Vec<LeadershipLog>, E
     Ok(context.try_full()?.leadership_logs.logs().await)
 }
 
+/// Evaluate the leadership of the leaders of the node over all the slots of
+/// the given epoch, the epoch of the tip by default. The schedule of the
+/// next epoch is only known once its stake distribution is final, with the
+/// genesis praos consensus.
+///
+/// The context is only locked to get the tip and the enclave. The schedule
+/// is evaluated on the blocking threads and cached, since it is the same
+/// for all the requests until the leaders change.
+pub async fn get_leaders_schedule(
+    context: &ContextLock,
+    epoch: Option<u32>,
+) -> Result<Option<Arc<Vec<LeadershipScheduleEntry>>>, Error> {
+    let (enclave, schedules, tip) = {
+        let context = context.read().await;
+        let full_context = context.try_full()?;
+        (
+            full_context.leadership_enclave.clone(),
+            full_context.leadership_schedules.clone(),
+            context.blockchain_tip()?.get_ref().await,
+        )
+    };
+    let tip_epoch = tip.block_date().epoch;
+    let epoch = epoch.unwrap_or(tip_epoch);
+    let previous = tip.last_ref_previous_epoch().map(Arc::clone);
+
+    let leadership = if epoch == tip_epoch {
+        Arc::clone(tip.epoch_leadership_schedule())
+    } else if epoch == tip_epoch + 1
+        && tip.ledger().consensus_version() == ConsensusVersion::GenesisPraos
+    {
+        // the leadership of the next epoch is evaluated with the stake
+        // distribution at the end of the previous epoch
+        match &previous {
+            Some(previous) => Arc::new(Leadership::new(epoch, &previous.ledger())),
+            None => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    let key = (epoch, previous.map(|previous| previous.hash()));
+    if let Some(schedule) = schedules.get(&key) {
+        return Ok(Some(schedule));
+    }
+
+    let schedule = spawn_blocking(move || {
+        let era = leadership.era();
+        let time_frame = tip.time_frame();
+        let events = futures::executor::block_on(enclave.query_schedules(
+            Arc::clone(&leadership),
+            0,
+            era.slots_per_epoch(),
+        ))?;
+        let schedule = events
+            .into_iter()
+            .filter_map(|event| {
+                let slot = era.from_era_to_slot(EpochPosition {
+                    epoch: Epoch(event.date.epoch),
+                    slot: EpochSlotOffset(event.date.slot_id),
+                });
+                let time = time_frame.slot_to_systemtime(slot)?;
+                Some(LeadershipScheduleEntry {
+                    enclave_leader_id: event.id,
+                    scheduled_at_date: event.date.into(),
+                    scheduled_at_time: time.into(),
+                })
+            })
+            .collect();
+        Ok::<_, Error>(Arc::new(schedule))
+    })
+    .await??;
+    schedules.insert(key, Arc::clone(&schedule));
+    Ok(Some(schedule))
+}
+
 pub async fn get_stake_pools(context: &Context) -> Result<Vec<String>, Error> {
     Ok(context
         .blockchain_tip()?
@@ -631,6 +807,27 @@ pub async fn get_network_p2p_view_topic(
     Ok(view.peers)
 }
 
+pub async fn get_network_p2p_scores(context: &Context) -> Result<Vec<PeerScore>, Error> {
+    Ok(context
+        .try_full()?
+        .network_state
+        .peer_scores()
+        .scores()
+        .into_iter()
+        .map(|score| PeerScore {
+            address: score.address.to_string(),
+            score: score.score,
+            useful: score.counters.useful,
+            invalid: score.counters.invalid,
+            announcements: score.counters.announcements,
+            first_announcements: score.counters.first_announcements,
+            announcement_delay: score
+                .announcement_delay
+                .map(|delay| std::time::Duration::from_millis(delay.as_millis() as u64).into()),
+        })
+        .collect())
+}
+
 pub async fn get_committees(context: &Context) -> Result<Vec<String>, Error> {
     Ok(context
         .blockchain_tip()?
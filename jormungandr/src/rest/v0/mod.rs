@@ -48,6 +48,33 @@ pub fn filter(
         .and_then(handlers::get_message_logs)
         .boxed();
 
+    let fragments = {
+        let root = warp::path!("fragments" / ..);
+
+        let post = warp::path::end()
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(with_context.clone())
+            .and_then(handlers::post_fragments)
+            .boxed();
+
+        let status = warp::path!("status")
+            .and(warp::get())
+            .and(warp::query())
+            .and(with_context.clone())
+            .and_then(handlers::get_fragment_statuses)
+            .boxed();
+
+        root.and(post.or(status)).boxed()
+    };
+
+    let mempool = warp::path!("mempool")
+        .and(warp::get())
+        .and(warp::query())
+        .and(with_context.clone())
+        .and_then(handlers::get_mempool)
+        .boxed();
+
     let leaders = {
         let root = warp::path!("leaders" / ..).boxed();
 
@@ -70,13 +97,21 @@ pub fn filter(
             .and_then(handlers::get_leaders_logs)
             .boxed();
 
+        let schedule = warp::path!("schedule")
+            .and(warp::get())
+            .and(warp::query())
+            .and(with_context.clone())
+            .and_then(handlers::get_leaders_schedule)
+            .boxed();
+
         let delete = warp::path!(u32)
             .and(warp::delete())
             .and(with_context.clone())
             .and_then(handlers::delete_leaders)
             .boxed();
 
-        root.and(get.or(post).or(logs).or(delete)).boxed()
+        root.and(get.or(post).or(logs).or(schedule).or(delete))
+            .boxed()
     };
 
     let p2p = {
@@ -100,6 +135,12 @@ pub fn filter(
             .and_then(handlers::get_network_p2p_available)
             .boxed();
 
+        let scores = warp::path!("scores")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_network_p2p_scores)
+            .boxed();
+
         let view = {
             let root = warp::path!("view" / ..);
 
@@ -118,7 +159,7 @@ pub fn filter(
             root.and(view.or(view_topic)).boxed()
         };
 
-        root.and(quarantined.or(non_public).or(available).or(view))
+        root.and(quarantined.or(non_public).or(available).or(scores).or(view))
             .boxed()
     };
 
@@ -239,6 +280,8 @@ pub fn filter(
         .or(account)
         .or(block)
         .or(fragment)
+        .or(fragments)
+        .or(mempool)
         .or(leaders)
         .or(network)
         .or(settings)
@@ -261,9 +304,11 @@ pub fn filter(
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
     if let Some(err) = err.find::<logic::Error>() {
         let (body, code) = match err {
-            logic::Error::PublicKey(_) | logic::Error::Hash(_) => {
-                (err.to_string(), StatusCode::BAD_REQUEST)
-            }
+            logic::Error::PublicKey(_)
+            | logic::Error::Hash(_)
+            | logic::Error::Hex(_)
+            | logic::Error::Deserialize(_)
+            | logic::Error::TooManyFragmentIds { .. } => (err.to_string(), StatusCode::BAD_REQUEST),
             err => (
                 display_internal_server_error(err),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -2,12 +2,66 @@ use crate::blockcfg::{
     BlockDate, HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
     HeaderSetConsensusSignature, SlotId,
 };
-use chain_impl_mockchain::leadership::{Leader, LeaderOutput, Leadership};
-use jormungandr_lib::interfaces::EnclaveLeaderId as LeaderId;
+use crate::secure::remote;
+use async_trait::async_trait;
+use chain_impl_mockchain::{
+    certificate::PoolId,
+    leadership::{Leader, LeaderOutput, Leadership},
+};
+use jormungandr_lib::{
+    crypto::kes::{self, KesError, KesPeriods},
+    interfaces::EnclaveLeaderId as LeaderId,
+};
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use thiserror::Error;
 use tokio::sync::RwLock;
 
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error(transparent)]
+    Kes(#[from] KesError),
+    #[error("the remote signer failed: {0}")]
+    Remote(#[from] remote::Error),
+}
+
+/// The operations of an enclave holding the leaders' secrets: evaluating
+/// the leadership schedule and signing the block headers.
+#[async_trait]
+pub trait EnclaveBackend: Send + Sync {
+    /// the leadership events of the leaders of the enclave over the given
+    /// slots, ordered by date
+    async fn leadership_evaluate(
+        &self,
+        leadership: &Leadership,
+        slot_start: u32,
+        nb_slots: u32,
+    ) -> Result<Vec<LeaderEvent>, SigningError>;
+
+    /// Returns `None` if the leader is not a BFT leader of the enclave.
+    async fn create_header_bft(
+        &self,
+        header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
+        id: LeaderId,
+    ) -> Option<HeaderBft>;
+
+    /// Sign the header with the KES key of the genesis praos leader,
+    /// evolving the key to the given period first.
+    ///
+    /// Returns `Ok(None)` if the leader is not a genesis praos leader of
+    /// the enclave.
+    async fn create_header_genesis_praos(
+        &self,
+        header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
+        id: LeaderId,
+        kes_period: u32,
+    ) -> Result<Option<HeaderGenesisPraos>, SigningError>;
+
+    /// the periods of the KES key of the given leader, `Ok(None)` if it is
+    /// not a genesis praos leader of the enclave
+    async fn kes_periods(&self, id: LeaderId) -> Result<Option<KesPeriods>, SigningError>;
+}
+
 #[derive(Default)]
 struct EnclaveLeadersWithCache {
     leaders: BTreeMap<LeaderId, Leader>,
@@ -138,15 +192,28 @@ impl Enclave {
         })
     }
 
-    pub async fn leadership_evaluate(
+    /// the stake pool of the given leader, if it is a genesis praos leader
+    pub async fn node_id(&self, id: LeaderId) -> Option<PoolId> {
+        let leaders = &self.leaders_data.read().await.leaders;
+        leaders
+            .get(&id)?
+            .genesis_leader
+            .as_ref()
+            .map(|genesis_leader| genesis_leader.node_id.clone())
+    }
+}
+
+#[async_trait]
+impl EnclaveBackend for Enclave {
+    async fn leadership_evaluate(
         &self,
         leadership: &Leadership,
         slot_start: u32,
         nb_slots: u32,
-    ) -> Vec<LeaderEvent> {
+    ) -> Result<Vec<LeaderEvent>, SigningError> {
         let leaders = &self.leaders_data.read().await.leaders;
         if leaders.len() == 0 {
-            return vec![];
+            return Ok(vec![]);
         }
 
         let mut output = Vec::new();
@@ -166,39 +233,53 @@ impl Enclave {
                 }
             }
         }
-        output
+        Ok(output)
     }
 
-    pub async fn create_header_genesis_praos(
+    async fn create_header_bft(
         &self,
-        header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
+        header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
-    ) -> Option<HeaderGenesisPraos> {
+    ) -> Option<HeaderBft> {
         let leaders = &self.leaders_data.read().await.leaders;
         let leader = leaders.get(&id)?;
-        if let Some(genesis_leader) = &leader.genesis_leader {
+        if let Some(ref leader) = &leader.bft_leader {
             let data = header_builder.get_authenticated_data();
-            let signature = genesis_leader.sig_key.sign_slice(data);
+            let signature = leader.sig_key.sign_slice(data);
             Some(header_builder.set_signature(signature.into()))
         } else {
             None
         }
     }
 
-    pub async fn create_header_bft(
+    async fn create_header_genesis_praos(
         &self,
-        header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
+        header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
-    ) -> Option<HeaderBft> {
+        kes_period: u32,
+    ) -> Result<Option<HeaderGenesisPraos>, SigningError> {
+        let leaders = &mut self.leaders_data.write().await.leaders;
+        let genesis_leader = match leaders
+            .get_mut(&id)
+            .and_then(|leader| leader.genesis_leader.as_mut())
+        {
+            Some(genesis_leader) => genesis_leader,
+            None => return Ok(None),
+        };
+        // the key is evolved in place, the states of the past periods are
+        // not kept
+        kes::evolve(&mut genesis_leader.sig_key, kes_period)?;
+        let data = header_builder.get_authenticated_data();
+        let signature = genesis_leader.sig_key.sign_slice(data);
+        Ok(Some(header_builder.set_signature(signature.into())))
+    }
+
+    async fn kes_periods(&self, id: LeaderId) -> Result<Option<KesPeriods>, SigningError> {
         let leaders = &self.leaders_data.read().await.leaders;
-        let leader = leaders.get(&id)?;
-        if let Some(ref leader) = &leader.bft_leader {
-            let data = header_builder.get_authenticated_data();
-            let signature = leader.sig_key.sign_slice(data);
-            Some(header_builder.set_signature(signature.into()))
-        } else {
-            None
-        }
+        Ok(leaders
+            .get(&id)
+            .and_then(|leader| leader.genesis_leader.as_ref())
+            .map(|genesis_leader| kes::periods(&genesis_leader.sig_key)))
     }
 }
 
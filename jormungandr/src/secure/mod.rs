@@ -1,14 +1,18 @@
-use chain_crypto::{Blake2b256, Curve25519_2HashDH, Ed25519, PublicKey, SumEd25519_12};
+use chain_crypto::{Blake2b256, Curve25519_2HashDH, Ed25519, PublicKey, SecretKey, SumEd25519_12};
 use chain_impl_mockchain::leadership::{BftLeader, GenesisLeader};
 use jormungandr_lib::crypto::{
     hash::Hash,
     key::{Identifier, SigningKey},
+    password::{self, DecryptionError},
 };
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
+use zeroize::Zeroizing;
 
 pub mod enclave;
+#[cfg(unix)]
+pub mod remote;
 
 /// hold the node's bft secret setting
 #[derive(Clone, Deserialize)]
@@ -18,10 +22,13 @@ pub struct Bft {
 
 /// the genesis praos setting
 ///
+/// the KES key can be left out when the block headers are signed by a
+/// remote signer, see `remote::RemoteEnclave`
 #[derive(Clone, Deserialize)]
 pub struct GenesisPraos {
     node_id: Hash,
-    sig_key: SigningKey<SumEd25519_12>,
+    #[serde(default)]
+    sig_key: Option<SigningKey<SumEd25519_12>>,
     vrf_key: SigningKey<Curve25519_2HashDH>,
 }
 
@@ -56,18 +63,70 @@ pub struct NodePublic {
     pub block_publickey: PublicKey<Ed25519>,
 }
 
+/// environment variable holding the passphrase of the encrypted node secrets
+pub const PASSPHRASE_ENV_VAR: &str = "JORMUNGANDR_SECRET_PASSPHRASE";
+
 #[derive(Debug, Error)]
 pub enum NodeSecretFromFileError {
     #[error("Cannot read node's secrets: {0}")]
     Io(#[from] std::io::Error),
     #[error("Invalid Node secret file: {0}")]
     Format(#[from] serde_yaml::Error),
+    #[error("Cannot get the passphrase of the node's secrets: {0}")]
+    Passphrase(#[source] std::io::Error),
+    #[error("Cannot decrypt node's secrets: {0}")]
+    Decryption(#[from] DecryptionError),
+}
+
+/// The passphrase of the encrypted node secrets. It is read from the
+/// passphrase file if one is given, from the `JORMUNGANDR_SECRET_PASSPHRASE`
+/// environment variable if it is set or prompted for on the terminal
+/// otherwise. It is only read once, when the first encrypted secret is
+/// loaded.
+pub struct Passphrase {
+    file: Option<PathBuf>,
+    passphrase: Option<Zeroizing<String>>,
+}
+
+impl Passphrase {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Passphrase {
+            file,
+            passphrase: None,
+        }
+    }
+
+    fn get(&mut self) -> Result<&str, NodeSecretFromFileError> {
+        if self.passphrase.is_none() {
+            let passphrase = if let Some(file) = &self.file {
+                std::fs::read_to_string(file)
+                    .map(|passphrase| passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned())
+            } else if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+                Ok(passphrase)
+            } else {
+                rpassword::read_password_from_tty(Some("Passphrase of the node secrets: "))
+            };
+            let passphrase = passphrase.map_err(NodeSecretFromFileError::Passphrase)?;
+            self.passphrase = Some(Zeroizing::new(passphrase));
+        }
+        Ok(self.passphrase.as_ref().unwrap().as_str())
+    }
 }
 
 impl NodeSecret {
-    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<NodeSecret, NodeSecretFromFileError> {
-        let file = std::fs::File::open(path)?;
-        Ok(serde_yaml::from_reader(file)?)
+    /// load the node secret from a YAML file, or from a YAML file encrypted
+    /// with `jcli key encrypt-secret`
+    pub fn load_from_file<P: AsRef<Path>>(
+        path: P,
+        passphrase: &mut Passphrase,
+    ) -> Result<NodeSecret, NodeSecretFromFileError> {
+        let content = Zeroizing::new(std::fs::read(path)?);
+        let content = if password::is_encrypted(&content) {
+            password::decrypt(passphrase.get()?.as_bytes(), &content)?
+        } else {
+            content
+        };
+        Ok(serde_yaml::from_slice(&content)?)
     }
 
     pub fn bft(&self) -> Option<BftLeader> {
@@ -76,11 +135,24 @@ impl NodeSecret {
         })
     }
 
+    /// the genesis praos leader of the node. If the secret does not hold
+    /// the KES key a throwaway key is generated: the leader can still be
+    /// evaluated but it cannot sign the block headers.
     pub fn genesis(&self) -> Option<GenesisLeader> {
         self.genesis.clone().map(|genesis| GenesisLeader {
             node_id: Blake2b256::from(genesis.node_id).into(),
-            sig_key: genesis.sig_key.into_secret_key(),
+            sig_key: genesis
+                .sig_key
+                .map(SigningKey::into_secret_key)
+                .unwrap_or_else(|| SecretKey::generate(rand::rngs::OsRng)),
             vrf_key: genesis.vrf_key.into_secret_key(),
         })
     }
+
+    /// `false` if the secret holds a genesis praos leader without its KES key
+    pub fn has_kes_key(&self) -> bool {
+        self.genesis
+            .as_ref()
+            .map_or(true, |genesis| genesis.sig_key.is_some())
+    }
 }
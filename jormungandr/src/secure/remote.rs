@@ -0,0 +1,205 @@
+//! An enclave delegating the signing of the genesis praos block headers to
+//! a remote signer process holding the KES keys.
+//!
+//! The leadership is still evaluated by the node: the ledger evaluates the
+//! leadership of a leader with its VRF key, so the VRF keys and the stake
+//! pool ids remain in the node secrets. The KES keys can be left out of the
+//! node secrets, they are only known to the signer. The BFT leaders are not
+//! affected and keep signing in the node.
+
+use crate::blockcfg::{
+    HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
+    HeaderSetConsensusSignature, Leadership,
+};
+use crate::secure::enclave::{Enclave, EnclaveBackend, LeaderEvent, SigningError};
+use async_trait::async_trait;
+use chain_crypto::{Signature, SumEd25519_12};
+use jormungandr_lib::{
+    crypto::kes::KesPeriods,
+    interfaces::{
+        EnclaveLeaderId as LeaderId, SignerRequest, SignerResponse, SIGNER_PROTOCOL_VERSION,
+    },
+};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::Mutex,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot connect to the signer at {path}")]
+    Connect {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("cannot communicate with the signer")]
+    Io(#[from] io::Error),
+    #[error("invalid message from the signer")]
+    Format(#[from] serde_json::Error),
+    #[error("the signer closed the connection")]
+    Closed,
+    #[error("the signer speaks the unsupported protocol version {version}")]
+    UnsupportedVersion { version: u32 },
+    #[error("the signer refused the request: {0}")]
+    Refused(String),
+    #[error("unexpected response from the signer")]
+    UnexpectedResponse,
+    #[error("invalid signature from the signer")]
+    InvalidSignature,
+}
+
+pub struct RemoteEnclave {
+    /// the leaders evaluated by the node
+    evaluator: Enclave,
+    client: Mutex<Client>,
+}
+
+struct Client {
+    socket_path: PathBuf,
+    connection: Option<BufReader<UnixStream>>,
+}
+
+impl RemoteEnclave {
+    /// The leaders of the given enclave are evaluated by the node, the
+    /// headers of the genesis praos leaders are signed by the signer
+    /// listening on the given socket. The connection to the signer is
+    /// opened on the first request and reopened after an error.
+    pub fn new(evaluator: Enclave, socket_path: PathBuf) -> Self {
+        RemoteEnclave {
+            evaluator,
+            client: Mutex::new(Client {
+                socket_path,
+                connection: None,
+            }),
+        }
+    }
+
+    async fn request(&self, request: SignerRequest) -> Result<SignerResponse, Error> {
+        match self.client.lock().await.request(&request).await? {
+            SignerResponse::Error { reason } => Err(Error::Refused(reason)),
+            response => Ok(response),
+        }
+    }
+}
+
+#[async_trait]
+impl EnclaveBackend for RemoteEnclave {
+    async fn leadership_evaluate(
+        &self,
+        leadership: &Leadership,
+        slot_start: u32,
+        nb_slots: u32,
+    ) -> Result<Vec<LeaderEvent>, SigningError> {
+        self.evaluator
+            .leadership_evaluate(leadership, slot_start, nb_slots)
+            .await
+    }
+
+    async fn create_header_bft(
+        &self,
+        header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
+        id: LeaderId,
+    ) -> Option<HeaderBft> {
+        self.evaluator.create_header_bft(header_builder, id).await
+    }
+
+    async fn create_header_genesis_praos(
+        &self,
+        header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
+        id: LeaderId,
+        kes_period: u32,
+    ) -> Result<Option<HeaderGenesisPraos>, SigningError> {
+        let node_id = match self.evaluator.node_id(id).await {
+            Some(node_id) => node_id,
+            None => return Ok(None),
+        };
+        let request = SignerRequest::SignGenesisPraos {
+            node_id: node_id.into(),
+            kes_period,
+            data: hex::encode(header_builder.get_authenticated_data()),
+        };
+        let signature = match self.request(request).await? {
+            SignerResponse::Signature { signature } => signature,
+            _ => return Err(Error::UnexpectedResponse.into()),
+        };
+        let signature = hex::decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::<[u8], SumEd25519_12>::from_binary(&bytes).ok())
+            .ok_or(Error::InvalidSignature)?;
+        Ok(Some(header_builder.set_signature(signature.into())))
+    }
+
+    async fn kes_periods(&self, id: LeaderId) -> Result<Option<KesPeriods>, SigningError> {
+        let node_id = match self.evaluator.node_id(id).await {
+            Some(node_id) => node_id,
+            None => return Ok(None),
+        };
+        let request = SignerRequest::KesPeriods {
+            node_id: node_id.into(),
+        };
+        match self.request(request).await? {
+            SignerResponse::KesPeriods { current, remaining } => {
+                Ok(Some(KesPeriods { current, remaining }))
+            }
+            _ => Err(Error::UnexpectedResponse.into()),
+        }
+    }
+}
+
+impl Client {
+    async fn request(&mut self, request: &SignerRequest) -> Result<SignerResponse, Error> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect().await?);
+        }
+        let connection = self.connection.as_mut().unwrap();
+        let result = exchange(connection, request).await;
+        if result.is_err() {
+            // the connection may be in an unknown state, open a new one on
+            // the next request
+            self.connection = None;
+        }
+        result
+    }
+
+    async fn connect(&self) -> Result<BufReader<UnixStream>, Error> {
+        let stream = UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|source| Error::Connect {
+                path: self.socket_path.clone(),
+                source,
+            })?;
+        let mut connection = BufReader::new(stream);
+        let hello = SignerRequest::Hello {
+            version: SIGNER_PROTOCOL_VERSION,
+        };
+        match exchange(&mut connection, &hello).await? {
+            SignerResponse::Hello { version } if version == SIGNER_PROTOCOL_VERSION => {
+                Ok(connection)
+            }
+            SignerResponse::Hello { version } => Err(Error::UnsupportedVersion { version }),
+            SignerResponse::Error { reason } => Err(Error::Refused(reason)),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
+
+/// send a request and read its response, each message is a JSON object on
+/// its own line
+async fn exchange(
+    connection: &mut BufReader<UnixStream>,
+    request: &SignerRequest,
+) -> Result<SignerResponse, Error> {
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    connection.get_mut().write_all(line.as_bytes()).await?;
+
+    let mut line = String::new();
+    if connection.read_line(&mut line).await? == 0 {
+        return Err(Error::Closed);
+    }
+    Ok(serde_json::from_str(&line)?)
+}
@@ -6,6 +6,7 @@ use structopt::StructOpt;
 
 use crate::{
     blockcfg::HeaderHash,
+    blockchain::BlockSelector,
     settings::logging::{LogFormat, LogOutput},
 };
 
@@ -19,11 +20,17 @@ pub struct StartArguments {
     #[structopt(long = "config", parse(from_os_str))]
     pub node_config: Option<PathBuf>,
 
-    /// Set the secret node config (in YAML format). Can be given
-    /// multiple times.
+    /// Set the secret node config (in YAML format, or encrypted with
+    /// `jcli key encrypt-secret`). Can be given multiple times.
     #[structopt(long = "secret", parse(from_os_str))]
     pub secret: Vec<PathBuf>,
 
+    /// Read the passphrase of the encrypted secret files from the given
+    /// file. If not set, the passphrase is read from the
+    /// `JORMUNGANDR_SECRET_PASSPHRASE` environment variable or prompted for.
+    #[structopt(long = "secret-passphrase-file", parse(from_os_str))]
+    pub secret_passphrase_file: Option<PathBuf>,
+
     /// Path to the genesis block (the block0) of the blockchain
     #[structopt(long = "genesis-block", parse(try_from_str))]
     pub block_0_path: Option<PathBuf>,
@@ -54,6 +61,69 @@ pub struct RestArguments {
     pub listen: Option<SocketAddr>,
 }
 
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Do not start the node: evaluate the leadership of the secrets against
+    /// the blockchain in the storage, build and sign a candidate block
+    /// without broadcasting it and print a report in YAML.
+    DryRun {
+        /// the epoch to evaluate the leadership of, the epoch of the tip or
+        /// the following one. Defaults to the epoch of the tip.
+        epoch: Option<u32>,
+    },
+    /// Do not start the node: write the blocks of the blockchain in the
+    /// storage to an archive file.
+    Export(ExportArguments),
+    /// Do not start the node: check and add the blocks of an archive file
+    /// to the blockchain in the storage.
+    Import(ImportArguments),
+    /// Do not start the node: maintenance of the storage.
+    Storage(StorageCommand),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ExportArguments {
+    /// the first block to export, given by its hash or its chain length.
+    /// Defaults to the block following the block0.
+    #[structopt(long = "from")]
+    pub from: Option<BlockSelector>,
+
+    /// the last block to export, given by its hash or its chain length.
+    /// Defaults to the tip of the blockchain.
+    #[structopt(long = "to")]
+    pub to: Option<BlockSelector>,
+
+    /// the archive file to write
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ImportArguments {
+    /// the archive file to read
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+/// the commands run on the blockchain loaded from the storage
+#[derive(Debug)]
+pub enum ArchiveCommand {
+    Export(ExportArguments),
+    Import(ImportArguments),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum StorageCommand {
+    /// Check the integrity of the blocks of the storage, from the block0,
+    /// and print a report in YAML.
+    Check {
+        /// truncate the storage back to the last consistent block if an
+        /// inconsistency is found
+        #[structopt(long = "repair")]
+        repair: bool,
+    },
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "jormungandr",
@@ -101,6 +171,9 @@ pub struct CommandLine {
     /// this option is useful for scripting retrieving the logs of the version of this application.
     #[structopt(long = "source-version")]
     pub source_version: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
 }
 
 impl CommandLine {
@@ -1,5 +1,11 @@
 use crate::{
-    network::p2p::{layers::LayersConfig, topic, Address, PolicyConfig},
+    fragment::selection::FragmentSelectionAlgorithmParams,
+    leadership::LeaderLockConfig,
+    network::p2p::{
+        layers::LayersConfig, topic, Address, ConnectionLimitsConfig, FragmentLimitsConfig,
+        PeerScoringConfig, PolicyConfig,
+    },
+    network::tls::PublicKeyPin,
     settings::logging::{LogFormat, LogOutput},
     settings::LOG_FILTER_LEVEL_POSSIBLE_VALUES,
 };
@@ -138,6 +144,19 @@ pub struct P2pConfig {
     #[serde(default)]
     pub policy: PolicyConfig,
 
+    /// limits on the fragments received from each peer
+    #[serde(default)]
+    pub fragment_limits: FragmentLimitsConfig,
+
+    /// settings of the scoring of the peers by the blocks they deliver
+    #[serde(default)]
+    pub peer_scoring: PeerScoringConfig,
+
+    /// limits on the connections per address and subnet, and the addresses
+    /// allowed or denied to connect
+    #[serde(default)]
+    pub connection_limits: ConnectionLimitsConfig,
+
     /// settings for the different custom layers
     #[serde(default)]
     pub layers: LayersConfig,
@@ -177,6 +196,26 @@ pub struct P2pConfig {
     /// gossip with the trusted peers if any are defined.
     #[serde(default)]
     pub max_bootstrap_attempts: Option<usize>,
+
+    /// Enables TLS on the connections with the other nodes if provided.
+    /// All the nodes of the network must then enable it.
+    #[serde(default)]
+    pub tls: Option<P2pTls>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct P2pTls {
+    /// Path to the X.509 certificate chain file of the node, must be PEM-encoded
+    /// and contain at least 1 item
+    pub cert_file: PathBuf,
+    /// Path to the private key file of the node, must be a single PEM-encoded,
+    /// unencrypted PKCS8 or RSA key
+    pub priv_key_file: PathBuf,
+    /// If not empty, the connecting peers must present a certificate with
+    /// one of these public keys
+    #[serde(default)]
+    pub client_public_keys: Vec<PublicKeyPin>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -190,6 +229,11 @@ pub struct TrustedPeer {
     // TODO: to remove once we can afford having a config breaking change
     #[serde(skip, default)]
     pub id: Option<Id>,
+
+    /// If provided, the connections to this peer only succeed if it presents
+    /// a certificate with this public key. Requires `p2p.tls`.
+    #[serde(default)]
+    pub public_key: Option<PublicKeyPin>,
 }
 
 // Lifted from poldercast 0.11 for backward compatibility
@@ -232,6 +276,22 @@ pub struct Leadership {
     /// the least recently used log will be erased from the logs for a new one
     /// to be inserted.
     pub logs_capacity: usize,
+
+    /// the algorithm used to select the fragments from the mempool when
+    /// building a new block.
+    #[serde(default)]
+    pub fragment_selection: FragmentSelectionAlgorithmParams,
+
+    /// the Unix socket of the remote signer holding the KES keys of the
+    /// genesis praos leaders. If not set the block headers are signed with
+    /// the keys of the node secrets.
+    #[serde(default)]
+    pub remote_signer: Option<PathBuf>,
+
+    /// the lock shared with the other nodes running the same leaders, to
+    /// make sure only one of them signs the block of a slot
+    #[serde(default)]
+    pub lock: Option<LeaderLockConfig>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
@@ -274,11 +334,15 @@ impl Default for P2pConfig {
             max_connections_threshold: None,
             allow_private_addresses: false,
             policy: PolicyConfig::default(),
+            fragment_limits: FragmentLimitsConfig::default(),
+            peer_scoring: PeerScoringConfig::default(),
+            connection_limits: ConnectionLimitsConfig::default(),
             layers: LayersConfig::default(),
             max_unreachable_nodes_to_connect_per_event: None,
             gossip_interval: None,
             topology_force_reset_interval: None,
             max_bootstrap_attempts: None,
+            tls: None,
         }
     }
 }
@@ -287,6 +351,9 @@ impl Default for Leadership {
     fn default() -> Self {
         Leadership {
             logs_capacity: 1_024,
+            fragment_selection: FragmentSelectionAlgorithmParams::default(),
+            remote_signer: None,
+            lock: None,
         }
     }
 }
@@ -314,6 +381,7 @@ impl std::str::FromStr for TrustedPeer {
         Ok(TrustedPeer {
             address,
             id: optional_id,
+            public_key: None,
         })
     }
 }
@@ -4,11 +4,12 @@ pub mod network;
 use self::config::{Config, Leadership};
 pub use self::config::{Cors, Rest, Tls};
 use self::network::Protocol;
+use crate::network::{p2p::address_book, tls::TlsContext};
 use crate::settings::logging::{LogFormat, LogOutput, LogSettings, LogSettingsEntry};
 use crate::settings::{command_arguments::*, Block0Info};
 use jormungandr_lib::interfaces::Mempool;
 use slog::{FilterLevel, Logger};
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf, sync::Arc};
 use thiserror::Error;
 
 const DEFAULT_FILTER_LEVEL: FilterLevel = FilterLevel::Info;
@@ -26,6 +27,10 @@ pub enum Error {
     ExpectedBlock0Info,
     #[error("In the node configuration file, the `p2p.listen_address` value is not a valid address. Use format `/ip4/x.x.x.x/tcp/4920")]
     ListenAddressNotValid,
+    #[error("In the node configuration file, the `p2p.peer_scoring` section is not valid: {0}")]
+    PeerScoringNotValid(#[from] crate::network::p2p::PeerScoringConfigError),
+    #[error("Cannot load the TLS certificate or private key of `p2p.tls`")]
+    P2pTls(#[from] crate::network::tls::TlsError),
 }
 
 /// Overall Settings for node
@@ -34,12 +39,16 @@ pub struct Settings {
     pub storage: Option<PathBuf>,
     pub block_0: Block0Info,
     pub secrets: Vec<PathBuf>,
+    pub secret_passphrase_file: Option<PathBuf>,
     pub rest: Option<Rest>,
     pub mempool: Mempool,
     pub rewards_report_all: bool,
     pub leadership: Leadership,
     pub explorer: bool,
     pub no_blockchain_updates_warning_interval: std::time::Duration,
+    /// the command run instead of the node: a block production dry run,
+    /// an archive export or import, or a storage maintenance
+    pub command: Option<Command>,
 }
 
 pub struct RawSettings {
@@ -126,7 +135,7 @@ impl RawSettings {
             config,
         } = self;
         let command_arguments = &command_line.start_arguments;
-        let network = generate_network(&command_arguments, &config, &logger)?;
+        let mut network = generate_network(&command_arguments, &config, &logger)?;
 
         let storage = match (
             command_arguments.storage.as_ref(),
@@ -136,6 +145,9 @@ impl RawSettings {
             (None, Some(path)) => Some(path.clone()),
             (None, None) => None,
         };
+        network.address_book = storage
+            .as_ref()
+            .map(|storage| storage.join(address_book::ADDRESS_BOOK_FILE_NAME));
 
         let mut secrets = command_arguments.secret.clone();
         if let Some(secret_files) = config.as_ref().map(|cfg| cfg.secret_files.clone()) {
@@ -171,6 +183,7 @@ impl RawSettings {
             block_0,
             network,
             secrets,
+            secret_passphrase_file: command_arguments.secret_passphrase_file.clone(),
             rewards_report_all: command_line.rewards_report_all,
             rest,
             mempool: config
@@ -187,6 +200,7 @@ impl RawSettings {
                 .unwrap_or(std::time::Duration::from_secs(
                     DEFAULT_NO_BLOCKCHAIN_UPDATES_WARNING_INTERVAL,
                 )),
+            command: command_line.command,
         })
     }
 }
@@ -227,6 +241,7 @@ fn generate_network(
                     Some(config::TrustedPeer {
                         address,
                         id: peer.id.clone(),
+                        public_key: peer.public_key,
                     })
                 }
                 Ok(None) => {
@@ -240,6 +255,8 @@ fn generate_network(
         }).collect();
     });
 
+    p2p.peer_scoring.validate()?;
+
     let mut profile = poldercast::NodeProfileBuilder::new();
 
     if let Some(address) = p2p.public_address {
@@ -278,6 +295,9 @@ fn generate_network(
             .collect(),
         protocol: Protocol::Grpc,
         policy: p2p.policy.clone(),
+        fragment_limits: p2p.fragment_limits.clone(),
+        peer_scoring: p2p.peer_scoring.clone(),
+        connection_limits: p2p.connection_limits.clone(),
         layers: p2p.layers.clone(),
         max_connections: p2p
             .max_connections
@@ -297,8 +317,43 @@ fn generate_network(
         http_fetch_block0_service,
         bootstrap_from_trusted_peers,
         skip_bootstrap,
+        tls: None,
+        address_book: None,
     };
 
+    let peer_pins: Vec<_> = network
+        .trusted_peers
+        .iter()
+        .filter_map(|peer| {
+            let pin = peer.public_key?;
+            match multiaddr_to_socket_addr(peer.address.multi_address()) {
+                Some(address) => Some((address, pin)),
+                None => {
+                    warn!(logger, "the public key of a trusted peer cannot be pinned, its address is not a socket address"; "address" => peer.address.multi_address().to_string());
+                    None
+                }
+            }
+        })
+        .collect();
+    match &p2p.tls {
+        Some(tls) => {
+            network.tls = Some(Arc::new(TlsContext::load(
+                &tls.cert_file,
+                &tls.priv_key_file,
+                &peer_pins,
+                &tls.client_public_keys,
+                logger.clone(),
+            )?));
+        }
+        None if !peer_pins.is_empty() => {
+            warn!(
+                logger,
+                "public keys of trusted peers are ignored, p2p.tls is not enabled"
+            );
+        }
+        None => {}
+    }
+
     if network.max_inbound_connections > network.max_connections {
         warn!(
             logger,
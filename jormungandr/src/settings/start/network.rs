@@ -1,6 +1,12 @@
-use crate::network::p2p::{layers::LayersConfig, Address, PolicyConfig};
+use crate::network::{
+    p2p::{
+        layers::LayersConfig, Address, ConnectionLimitsConfig, FragmentLimitsConfig,
+        PeerScoringConfig, PolicyConfig,
+    },
+    tls::{PublicKeyPin, TlsContext},
+};
 use poldercast::NodeProfile;
-use std::{net::SocketAddr, str, time::Duration};
+use std::{net::SocketAddr, path::PathBuf, str, sync::Arc, time::Duration};
 
 /// Protocol to use for a connection.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -72,6 +78,12 @@ pub struct Configuration {
 
     pub policy: PolicyConfig,
 
+    pub fragment_limits: FragmentLimitsConfig,
+
+    pub peer_scoring: PeerScoringConfig,
+
+    pub connection_limits: ConnectionLimitsConfig,
+
     pub layers: LayersConfig,
 
     /// Whether to allow non-public IP addresses in gossip
@@ -92,17 +104,27 @@ pub struct Configuration {
     pub skip_bootstrap: bool,
 
     pub http_fetch_block0_service: Vec<String>,
+
+    /// TLS of the connections with the other nodes, if enabled
+    pub tls: Option<Arc<TlsContext>>,
+
+    /// the file of the address book of the known peers, in the storage
+    /// directory
+    pub address_book: Option<PathBuf>,
 }
 
 #[derive(Clone)]
 pub struct TrustedPeer {
     pub address: poldercast::Address,
+    /// the public key the peer must present, if pinned
+    pub public_key: Option<PublicKeyPin>,
 }
 
 impl From<super::config::TrustedPeer> for TrustedPeer {
     fn from(tp: super::config::TrustedPeer) -> Self {
         TrustedPeer {
             address: tp.address,
+            public_key: tp.public_key,
         }
     }
 }
@@ -1,11 +1,11 @@
 use crate::{
     blockcfg, blockchain,
     diagnostic::DiagnosticError,
-    explorer, network, secure,
+    explorer, leadership, network, secure,
     settings::{self, logging},
 };
 use chain_storage::Error as StorageError;
-use std::io;
+use std::{io, path::PathBuf};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -51,6 +51,10 @@ pub enum Error {
     NetworkBootstrapError(#[source] network::BootstrapError),
     #[error("Error while loading the node's secrets.")]
     NodeSecrets(#[from] secure::NodeSecretFromFileError),
+    #[error("The node secret {path} has no KES key and no remote signer is configured")]
+    MissingKesKey { path: PathBuf },
+    #[error("Cannot set up the leader lock")]
+    LeaderLock(#[from] leadership::LockError),
     #[error("Block 0 is set to start in the future")]
     Block0InFuture,
     #[error("Error while loading the explorer from storage")]
@@ -61,6 +65,18 @@ pub enum Error {
     DiagnosticError(#[from] DiagnosticError),
     #[error("Interrupted by the user")]
     Interrupted,
+    #[error("The block production dry run failed")]
+    DryRun(#[from] leadership::DryRunError),
+    #[error("Cannot format the dry run report")]
+    DryRunReport(#[source] serde_yaml::Error),
+    #[error("Error with the blockchain archive")]
+    Archive(#[from] blockchain::ArchiveError),
+    #[error("The storage check failed")]
+    StorageCheck(#[from] blockchain::StorageCheckError),
+    #[error("Cannot format the storage check report")]
+    StorageCheckReport(#[source] serde_yaml::Error),
+    #[error("Found {issues} inconsistencies in the storage, use `--repair` to repair it")]
+    StorageCorrupted { issues: usize },
 }
 
 impl From<network::BootstrapError> for Error {
@@ -87,11 +103,19 @@ impl Error {
             Error::Block0Mismatch { .. } => 7,
             Error::Block0InFuture => 7,
             Error::NodeSecrets { .. } => 8,
+            Error::MissingKesKey { .. } => 8,
+            Error::LeaderLock { .. } => 8,
             Error::FetchBlock0 { .. } => 9,
             Error::NetworkBootstrapError { .. } => 10,
             Error::ExplorerBootstrapError { .. } => 11,
             Error::ServiceTerminatedWithError => 12,
             Error::DiagnosticError { .. } => 13,
+            Error::DryRun { .. } => 14,
+            Error::DryRunReport { .. } => 14,
+            Error::Archive { .. } => 15,
+            Error::StorageCheck { .. } => 16,
+            Error::StorageCheckReport { .. } => 16,
+            Error::StorageCorrupted { .. } => 16,
         }
     }
 }
@@ -3,7 +3,10 @@ mod error;
 pub use self::error::{Error, ErrorKind};
 use crate::{
     blockcfg::{Block, HeaderId},
-    blockchain::{Blockchain, ErrorKind as BlockchainError, Storage, Tip},
+    blockchain::{
+        replace_truncated_database, Blockchain, ErrorKind as BlockchainError, Storage, Tip,
+        BLOCKSTORE_FILE_NAME,
+    },
     log, network,
     settings::start::Settings,
 };
@@ -13,7 +16,7 @@ use slog::Logger;
 pub type NodeStorage = BlockStore<Block>;
 pub type NodeStorageConnection = BlockStoreConnection<Block>;
 
-const BLOCKSTORE_BUSY_TIMEOUT: u64 = 1000;
+pub const BLOCKSTORE_BUSY_TIMEOUT: u64 = 1000;
 
 /// prepare the block storage from the given settings
 ///
@@ -30,8 +33,15 @@ pub fn prepare_storage(setting: &Settings, logger: &Logger) -> Result<Storage, E
                 source: err,
                 reason: ErrorKind::SQLite,
             })?;
+            let replaced = replace_truncated_database(dir).map_err(|err| Error::IO {
+                source: err,
+                reason: ErrorKind::SQLite,
+            })?;
+            if replaced {
+                info!(logger, "the blocks of the storage have been truncated");
+            }
             let mut sqlite = dir.clone();
-            sqlite.push("blocks.sqlite");
+            sqlite.push(BLOCKSTORE_FILE_NAME);
             info!(logger, "storing blockchain in '{:?}'", sqlite);
             BlockStoreBuilder::file(sqlite)
                 .busy_timeout(BLOCKSTORE_BUSY_TIMEOUT)
@@ -41,6 +51,7 @@ pub fn prepare_storage(setting: &Settings, logger: &Logger) -> Result<Storage, E
 
     Ok(Storage::new(
         raw_block_store,
+        setting.storage.clone(),
         logger.new(o!(log::KEY_SUB_TASK => "storage")),
     ))
 }
@@ -417,6 +417,13 @@ impl NodeController {
                         ));
                         return Ok(status);
                     }
+                    Replaced { by } => {
+                        self.progress_bar.log_info(format!(
+                            "Fragment '{}' replaced by: {}",
+                            check.fragment_id, by
+                        ));
+                        return Ok(status);
+                    }
                 }
             } else {
                 bail!(ErrorKind::FragmentNoInMemPoolLogs(